@@ -8,10 +8,10 @@ use biz_core::service::rpc_server_client_service::ArbServerRpcServiceClientServi
 use once_cell::sync::OnceCell;
 use std::str::FromStr;
 use std::sync::Arc;
-use tonic::{transport, Request, Response, Status};
+use tonic::{transport, Request, Response, Status, Streaming};
 use biz_core::kafka_util::node_util::NodeUtil;
 use biz_core::protocol::arb::arb_client::arb_client_service_server::{ArbClientService, ArbClientServiceServer};
-use biz_core::protocol::arb::arb_client::UpdateVersionReq;
+use biz_core::protocol::arb::arb_client::{SyncGroupChunk, SyncStreamResp, UpdateVersionReq};
 use biz_core::protocol::arb::arb_models::{NodeType, QueryNodeReq, RegRequest, SyncListGroup};
 use biz_core::protocol::arb::arb_models::NodeType::SocketNode;
 
@@ -22,7 +22,6 @@ impl ArbClientServiceImpl {
     pub async fn start() {
         // 读取配置文件
         let rpc_server_service = ArbServerRpcServiceClientService::get();
-        let mut client = rpc_server_service.client.lock().await;
 
         let client_addr = AppConfig::get().get_shard().clone().client_addr.unwrap();
         let client_services = Self {};
@@ -31,9 +30,12 @@ impl ArbClientServiceImpl {
             node_type: NodeType::SocketGateway as i32,
             node_addr: client_addr.clone(),
             kafka_addr: None,
+            socket_addr: None,
+            ws_addr: None,
         };
 
-        client.register_node(request).await.expect("reg socket gateway error");
+        rpc_server_service.register(request).await.expect("reg socket gateway error");
+        let mut client = rpc_server_service.client.lock().await;
 
         let response = client
             .list_all_nodes(QueryNodeReq {
@@ -95,6 +97,10 @@ impl ArbClientService for ArbClientServiceImpl {
         }))
     }
 
+    async fn sync_data_stream(&self, _request: Request<Streaming<SyncGroupChunk>>) -> Result<Response<SyncStreamResp>, Status> {
+        Err(Status::unimplemented("sync_data_stream is only served by group shard nodes"))
+    }
+
     async fn flush_nodes(&self, _: Request<()>) -> Result<Response<CommonResp>, Status> {
         let rpc_server_service = ArbServerRpcServiceClientService::get();
         let mut client = rpc_server_service.client.lock().await;
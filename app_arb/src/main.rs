@@ -1,10 +1,13 @@
 mod service;
 
+use crate::service::arb_store::ArbStore;
+use crate::service::leader_lease::LeaderLease;
+use crate::service::node_sweeper::start_node_sweeper;
 use crate::service::rpc::arb_service_impl::ArbiterServiceImpl;
 use common::config::AppConfig;
 use std::net::SocketAddr;
 use std::str::FromStr;
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tracing::log;
 use biz_core::protocol::arb::arb_server::arb_server_rpc_service_server::ArbServerRpcServiceServer;
@@ -15,8 +18,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 读取配置文件
     let app_cfg = AppConfig::get();
     let addr = SocketAddr::from_str(&app_cfg.get_shard().server_addr.unwrap())?;
-    let svc = ArbiterServiceImpl::new();
-    tonic::transport::Server::builder().add_service(ArbServerRpcServiceServer::new(svc)).serve(addr).await?;
-    log::warn!("ArbServerRpcServiceServer started");
-    Ok(())
+    let lease = Arc::new(LeaderLease::new());
+    let store = Arc::new(ArbStore::new(lease.instance_id()));
+    // 主备实例使用同一个 server_addr，需由共享 VIP 指向当前主实例（见 arb-config.toml），
+    // 备用实例在取得租约前不监听该地址
+    loop {
+        // 备用状态：等待主租约，成为主实例后从持久化存储恢复集群视图再对外服务
+        lease.wait_for_leadership().await;
+        let svc = ArbiterServiceImpl::restore(store.clone()).await?;
+        let lost = lease.clone().keep_alive();
+        let sweeper = start_node_sweeper(svc.clone());
+        let stopping = Arc::new(AtomicBool::new(false));
+        let shutdown = {
+            let stopping = stopping.clone();
+            async move {
+                tokio::select! {
+                    _ = lost.notified() => log::warn!("lost arbiter leadership, back to standby"),
+                    _ = tokio::signal::ctrl_c() => stopping.store(true, Ordering::SeqCst),
+                }
+            }
+        };
+        log::warn!("ArbServerRpcServiceServer started");
+        tonic::transport::Server::builder().add_service(ArbServerRpcServiceServer::new(svc)).serve_with_shutdown(addr, shutdown).await?;
+        sweeper.abort();
+        if stopping.load(Ordering::SeqCst) {
+            lease.release().await?;
+            log::warn!("ArbServerRpcServiceServer stopped");
+            return Ok(());
+        }
+    }
 }
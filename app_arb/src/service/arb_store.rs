@@ -0,0 +1,173 @@
+use crate::service::leader_lease::LEADER_KEY;
+use anyhow::{anyhow, Result};
+use biz_core::protocol::arb::arb_models::{NodeInfo, NodeType, ShardState};
+use common::redis::redis_pool::RedisPoolTools;
+use common::RedisPool;
+use deadpool_redis::redis::{cmd, Script};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 全局分片状态
+const SHARD_STATE_KEY: &str = "arb:shard_state";
+/// 群分片归属纪元
+const SHARD_EPOCH_KEY: &str = "arb:shard_epoch";
+
+/// 持久化的节点类型（与 `ArbiterServiceImpl` 的各节点表一一对应）
+pub const PERSISTED_NODE_TYPES: [NodeType; 6] = [
+    NodeType::GroupNode,
+    NodeType::SocketNode,
+    NodeType::SocketGateway,
+    NodeType::MsgGateway,
+    NodeType::MesGroup,
+    NodeType::MsgFriend,
+];
+
+/// 仅当本实例仍持有主租约时写入，防止失去租约的旧主覆盖新主的数据
+const GUARDED_HSET: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return redis.error_reply('arb.not.leader')
+end
+return redis.call('HSET', KEYS[2], ARGV[2], ARGV[3])
+"#;
+
+const GUARDED_HDEL: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return redis.error_reply('arb.not.leader')
+end
+return redis.call('HDEL', KEYS[2], ARGV[2])
+"#;
+
+const GUARDED_SET: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return redis.error_reply('arb.not.leader')
+end
+redis.call('SET', KEYS[2], ARGV[2])
+return 1
+"#;
+
+const GUARDED_INCR: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return redis.error_reply('arb.not.leader')
+end
+return redis.call('INCR', KEYS[2])
+"#;
+
+/// 仲裁集群视图持久化
+///
+/// - `arb:nodes:{node_type}`：哈希，node_addr → `NodeInfo`（JSON）；
+/// - `arb:shard_state`：全局分片状态；
+/// - `arb:shard_epoch`：群分片归属纪元，只增不减，主实例切换后继续递增。
+///
+/// 所有写入都校验主租约，主实例切换后由新主从 Redis 恢复，节点无需重新注册。
+#[derive(Debug)]
+pub struct ArbStore {
+    pool: Arc<RedisPool>,
+    instance_id: String,
+}
+
+impl ArbStore {
+    pub fn new(instance_id: &str) -> Self {
+        Self {
+            pool: RedisPoolTools::get().clone(),
+            instance_id: instance_id.to_string(),
+        }
+    }
+
+    fn nodes_key(node_type: i32) -> String {
+        format!("arb:nodes:{}", node_type)
+    }
+
+    /// 写入（新增或更新）节点
+    pub async fn save_node(&self, node: &NodeInfo) -> Result<()> {
+        let value = serde_json::to_string(node)?;
+        let mut conn = self.pool.get().await?;
+        let _: i64 = Script::new(GUARDED_HSET)
+            .key(LEADER_KEY)
+            .key(Self::nodes_key(node.node_type))
+            .arg(&self.instance_id)
+            .arg(&node.node_addr)
+            .arg(value)
+            .invoke_async(&mut conn)
+            .await
+            .map_err(not_leader)?;
+        Ok(())
+    }
+
+    /// 删除节点
+    pub async fn remove_node(&self, node_type: i32, node_addr: &str) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        let _: i64 = Script::new(GUARDED_HDEL)
+            .key(LEADER_KEY)
+            .key(Self::nodes_key(node_type))
+            .arg(&self.instance_id)
+            .arg(node_addr)
+            .invoke_async(&mut conn)
+            .await
+            .map_err(not_leader)?;
+        Ok(())
+    }
+
+    /// 写入全局分片状态
+    pub async fn save_shard_state(&self, state: ShardState) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        let _: i64 = Script::new(GUARDED_SET)
+            .key(LEADER_KEY)
+            .key(SHARD_STATE_KEY)
+            .arg(&self.instance_id)
+            .arg(state as i32)
+            .invoke_async(&mut conn)
+            .await
+            .map_err(not_leader)?;
+        Ok(())
+    }
+
+    /// 分配下一个群分片归属纪元（在 Redis 中原子递增，跨主实例单调）
+    pub async fn next_shard_epoch(&self) -> Result<u64> {
+        let mut conn = self.pool.get().await?;
+        let epoch: u64 = Script::new(GUARDED_INCR)
+            .key(LEADER_KEY)
+            .key(SHARD_EPOCH_KEY)
+            .arg(&self.instance_id)
+            .invoke_async(&mut conn)
+            .await
+            .map_err(not_leader)?;
+        Ok(epoch)
+    }
+
+    /// 读取全部已持久化节点，无法解析的记录跳过
+    pub async fn load_nodes(&self) -> Result<Vec<NodeInfo>> {
+        let mut conn = self.pool.get().await?;
+        let mut nodes = Vec::new();
+        for node_type in PERSISTED_NODE_TYPES {
+            let entries: HashMap<String, String> = cmd("HGETALL").arg(Self::nodes_key(node_type as i32)).query_async(&mut conn).await?;
+            for (node_addr, value) in entries {
+                match serde_json::from_str::<NodeInfo>(&value) {
+                    Ok(node) => nodes.push(node),
+                    Err(e) => tracing::log::warn!("跳过无法解析的节点记录 {:?} {}: {:?}", node_type, node_addr, e),
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// 读取全局分片状态，未持久化过时返回 None
+    pub async fn load_shard_state(&self) -> Result<Option<ShardState>> {
+        let mut conn = self.pool.get().await?;
+        let state: Option<i32> = cmd("GET").arg(SHARD_STATE_KEY).query_async(&mut conn).await?;
+        Ok(state.and_then(|s| ShardState::try_from(s).ok()))
+    }
+
+    /// 读取群分片归属纪元，未分配过时为 0
+    pub async fn load_shard_epoch(&self) -> Result<u64> {
+        let mut conn = self.pool.get().await?;
+        let epoch: Option<u64> = cmd("GET").arg(SHARD_EPOCH_KEY).query_async(&mut conn).await?;
+        Ok(epoch.unwrap_or(0))
+    }
+}
+
+fn not_leader(e: deadpool_redis::redis::RedisError) -> anyhow::Error {
+    match e.detail() {
+        Some(detail) if detail.contains("arb.not.leader") => anyhow!("arb.not.leader"),
+        _ => anyhow!(e),
+    }
+}
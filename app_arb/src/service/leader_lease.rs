@@ -0,0 +1,142 @@
+use anyhow::Result;
+use common::config::AppConfig;
+use common::redis::redis_pool::RedisPoolTools;
+use common::RedisPool;
+use deadpool_redis::redis::{cmd, Script};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{self, Instant};
+use tracing::log;
+
+/// 主租约键，值为持有者实例标识
+pub const LEADER_KEY: &str = "arb:leader";
+/// 默认租约时长（毫秒）
+const DEFAULT_LEASE_TTL_MS: u64 = 10_000;
+
+/// 已持有则续期，否则在无人持有时抢占
+const ACQUIRE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    redis.call('PEXPIRE', KEYS[1], ARGV[2])
+    return 1
+end
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return 1
+end
+return 0
+"#;
+
+/// 仅释放自己持有的租约
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+/// 仲裁主备选举：基于 Redis 租约
+///
+/// 备实例每隔 1/3 租约时长尝试抢占；主实例同频率续期，续期失败或超过租约时长未能续期即视为失去主身份，
+/// 停止对外服务并退回备用状态。
+#[derive(Debug)]
+pub struct LeaderLease {
+    pool: Arc<RedisPool>,
+    instance_id: String,
+    ttl_ms: u64,
+}
+
+impl LeaderLease {
+    pub fn new() -> Self {
+        let app_cfg = AppConfig::get();
+        let arb_cfg = app_cfg.get_arb();
+        let instance_id = arb_cfg
+            .instance_id
+            .unwrap_or_else(|| format!("{}-{}", app_cfg.get_shard().server_addr.unwrap_or_default(), std::process::id()));
+        Self {
+            pool: RedisPoolTools::get().clone(),
+            instance_id,
+            ttl_ms: arb_cfg.lease_ttl_ms.unwrap_or(DEFAULT_LEASE_TTL_MS).max(1_000),
+        }
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    fn renew_interval(&self) -> Duration {
+        Duration::from_millis(self.ttl_ms / 3)
+    }
+
+    /// 抢占或续期租约，返回当前是否为主
+    pub async fn try_acquire(&self) -> Result<bool> {
+        let mut conn = self.pool.get().await?;
+        let acquired: i64 = Script::new(ACQUIRE_SCRIPT).key(LEADER_KEY).arg(&self.instance_id).arg(self.ttl_ms).invoke_async(&mut conn).await?;
+        Ok(acquired == 1)
+    }
+
+    /// 当前主实例标识
+    pub async fn current_leader(&self) -> Result<Option<String>> {
+        let mut conn = self.pool.get().await?;
+        Ok(cmd("GET").arg(LEADER_KEY).query_async(&mut conn).await?)
+    }
+
+    /// 阻塞直到成为主实例
+    pub async fn wait_for_leadership(&self) {
+        let mut interval = time::interval(self.renew_interval());
+        let mut announced = false;
+        loop {
+            interval.tick().await;
+            match self.try_acquire().await {
+                Ok(true) => {
+                    log::warn!("👑 仲裁实例 {} 成为主实例", self.instance_id);
+                    return;
+                }
+                Ok(false) => {
+                    if !announced {
+                        let leader = self.current_leader().await.ok().flatten().unwrap_or_default();
+                        log::warn!("⏳ 仲裁实例 {} 进入备用状态，当前主实例: {}", self.instance_id, leader);
+                        announced = true;
+                    }
+                }
+                Err(e) => log::error!("❌ 抢占主租约失败: {:?}", e),
+            }
+        }
+    }
+
+    /// 启动续期任务，失去主身份时通知返回的 `Notify`
+    pub fn keep_alive(self: Arc<Self>) -> Arc<Notify> {
+        let lost = Arc::new(Notify::new());
+        let notify = lost.clone();
+        tokio::spawn(async move {
+            let ttl = Duration::from_millis(self.ttl_ms);
+            let mut last_renewed = Instant::now();
+            let mut interval = time::interval(self.renew_interval());
+            loop {
+                interval.tick().await;
+                match self.try_acquire().await {
+                    Ok(true) => last_renewed = Instant::now(),
+                    Ok(false) => {
+                        log::error!("❌ 仲裁实例 {} 的主租约已被其他实例持有", self.instance_id);
+                        break;
+                    }
+                    Err(e) => {
+                        log::warn!("⚠️ 主租约续期失败: {:?}", e);
+                        if last_renewed.elapsed() >= ttl {
+                            log::error!("❌ 仲裁实例 {} 超过租约时长未能续期", self.instance_id);
+                            break;
+                        }
+                    }
+                }
+            }
+            notify.notify_one();
+        });
+        lost
+    }
+
+    /// 主动释放租约（优雅退出时），备实例可立即接管
+    pub async fn release(&self) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        let _: i64 = Script::new(RELEASE_SCRIPT).key(LEADER_KEY).arg(&self.instance_id).invoke_async(&mut conn).await?;
+        Ok(())
+    }
+}
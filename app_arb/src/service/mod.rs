@@ -1 +1,4 @@
+pub mod arb_store;
+pub mod leader_lease;
+pub mod node_sweeper;
 pub mod rpc;
@@ -0,0 +1,94 @@
+use crate::service::arb_store::PERSISTED_NODE_TYPES;
+use crate::service::rpc::arb_service_impl::ArbiterServiceImpl;
+use biz_core::protocol::arb::arb_models::{NodeType, ShardState};
+use common::util::date_util::now;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::log;
+
+/// 扫描间隔
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 各节点类型的失联判定：(判定失联, 判定下线) 距最后一次心跳的毫秒数
+///
+/// 客户端心跳间隔为 10 秒，失联判定至少容忍两次心跳丢失。
+fn timeouts(node_type: NodeType) -> (u64, u64) {
+    match node_type {
+        // 分片节点下线后其群组由环上后继节点接管，下线判定更保守
+        NodeType::GroupNode => (30_000, 120_000),
+        // socket 节点承载客户端长连接，尽快摘除以便客户端重连到其他节点
+        NodeType::SocketNode => (25_000, 60_000),
+        _ => (30_000, 90_000),
+    }
+}
+
+/// 启动失联检测任务：超时未心跳的节点先标记为 Failed（从节点列表中摘除），
+/// 继续超时则标记为 Offline 并移出节点表；每次变更都通知关注该类型的节点刷新节点列表。
+pub fn start_node_sweeper(service: ArbiterServiceImpl) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            for node_type in PERSISTED_NODE_TYPES {
+                sweep(&service, node_type).await;
+            }
+        }
+    })
+}
+
+async fn sweep(service: &ArbiterServiceImpl, node_type: NodeType) {
+    let Some(registry) = service.registry(node_type as i32) else {
+        return;
+    };
+    let (failed_after, offline_after) = timeouts(node_type);
+    let now = now() as u64;
+    let mut failed = Vec::new();
+    let mut offline = Vec::new();
+    for entry in registry.iter() {
+        let silent = now.saturating_sub(entry.last_update_time);
+        if silent > offline_after {
+            offline.push(entry.key().clone());
+        } else if silent > failed_after && entry.state != ShardState::Failed as i32 {
+            failed.push(entry.key().clone());
+        }
+    }
+    if failed.is_empty() && offline.is_empty() {
+        return;
+    }
+
+    let before = (node_type == NodeType::GroupNode).then(|| service.shard_ring());
+    // 先持久化再更新节点表，持久化失败（如已失去主租约）的节点留待下一轮扫描
+    for node_addr in &failed {
+        let Some(mut node) = registry.get(node_addr).map(|entry| entry.clone()) else {
+            continue;
+        };
+        node.state = ShardState::Failed as i32;
+        node.version += 1;
+        if let Err(e) = service.store.save_node(&node).await {
+            log::error!("失联状态持久化失败 {}: {:?}", node_addr, e);
+            continue;
+        }
+        if let Some(mut entry) = registry.get_mut(node_addr) {
+            entry.state = node.state;
+            entry.version = node.version;
+        }
+        log::warn!("节点失联: {:?} {}", node_type, node_addr);
+    }
+    for node_addr in &offline {
+        if let Err(e) = service.store.remove_node(node_type as i32, node_addr).await {
+            log::error!("下线节点删除失败 {}: {:?}", node_addr, e);
+            continue;
+        }
+        if let Some((_, node)) = registry.remove(node_addr) {
+            log::warn!("节点下线: {:?} {} (最后心跳 {})", node_type, node_addr, node.last_update_time);
+        }
+    }
+    if let Some(before) = before {
+        // 失联分片节点的群组按新环迁移到后继节点，分配新纪元
+        if let Err(e) = service.advance_shard_epoch(&before).await {
+            log::error!("分片纪元推进失败: {:?}", e);
+        }
+    }
+    service.notify_node_types(ArbiterServiceImpl::interested_types(node_type), None);
+}
@@ -1,11 +1,12 @@
 use anyhow::__private::not;
+use common::config::AppConfig;
 use common::errors::AppError;
 use common::util::date_util::now;
+use common::util::hash_ring::{HashRing, DEFAULT_VIRTUAL_NODES};
 use dashmap::DashMap;
 use std::clone;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::io::Empty;
 use tonic::transport::Channel;
 use tonic::{Code, IntoRequest, Request, Response, Status};
@@ -15,12 +16,13 @@ use biz_core::protocol::arb::arb_client::UpdateVersionReq;
 use biz_core::protocol::arb::arb_models::{BaseRequest, ListAllNodesResponse, NodeInfo, NodeType, QueryNodeReq, RegRequest, ShardState, UpdateShardStateRequest};
 use biz_core::protocol::arb::arb_server::arb_server_rpc_service_server::ArbServerRpcService;
 use biz_core::protocol::common::CommonResp;
+use crate::service::arb_store::ArbStore;
 
 /// 分片节点状态信息
 /// 表示某个 vnode 当前的版本号、状态、归属节点及上次更新时间
 
 /// ArbiterService 实现体，持有共享状态引用
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct ArbiterServiceImpl {
     pub shard_nodes: Arc<DashMap<String, NodeInfo>>,
     pub global_shard_state: Arc<tokio::sync::RwLock<ShardState>>,
@@ -29,10 +31,14 @@ pub struct ArbiterServiceImpl {
     pub msg_gateway_nodes: Arc<DashMap<String, NodeInfo>>,
     pub msg_group_nodes: Arc<DashMap<String, NodeInfo>>,
     pub msg_friend_nodes: Arc<DashMap<String, NodeInfo>>,
+    /// 集群视图持久化，节点表与全局分片状态的每次变更都写穿
+    pub store: Arc<ArbStore>,
+    /// 群分片归属纪元：分片节点成员（环）每变化一次加一，分片节点与调用方据此判断归属视图新旧
+    pub shard_epoch: Arc<AtomicU64>,
 }
 
 impl ArbiterServiceImpl {
-    pub fn new() -> Self {
+    pub fn new(store: Arc<ArbStore>) -> Self {
         Self {
             shard_nodes: Arc::new(DashMap::new()),
             global_shard_state: Arc::new(tokio::sync::RwLock::new(ShardState::Normal)),
@@ -41,8 +47,64 @@ impl ArbiterServiceImpl {
             msg_gateway_nodes: Arc::new(DashMap::new()),
             msg_group_nodes: Arc::new(DashMap::new()),
             msg_friend_nodes: Arc::new(DashMap::new()),
+            store,
+            shard_epoch: Arc::new(AtomicU64::new(0)),
         }
     }
+
+    /// 成为主实例后从持久化存储恢复集群视图
+    ///
+    /// 恢复的节点心跳时间重置为当前时间，给节点重新上报心跳的宽限，避免被立即判定失联。
+    pub async fn restore(store: Arc<ArbStore>) -> anyhow::Result<Self> {
+        let service = Self::new(store);
+        let now = now() as u64;
+        let nodes = service.store.load_nodes().await?;
+        let count = nodes.len();
+        for mut node in nodes {
+            let Some(registry) = service.registry(node.node_type) else {
+                continue;
+            };
+            node.last_update_time = now;
+            registry.insert(node.node_addr.clone(), node);
+        }
+        if let Some(state) = service.store.load_shard_state().await? {
+            *service.global_shard_state.write().await = state;
+        }
+        service.shard_epoch.store(service.store.load_shard_epoch().await?, Ordering::Release);
+        log::warn!(
+            "已恢复集群视图: {} 个节点, 全局分片状态 {:?}, 分片纪元 {}",
+            count,
+            *service.global_shard_state.read().await,
+            service.shard_epoch.load(Ordering::Acquire)
+        );
+        Ok(service)
+    }
+
+    /// 节点类型对应的节点表
+    pub(crate) fn registry(&self, node_type: i32) -> Option<&Arc<DashMap<String, NodeInfo>>> {
+        match NodeType::try_from(node_type).ok()? {
+            NodeType::GroupNode => Some(&self.shard_nodes),
+            NodeType::SocketNode => Some(&self.socket_nodes),
+            NodeType::SocketGateway => Some(&self.socket_gateway_nodes),
+            NodeType::MsgGateway => Some(&self.msg_gateway_nodes),
+            NodeType::MesGroup => Some(&self.msg_group_nodes),
+            NodeType::MsgFriend => Some(&self.msg_friend_nodes),
+            _ => None,
+        }
+    }
+
+    /// 持久化节点，失去主租约或写入失败时拒绝本次请求
+    async fn persist_node(&self, node: &NodeInfo) -> Result<(), Status> {
+        self.store.save_node(node).await.map_err(|e| Status::unavailable(e.to_string()))
+    }
+
+    async fn forget_node(&self, node_type: i32, node_addr: &str) -> Result<(), Status> {
+        self.store.remove_node(node_type, node_addr).await.map_err(|e| Status::unavailable(e.to_string()))
+    }
+
+    async fn persist_shard_state(&self, state: ShardState) -> Result<(), Status> {
+        self.store.save_shard_state(state).await.map_err(|e| Status::unavailable(e.to_string()))
+    }
     pub async fn init_socket_clients(&self) -> Result<Vec<ArbClientServiceClient<Channel>>, Box<dyn std::error::Error + Send + Sync>> {
         let mut endpoints = Vec::new();
         for ref node in self.socket_nodes.iter() {
@@ -59,16 +121,8 @@ impl ArbiterServiceImpl {
     }
     pub async fn init_clients(&self, node_type: NodeType) -> Result<Vec<ArbClientServiceClient<Channel>>, Box<dyn std::error::Error + Send + Sync>> {
         let mut endpoints = Vec::new();
-        let arc = match node_type {
-            NodeType::GroupNode => self.shard_nodes.clone(),
-            NodeType::SocketNode => self.socket_nodes.clone(),
-            NodeType::SocketGateway => self.socket_gateway_nodes.clone(),
-            NodeType::MsgGateway => self.msg_gateway_nodes.clone(),
-            NodeType::MesGroup => self.msg_group_nodes.clone(),
-            NodeType::MsgFriend => self.msg_friend_nodes.clone(),
-            _ => {
-                return Err(AppError::BizError("Invalid node type".to_string()).into());
-            }
+        let Some(arc) = self.registry(node_type as i32) else {
+            return Err(AppError::BizError("Invalid node type".to_string()).into());
         };
 
         for ref node in arc.iter() {
@@ -83,6 +137,125 @@ impl ArbiterServiceImpl {
         }
         Ok(clients)
     }
+
+    /// 配置的一致性哈希虚拟节点数（`[shard] virtual_nodes`），随节点列表及纪元推送下发
+    pub fn virtual_nodes() -> u32 {
+        AppConfig::get().get_shard().virtual_nodes.unwrap_or(DEFAULT_VIRTUAL_NODES).max(1) as u32
+    }
+
+    /// 当前群分片节点构成的一致性哈希环（失联节点不参与）
+    pub fn shard_ring(&self) -> HashRing {
+        HashRing::from_arb(Self::virtual_nodes(), self.shard_nodes.iter().filter(|node| node.state != ShardState::Failed as i32).map(|node| node.key().clone()))
+    }
+
+    /// 输出群分片成员变更后归属发生变化的哈希区间
+    pub(crate) fn report_shard_moves(before: &HashRing, after: &HashRing) {
+        let moves = before.diff(after);
+        log::warn!("群分片节点变更: {} → {} 个节点，{} 段区间迁移", before.len(), after.len(), moves.len());
+        for item in &moves {
+            log::info!(
+                "  [{:#018x}, {:#018x}] {} → {}",
+                item.range.start,
+                item.range.end,
+                item.from.as_deref().unwrap_or("-"),
+                item.to.as_deref().unwrap_or("-")
+            );
+        }
+    }
+
+    /// 群分片成员变更后分配新的归属纪元并推送给各分片节点，返回当前纪元
+    ///
+    /// 成员集合未变化（如已失联的节点再被移出节点表）时沿用原纪元。
+    pub(crate) async fn advance_shard_epoch(&self, before: &HashRing) -> Result<u64, Status> {
+        let after = self.shard_ring();
+        if before.nodes().eq(after.nodes()) {
+            return Ok(self.shard_epoch.load(Ordering::Acquire));
+        }
+        Self::report_shard_moves(before, &after);
+        let epoch = self.store.next_shard_epoch().await.map_err(|e| Status::unavailable(e.to_string()))?;
+        self.shard_epoch.fetch_max(epoch, Ordering::AcqRel);
+        log::warn!("群分片纪元推进到 {}", epoch);
+        self.notify_shard_epoch(epoch);
+        Ok(epoch)
+    }
+
+    /// 异步向各分片节点推送新纪元（`UpdateVersionReq.version`），节点只接受比本地更大的纪元
+    fn notify_shard_epoch(&self, epoch: u64) {
+        let total = self.shard_nodes.len() as i32;
+        let requests: Vec<UpdateVersionReq> = self
+            .shard_nodes
+            .iter()
+            .filter(|node| node.state != ShardState::Failed as i32)
+            .map(|node| UpdateVersionReq {
+                node_addr: node.node_addr.clone(),
+                version: epoch,
+                state: node.state,
+                last_update_time: now() as u64,
+                total,
+                virtual_nodes: Self::virtual_nodes(),
+            })
+            .collect();
+        tokio::spawn(async move {
+            for request in requests {
+                let endpoint = request.node_addr.clone();
+                let result = async {
+                    let channel = Channel::from_shared(format!("http://{}", endpoint))?.connect().await?;
+                    ArbClientServiceClient::new(channel).update_version(request).await?;
+                    Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+                }
+                .await;
+                if let Err(e) = result {
+                    log::warn!("推送分片纪元 {} 到节点 {} 失败: {:?}", epoch, endpoint, e);
+                }
+            }
+        });
+    }
+
+    /// 节点变更时需要刷新节点列表的节点类型
+    pub(crate) fn interested_types(node_type: NodeType) -> &'static [NodeType] {
+        match node_type {
+            NodeType::GroupNode => &[NodeType::GroupNode, NodeType::MsgGateway, NodeType::SocketGateway, NodeType::MsgFriend, NodeType::MesGroup],
+            NodeType::SocketNode => &[NodeType::SocketNode, NodeType::MsgGateway, NodeType::SocketGateway, NodeType::GroupNode, NodeType::MsgFriend, NodeType::MesGroup],
+            NodeType::MsgGateway | NodeType::MsgFriend | NodeType::MesGroup => &[NodeType::SocketNode],
+            NodeType::SocketGateway => &[NodeType::GroupNode],
+            _ => &[],
+        }
+    }
+
+    /// 异步通知指定类型的节点刷新节点列表（排除 `except`），通知失败只记录日志
+    ///
+    /// 不阻塞调用方：新注册节点的 gRPC 服务此时可能尚未启动，失联节点也可能无法连接。
+    pub fn notify_node_types(&self, node_types: &[NodeType], except: Option<&str>) {
+        let endpoints: Vec<String> = node_types
+            .iter()
+            .filter_map(|node_type| self.registry(*node_type as i32))
+            .flat_map(|registry| {
+                registry
+                    .iter()
+                    .filter(|node| node.state != ShardState::Failed as i32 && Some(node.key().as_str()) != except)
+                    .map(|node| node.node_addr.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        tokio::spawn(async move {
+            for endpoint in endpoints {
+                let result = async {
+                    let channel = Channel::from_shared(format!("http://{}", endpoint))?.connect().await?;
+                    ArbClientServiceClient::new(channel).flush_nodes(()).await?;
+                    Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+                }
+                .await;
+                if let Err(e) = result {
+                    log::warn!("通知节点 {} 刷新失败: {:?}", endpoint, e);
+                }
+            }
+        });
+    }
+
+    /// socket 节点增减后通知其余 socket 节点刷新节点列表并迁移连接
+    pub fn notify_socket_nodes(&self, except: &str) {
+        self.notify_node_types(&[NodeType::SocketNode], Some(except));
+    }
 }
 
 #[tonic::async_trait]
@@ -94,59 +267,66 @@ impl ArbServerRpcService for ArbiterServiceImpl {
         let current_node_addr = req.node_addr.clone();
         let new_state = req.new_state;
 
-        // 更新目标节点状态
-        match self.shard_nodes.get_mut(&current_node_addr) {
-            Some(mut entry) => {
-                entry.state = new_state;
-                entry.version += 1;
-                entry.last_update_time = now() as u64;
-                if entry.node_type == NodeType::GroupNode as i32 {
-                    entry.total = self.shard_nodes.len() as i32;
-
-                    // 如果新状态是 Normal，检查所有 shard 状态
-                    if req.new_state == ShardState::Normal as i32 {
-                        // 统计是否所有 shard 节点状态为 Normal
-                        let all_normal = self.shard_nodes.iter().all(|entry| entry.value().state == ShardState::Normal as i32);
-
-                        if all_normal {
-                            // 升级 arb_version
-                            let mut global_shard_state = self.global_shard_state.write().await;
-                            *global_shard_state = ShardState::Normal;
-
-                            //通知消息网关节点有变
-                            let mut client_list = self.init_clients(NodeType::MsgGateway).await.expect("init clients error");
-                            for client in client_list.iter_mut() {
-                                client.flush_nodes(()).await?;
-                            }
-                            //通知socket网关节点有变
-                            let mut client_list = self.init_clients(NodeType::SocketGateway).await.expect("init clients error");
-                            for client in client_list.iter_mut() {
-                                client.flush_nodes(()).await?;
-                            }
-                            //通知msg_friend节点有变
-                            let mut client_list = self.init_clients(NodeType::MsgFriend).await.expect("init clients error");
-                            for client in client_list.iter_mut() {
-                                client.flush_nodes(()).await?;
-                            }
-                            //通知msg_group节点有变
-                            let mut client_list = self.init_clients(NodeType::MesGroup).await.expect("init clients error");
-                            for client in client_list.iter_mut() {
-                                client.flush_nodes(()).await?;
-                            }
-                        } else {
-                            log::info!("[ArbVersion] Node {} is Normal, but not all are Normal yet.", current_node_addr);
-                        }
+        // 在副本上更新目标节点状态，持久化成功后再写回节点表，失去主租约时内存视图保持不变
+        let before = self.shard_ring();
+        let total = self.shard_nodes.len() as i32;
+        let Some(mut node) = self.shard_nodes.get(&current_node_addr).map(|entry| entry.clone()) else {
+            return Err(Status::not_found(format!("Node {} not found.", current_node_addr)));
+        };
+        node.state = new_state;
+        node.version += 1;
+        node.last_update_time = now() as u64;
+        if node.node_type == NodeType::GroupNode as i32 {
+            node.total = total;
+        }
+        self.persist_node(&node).await?;
+        self.shard_nodes.insert(current_node_addr.clone(), node.clone());
+        if node.node_type == NodeType::GroupNode as i32 {
+            // 节点自报失败（迁移多次回滚）后被摘出环，归属随之变化
+            if new_state == ShardState::Failed as i32 {
+                self.advance_shard_epoch(&before).await?;
+            }
+
+            // 如果新状态是 Normal，检查所有 shard 状态
+            if req.new_state == ShardState::Normal as i32 {
+                // 统计是否所有 shard 节点状态为 Normal
+                let all_normal = self.shard_nodes.iter().all(|entry| entry.value().state == ShardState::Normal as i32);
+
+                if all_normal {
+                    // 升级 arb_version（写锁只在赋值时持有，不跨越持久化与通知）
+                    self.persist_shard_state(ShardState::Normal).await?;
+                    *self.global_shard_state.write().await = ShardState::Normal;
+
+                    //通知消息网关节点有变
+                    let mut client_list = self.init_clients(NodeType::MsgGateway).await.expect("init clients error");
+                    for client in client_list.iter_mut() {
+                        client.flush_nodes(()).await?;
+                    }
+                    //通知socket网关节点有变
+                    let mut client_list = self.init_clients(NodeType::SocketGateway).await.expect("init clients error");
+                    for client in client_list.iter_mut() {
+                        client.flush_nodes(()).await?;
                     }
+                    //通知msg_friend节点有变
+                    let mut client_list = self.init_clients(NodeType::MsgFriend).await.expect("init clients error");
+                    for client in client_list.iter_mut() {
+                        client.flush_nodes(()).await?;
+                    }
+                    //通知msg_group节点有变
+                    let mut client_list = self.init_clients(NodeType::MesGroup).await.expect("init clients error");
+                    for client in client_list.iter_mut() {
+                        client.flush_nodes(()).await?;
+                    }
+                } else {
+                    log::info!("[ArbVersion] Node {} is Normal, but not all are Normal yet.", current_node_addr);
                 }
-
-                Ok(Response::new(CommonResp {
-                    success: true,
-                    message: format!("Updated node {} to state {:?}, version = {}", current_node_addr, entry.state, entry.version),
-                }))
             }
-
-            None => Err(Status::not_found(format!("Node {} not found.", current_node_addr))),
         }
+
+        Ok(Response::new(CommonResp {
+            success: true,
+            message: format!("Updated node {} to state {:?}, version = {}", current_node_addr, node.state, node.version),
+        }))
     }
 
     /// 注册节点：如果 node_addr 已存在，则返回失败；否则分配唯一 index 并插入
@@ -166,13 +346,14 @@ impl ArbServerRpcService for ArbiterServiceImpl {
                     node_type: req.node_type,
                     last_update_time: node_info.last_update_time,
                     kafka_addr: req.kafka_addr,
+                    socket_addr: None,
+                    ws_addr: None,
                 }));
             }
-            let mut global_shard_state = self.global_shard_state.write().await;
-            *global_shard_state = ShardState::Ready;
-            // 当前时间戳（毫秒）
+            self.persist_shard_state(ShardState::Ready).await?;
+            *self.global_shard_state.write().await = ShardState::Ready;
             // 构建新 entry
-            let mut entry = NodeInfo {
+            let entry = NodeInfo {
                 node_addr: node_addr.clone(),
                 total: self.shard_nodes.len() as i32 + 1,
                 version: 0,
@@ -180,26 +361,31 @@ impl ArbServerRpcService for ArbiterServiceImpl {
                 state: ShardState::Preparing as i32,
                 last_update_time: now,
                 kafka_addr: req.kafka_addr,
+                socket_addr: None,
+                ws_addr: None,
             };
 
-            for (mut item) in self.shard_nodes.iter_mut() {
-                item.state = ShardState::Preparing as i32;
-                let mut client_list = self.init_clients(NodeType::GroupNode).await.expect("init shard clients error");
-                for mut client in client_list.iter_mut() {
-                    let request = Request::new(UpdateVersionReq {
-                        node_addr: item.node_addr.clone(),
-                        version: item.version,
-                        state: item.state,
-                        last_update_time: now,
-                        total: entry.total,
-                    });
-                    client.update_version(request).await.expect("update version error");
-                }
+            // 已有节点进入 Preparing：先全部持久化，再写回节点表
+            let preparing: Vec<NodeInfo> = self
+                .shard_nodes
+                .iter()
+                .map(|item| NodeInfo {
+                    state: ShardState::Preparing as i32,
+                    ..item.clone()
+                })
+                .collect();
+            for item in &preparing {
+                self.persist_node(item).await?;
             }
+            self.persist_node(&entry).await?;
 
-            // 插入
+            // 插入，并分配新纪元推送给各分片节点（各节点据此进入再均衡）
+            let before = self.shard_ring();
+            for item in preparing {
+                self.shard_nodes.insert(item.node_addr.clone(), item);
+            }
             self.shard_nodes.insert(node_addr.clone(), entry.clone());
-            entry.total = self.shard_nodes.len() as i32;
+            self.advance_shard_epoch(&before).await?;
             //打印信息
             // log::warn!("新增分片节点: {:?}", &entry);
             return Ok(Response::new(entry));
@@ -216,12 +402,13 @@ impl ArbServerRpcService for ArbiterServiceImpl {
                     node_type: req.node_type,
                     last_update_time: node_info.last_update_time,
                     kafka_addr: req.kafka_addr,
+                    socket_addr: req.socket_addr,
+                    ws_addr: req.ws_addr,
                 }));
             }
 
-            // 当前时间戳（毫秒）
             // 构建新 entry
-            let mut entry = NodeInfo {
+            let entry = NodeInfo {
                 node_addr: node_addr.clone(),
                 total: self.socket_nodes.len() as i32 + 1,
                 version: 0,
@@ -229,11 +416,13 @@ impl ArbServerRpcService for ArbiterServiceImpl {
                 state: ShardState::Normal as i32,
                 last_update_time: now,
                 kafka_addr: req.kafka_addr,
+                socket_addr: req.socket_addr,
+                ws_addr: req.ws_addr,
             };
 
-            // 插入
+            // 持久化后插入
+            self.persist_node(&entry).await?;
             self.socket_nodes.insert(node_addr.clone(), entry.clone());
-            entry.total = self.socket_nodes.len() as i32;
             let mut client_list = self.init_clients(NodeType::MsgGateway).await.expect("init clients error");
             for client in client_list.iter_mut() {
                 client.flush_nodes(()).await?;
@@ -255,13 +444,15 @@ impl ArbServerRpcService for ArbiterServiceImpl {
             for client in client_list.iter_mut() {
                 client.flush_nodes(()).await?;
             }
+            // 其余 socket 节点迁移归属新节点的连接
+            self.notify_socket_nodes(&node_addr);
             //打印信息
             // log::warn!("新增分片节点: {:?}", &entry);
             return Ok(Response::new(entry));
         }
         if req.node_type == NodeType::MsgGateway as i32 {
             // 构建新 entry
-            let mut entry = NodeInfo {
+            let entry = NodeInfo {
                 node_addr: node_addr.clone(),
                 total: self.msg_gateway_nodes.len() as i32 + 1,
                 version: 0,
@@ -269,8 +460,11 @@ impl ArbServerRpcService for ArbiterServiceImpl {
                 state: ShardState::Normal as i32,
                 last_update_time: now,
                 kafka_addr: req.kafka_addr,
+                socket_addr: None,
+                ws_addr: None,
             };
-            // 插入
+            // 持久化后插入
+            self.persist_node(&entry).await?;
             self.msg_gateway_nodes.insert(node_addr.clone(), entry.clone());
             //打印信息
             // log::warn!("新增分片节点: {:?}", &entry);
@@ -278,7 +472,7 @@ impl ArbServerRpcService for ArbiterServiceImpl {
         }
         if req.node_type == NodeType::SocketGateway as i32 {
             // 构建新 entry
-            let mut entry = NodeInfo {
+            let entry = NodeInfo {
                 node_addr: node_addr.clone(),
                 total: self.socket_gateway_nodes.len() as i32 + 1,
                 version: 0,
@@ -286,7 +480,10 @@ impl ArbServerRpcService for ArbiterServiceImpl {
                 state: ShardState::Normal as i32,
                 last_update_time: now,
                 kafka_addr: req.kafka_addr,
+                socket_addr: None,
+                ws_addr: None,
             };
+            self.persist_node(&entry).await?;
             self.socket_gateway_nodes.insert(node_addr.clone(), entry.clone());
             let mut client_list = self.init_clients(NodeType::GroupNode).await.expect("init clients error");
             for client in client_list.iter_mut() {
@@ -295,86 +492,145 @@ impl ArbServerRpcService for ArbiterServiceImpl {
             //打印信息
             return Ok(Response::new(entry));
         }
-        Err(Status::new(Code::Unknown, "未知错误"))
-    }
-    async fn list_all_nodes(&self, request: Request<QueryNodeReq>) -> Result<Response<ListAllNodesResponse>, Status> {
-        let req = request.into_inner();
-
-        if req.node_type == NodeType::GroupNode as i32 {
-            let nodes: Vec<NodeInfo> = self.shard_nodes.iter().map(|entry| entry.value().clone()).collect();
-            let mut group_nodes: Vec<NodeInfo> = vec![];
-            for entry in nodes.iter() {
-                if entry.node_type == NodeType::GroupNode as i32 {
-                    group_nodes.push(entry.clone());
-                }
+        if req.node_type == NodeType::MsgFriend as i32 {
+            if let Some(node_info) = self.msg_friend_nodes.get(&node_addr) {
+                return Ok(Response::new(node_info.clone()));
             }
-
-            log::info!("获取所有节点信息");
-            let response = ListAllNodesResponse {
-                nodes: group_nodes,
+            // 构建新 entry
+            let entry = NodeInfo {
+                node_addr: node_addr.clone(),
+                total: self.msg_friend_nodes.len() as i32 + 1,
+                version: 0,
+                node_type: req.node_type,
+                state: ShardState::Normal as i32,
+                last_update_time: now,
+                kafka_addr: req.kafka_addr,
+                socket_addr: None,
+                ws_addr: None,
             };
-            return Ok(Response::new(response));
+            self.persist_node(&entry).await?;
+            self.msg_friend_nodes.insert(node_addr.clone(), entry.clone());
+            //通知socket节点有变
+            let mut client_list = self.init_clients(NodeType::SocketNode).await.expect("init clients error");
+            for client in client_list.iter_mut() {
+                client.flush_nodes(()).await?;
+            }
+            return Ok(Response::new(entry));
         }
-        if req.node_type == NodeType::SocketNode as i32 {
-            let nodes: Vec<NodeInfo> = self.socket_nodes.iter().map(|entry| entry.value().clone()).collect();
-            let mut socket_nodes: Vec<NodeInfo> = vec![];
-            for entry in nodes.iter() {
-                if entry.node_type == NodeType::SocketNode as i32 {
-                    socket_nodes.push(entry.clone());
-                }
+        if req.node_type == NodeType::MesGroup as i32 {
+            if let Some(node_info) = self.msg_group_nodes.get(&node_addr) {
+                return Ok(Response::new(node_info.clone()));
             }
-            log::info!("获取所有节点信息");
-            let response = ListAllNodesResponse {
-                nodes: socket_nodes,
+            // 构建新 entry
+            let entry = NodeInfo {
+                node_addr: node_addr.clone(),
+                total: self.msg_group_nodes.len() as i32 + 1,
+                version: 0,
+                node_type: req.node_type,
+                state: ShardState::Normal as i32,
+                last_update_time: now,
+                kafka_addr: req.kafka_addr,
+                socket_addr: None,
+                ws_addr: None,
             };
-            return Ok(Response::new(response));
+            self.persist_node(&entry).await?;
+            self.msg_group_nodes.insert(node_addr.clone(), entry.clone());
+            //通知socket节点有变
+            let mut client_list = self.init_clients(NodeType::SocketNode).await.expect("init clients error");
+            for client in client_list.iter_mut() {
+                client.flush_nodes(()).await?;
+            }
+            return Ok(Response::new(entry));
         }
-        let mut all_nodes: Vec<NodeInfo> = vec![];
-        let nodes: Vec<NodeInfo> = self.socket_nodes.iter().map(|entry| entry.value().clone()).collect();
-        all_nodes.extend(nodes);
-        let nodes: Vec<NodeInfo> = self.shard_nodes.iter().map(|entry| entry.value().clone()).collect();
-        all_nodes.extend(nodes);
-        log::info!("获取所有节点信息");
-        let response = ListAllNodesResponse {
-            nodes: all_nodes,
+        Err(Status::new(Code::Unknown, "未知错误"))
+    }
+    async fn list_all_nodes(&self, request: Request<QueryNodeReq>) -> Result<Response<ListAllNodesResponse>, Status> {
+        let req = request.into_inner();
+        // 失联节点不对外返回，路由方刷新列表后即绕开
+        let live = |registry: &DashMap<String, NodeInfo>| -> Vec<NodeInfo> {
+            registry.iter().filter(|entry| entry.state != ShardState::Failed as i32).map(|entry| entry.value().clone()).collect()
+        };
+        let nodes = match self.registry(req.node_type) {
+            Some(registry) => live(registry),
+            None => {
+                let mut all_nodes = live(&self.socket_nodes);
+                all_nodes.extend(live(&self.shard_nodes));
+                all_nodes
+            }
         };
-        return Ok(Response::new(response));
+        log::info!("获取所有节点信息");
+        Ok(Response::new(ListAllNodesResponse {
+            nodes,
+            shard_epoch: self.shard_epoch.load(Ordering::Acquire),
+            virtual_nodes: Self::virtual_nodes(),
+        }))
     }
 
     async fn graceful_leave(&self, request: Request<BaseRequest>) -> Result<Response<CommonResp>, Status> {
         let req = request.into_inner();
         let node_addr = req.node_addr;
 
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
-
-        match self.shard_nodes.get_mut(&node_addr) {
-            Some(mut entry) => {
-                entry.state = ShardState::PreparingOffline as i32;
-                entry.last_update_time = now;
-                self.shard_nodes.remove(&node_addr);
-
-                log::info!("节点 {} 已离线", node_addr);
-
-                Ok(Response::new(CommonResp {
-                    success: true,
-                    message: format!("Node {} has gracefully left", node_addr),
-                }))
+        // socket 节点下线：移出节点列表并通知其余 socket 节点（其在线登记与连接迁移由节点自身处理）
+        if req.node_type == NodeType::SocketNode as i32 {
+            let removed = self.socket_nodes.contains_key(&node_addr);
+            log::info!("socket 节点 {} 已离线", node_addr);
+            if removed {
+                self.forget_node(NodeType::SocketNode as i32, &node_addr).await?;
+                self.socket_nodes.remove(&node_addr);
+                self.notify_socket_nodes(&node_addr);
             }
-            None => Ok(Response::new(CommonResp {
+            return Ok(Response::new(CommonResp {
+                success: removed,
+                message: format!("Socket node {} {}", node_addr, if removed { "has gracefully left" } else { "not found" }),
+            }));
+        }
+
+        if !self.shard_nodes.contains_key(&node_addr) {
+            return Ok(Response::new(CommonResp {
                 success: false,
                 message: format!("Node {} not found", node_addr),
-            })),
+            }));
         }
+        // 先删除持久化记录再移出节点表
+        self.forget_node(NodeType::GroupNode as i32, &node_addr).await?;
+        let before = self.shard_ring();
+        self.shard_nodes.remove(&node_addr);
+        self.advance_shard_epoch(&before).await?;
+
+        log::info!("节点 {} 已离线", node_addr);
+
+        Ok(Response::new(CommonResp {
+            success: true,
+            message: format!("Node {} has gracefully left", node_addr),
+        }))
     }
 
+    /// 节点心跳：刷新最后心跳时间；已被判定失联的节点移除并要求重新注册
+    ///
+    /// 心跳时间只保存在内存中，不写入持久化存储：新的主实例恢复视图时会统一重置心跳时间。
     async fn heartbeat(&self, request: Request<BaseRequest>) -> Result<Response<CommonResp>, Status> {
-        match self.shard_nodes.get_mut(&request.get_ref().node_addr) {
-            Some(mut entry) => {
-                let value: &mut NodeInfo = entry.value_mut();
-                // 更新心跳时间
-                value.last_update_time = now() as u64;
-                //打印日志
-                log::info!("心跳: {}", &request.get_ref().node_addr);
+        let req = request.into_inner();
+        let Some(registry) = self.registry(req.node_type) else {
+            return Err(Status::invalid_argument(format!("Invalid node type {}", req.node_type)));
+        };
+        let node = registry.get_mut(&req.node_addr).map(|mut entry| {
+            if entry.state != ShardState::Failed as i32 {
+                entry.last_update_time = now() as u64;
+            }
+            entry.clone()
+        });
+        match node {
+            Some(node) if node.state == ShardState::Failed as i32 => {
+                self.forget_node(req.node_type, &req.node_addr).await?;
+                registry.remove(&req.node_addr);
+                log::warn!("失联节点 {} 恢复心跳，要求重新注册", req.node_addr);
+                Ok(Response::new(CommonResp {
+                    success: false,
+                    message: "node.rejoin.required".to_string(),
+                }))
+            }
+            Some(_) => {
+                log::info!("心跳: {}", &req.node_addr);
                 Ok(Response::new(CommonResp {
                     success: true,
                     message: "".to_string(),
@@ -382,7 +638,7 @@ impl ArbServerRpcService for ArbiterServiceImpl {
             }
             None => Ok(Response::new(CommonResp {
                 success: false,
-                message: format!("Node {} not found", &request.get_ref().node_addr),
+                message: format!("Node {} not found", &req.node_addr),
             })),
         }
     }
@@ -1,2 +1,10 @@
+use crate::service::user_msg_service::UserMsgEntityService;
+use common::db::Db;
+
+pub mod rpc;
 pub mod user_msg_service;
-mod rpc;
\ No newline at end of file
+
+pub async fn init_service() {
+    UserMsgEntityService::init(Db::get().clone()).await;
+    rpc::init().await;
+}
@@ -0,0 +1,106 @@
+use crate::service::rpc::friend_rpc_service_impl::FriendRpcServiceImpl;
+use biz_core::kafka_util::node_util::NodeUtil;
+use biz_core::protocol::arb::arb_client::arb_client_service_server::{ArbClientService, ArbClientServiceServer};
+use biz_core::protocol::arb::arb_client::{SyncGroupChunk, SyncStreamResp, UpdateVersionReq};
+use biz_core::protocol::arb::arb_models::{NodeType, QueryNodeReq, RegRequest, SyncListGroup};
+use biz_core::protocol::common::CommonResp;
+use biz_core::protocol::msg::friend_msg_server::friend_rpc_service_server::FriendRpcServiceServer;
+use biz_core::service::rpc_server_client_service::ArbServerRpcServiceClientService;
+use common::config::AppConfig;
+use once_cell::sync::OnceCell;
+use std::str::FromStr;
+use std::sync::Arc;
+use tonic::{Request, Response, Status, Streaming};
+
+/// arb 客户端接口（好友消息节点）
+#[derive(Debug, Clone)]
+pub struct ArbClientServiceImpl {}
+impl ArbClientServiceImpl {
+    /// 启动 gRPC 服务（FriendRpcService + ArbClientService），并向 arb 注册为 MsgFriend 节点
+    pub async fn start() {
+        let client_addr = AppConfig::get().get_shard().clone().client_addr.unwrap();
+        let addr = std::net::SocketAddr::from_str(&client_addr).expect("Invalid socket address");
+        let client_services = Self {};
+        INSTANCE.set(Arc::new(client_services.clone())).expect("INSTANCE already initialized");
+
+        tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .add_service(FriendRpcServiceServer::new(FriendRpcServiceImpl::new()))
+                .add_service(ArbClientServiceServer::new(client_services))
+                .serve(addr)
+                .await
+                .expect("Failed to start server");
+        });
+
+        let rpc_server_service = ArbServerRpcServiceClientService::get();
+        // 向服务端注册节点
+        let request = RegRequest {
+            node_type: NodeType::MsgFriend as i32,
+            node_addr: client_addr.clone(),
+            kafka_addr: None,
+            socket_addr: None,
+            ws_addr: None,
+        };
+        rpc_server_service.register(request).await.expect("reg msg friend error");
+        let mut client = rpc_server_service.client.lock().await;
+
+        let response = client
+            .list_all_nodes(QueryNodeReq {
+                node_type: NodeType::SocketNode as i32,
+            })
+            .await
+            .expect("list_all_nodes.error");
+        NodeUtil::get().await.push_list(NodeType::SocketNode, response.into_inner().nodes);
+
+        log::warn!("FriendRpcServiceServer started: {}", client_addr);
+    }
+
+    /// 获取单例
+    pub fn get() -> Arc<Self> {
+        INSTANCE.get().expect("INSTANCE is not initialized").clone()
+    }
+
+    pub async fn init() -> anyhow::Result<()> {
+        NodeUtil::init().await;
+        ArbServerRpcServiceClientService::init().await?;
+        ArbClientServiceImpl::start().await;
+        Ok(())
+    }
+}
+
+static INSTANCE: OnceCell<Arc<ArbClientServiceImpl>> = OnceCell::new();
+#[tonic::async_trait]
+impl ArbClientService for ArbClientServiceImpl {
+    async fn update_version(&self, _request: Request<UpdateVersionReq>) -> Result<Response<CommonResp>, Status> {
+        Ok(Response::new(CommonResp {
+            success: true,
+            message: "".to_string(),
+        }))
+    }
+
+    async fn sync_data(&self, _request: Request<SyncListGroup>) -> Result<Response<CommonResp>, Status> {
+        Ok(Response::new(CommonResp {
+            success: true,
+            message: "".to_string(),
+        }))
+    }
+
+    async fn sync_data_stream(&self, _request: Request<Streaming<SyncGroupChunk>>) -> Result<Response<SyncStreamResp>, Status> {
+        Err(Status::unimplemented("sync_data_stream is only served by group shard nodes"))
+    }
+
+    async fn flush_nodes(&self, _: Request<()>) -> Result<Response<CommonResp>, Status> {
+        let rpc_server_service = ArbServerRpcServiceClientService::get();
+        let mut client = rpc_server_service.client.lock().await;
+        let response = client
+            .list_all_nodes(QueryNodeReq {
+                node_type: NodeType::SocketNode as i32,
+            })
+            .await?;
+        NodeUtil::get().await.push_list(NodeType::SocketNode, response.into_inner().nodes);
+        Ok(Response::new(CommonResp {
+            success: true,
+            message: String::new(),
+        }))
+    }
+}
@@ -1,35 +1,199 @@
-use tonic::{Request, Response, Status};
-use biz_core::protocol::common::{CommonResp, IdReq};
-use biz_core::protocol::msg::friend_msg_server::{AcceptFriendReqMsg, AddFriendReqMsg, ChangeFriendReqMsg, DeleteFriendReqMsg, FriendListRespMsg, SendMessageRespMsg};
+use crate::service::user_msg_service::UserMsgEntityService;
+use biz_core::kafka_util::kafka_producer::KafkaInstanceService;
+use biz_core::manager::user_manager::{UserManager, UserManagerOpt};
+use biz_core::protocol::common::{ByteMessageType, CommonResp, IdReq, SendMessageResp};
+use biz_core::protocol::msg::friend::{EventStatus, FriendEventMsg, FriendEventType, FriendSourceType};
 use biz_core::protocol::msg::friend_msg_server::friend_rpc_service_server::FriendRpcService;
+use biz_core::protocol::msg::friend_msg_server::{AcceptFriendReqMsg, AddFriendReqMsg, ChangeFriendReqMsg, DeleteFriendReqMsg, FriendListRespMsg, SendMessageRespMsg};
+use biz_core::service::friend_event_service::FriendEventService;
+use biz_core::service::friend_service::UserFriendService;
+use common::config::AppConfig;
+use common::util::date_util::now;
+use deadpool_redis::redis::AsyncCommands;
+use log::warn;
+use tonic::{Request, Response, Status};
+
+#[derive(Debug, Default)]
+pub struct FriendRpcServiceImpl {}
+
+impl FriendRpcServiceImpl {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    fn ok_resp() -> Response<CommonResp> {
+        Response::new(CommonResp {
+            success: true,
+            message: String::new(),
+        })
+    }
+
+    fn fail_resp(message: &str) -> Response<CommonResp> {
+        Response::new(CommonResp {
+            success: false,
+            message: message.to_string(),
+        })
+    }
 
-#[derive(Debug)]
-pub struct FriendRpcServiceImpl{
+    fn send_rejected(message: &str) -> Response<SendMessageResp> {
+        Response::new(SendMessageResp {
+            success: false,
+            message: message.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// 好友事件推送到单聊 topic，由 socket 节点投递给在线用户
+    async fn notify_event(event: &FriendEventMsg) {
+        let topic = AppConfig::get().get_kafka().topic_single;
+        if let Err(e) = KafkaInstanceService::get().send_proto(&ByteMessageType::FriendEventMsgType, event, &event.message_id, &topic).await {
+            warn!("⚠️ 好友事件推送失败 [{}]: {:?}", event.message_id, e);
+        }
+    }
 
+    /// 同步 Redis 好友集合（is_friend 优先命中 Redis）
+    async fn sync_friend_cache(uid: &str, friend_id: &str, add: bool) -> Result<(), Status> {
+        let mut conn = UserManager::get().pool.get().await.map_err(|e| Status::internal(e.to_string()))?;
+        for (a, b) in [(uid, friend_id), (friend_id, uid)] {
+            let key = format!("friend:user:{}", a);
+            let result: Result<(), _> = if add { conn.sadd(&key, b).await } else { conn.srem(&key, b).await };
+            result.map_err(|e| Status::internal(e.to_string()))?;
+        }
+        Ok(())
+    }
 }
+
 #[tonic::async_trait]
 impl FriendRpcService for FriendRpcServiceImpl {
     async fn add_friend_req(&self, request: Request<AddFriendReqMsg>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        if req.from_uid == req.to_uid {
+            return Ok(Self::fail_resp("friend.self.not.allowed"));
+        }
+        let user_manager = UserManager::get();
+        if user_manager.is_friend(&req.from_uid, &req.to_uid).await.map_err(|e| Status::internal(e.to_string()))? {
+            return Ok(Self::fail_resp("friend.already.exists"));
+        }
+        let event_service = FriendEventService::get();
+        let pending = event_service.find_pending_request(&req.from_uid, &req.to_uid).await.map_err(|e| Status::internal(e.to_string()))?;
+        if pending.is_some() {
+            return Ok(Self::fail_resp("friend.request.pending"));
+        }
+        let from_user = user_manager
+            .get_user_info(&req.from_uid)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found("user.not.found"))?;
+
+        let time = now() as u64;
+        let event = FriendEventMsg {
+            message_id: req.message_id,
+            from_uid: req.from_uid.clone(),
+            to_uid: req.to_uid.clone(),
+            event_type: FriendEventType::FriendRequest as i32,
+            message: req.reason.clone(),
+            status: EventStatus::Pending as i32,
+            created_at: time,
+            updated_at: time,
+            source_type: FriendSourceType::FriendSourceSearch as i32,
+            from_a_name: from_user.name.clone(),
+            to_a_name: "".to_string(),
+            from_remark: None,
+            to_remark: None,
+        };
+        event_service.apply_friend(&event).await.map_err(|e| Status::internal(e.to_string()))?;
+        Self::notify_event(&event).await;
+        Ok(Self::ok_resp())
     }
 
     async fn accept_friend_req(&self, request: Request<AcceptFriendReqMsg>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        let event_service = FriendEventService::get();
+        let event_id = event_service
+            .find_pending_request(&req.requestor_uid, &req.acceptor_uid)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found("friend.request.not.found"))?;
+
+        let time = now() as u64;
+        let mut event = FriendEventMsg {
+            message_id: req.message_id,
+            from_uid: req.acceptor_uid.clone(),
+            to_uid: req.requestor_uid.clone(),
+            event_type: FriendEventType::FriendReject as i32,
+            message: req.reason.clone(),
+            status: EventStatus::Done as i32,
+            created_at: time,
+            updated_at: time,
+            source_type: FriendSourceType::FriendSourceSearch as i32,
+            from_a_name: "".to_string(),
+            to_a_name: "".to_string(),
+            from_remark: None,
+            to_remark: None,
+        };
+
+        if !req.accept {
+            event_service.reject_friend(&event_id, &req.acceptor_uid, &req.reason).await.map_err(|e| Status::internal(e.to_string()))?;
+            Self::notify_event(&event).await;
+            return Ok(Self::ok_resp());
+        }
+
+        let acceptor = UserManager::get()
+            .get_user_info(&req.acceptor_uid)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found("user.not.found"))?;
+        event_service.accept_friend(&event_id, &req.acceptor_uid, &acceptor.name, None).await.map_err(|e| Status::internal(e.to_string()))?;
+        Self::sync_friend_cache(&req.acceptor_uid, &req.requestor_uid, true).await?;
+
+        event.event_type = FriendEventType::FriendAccept as i32;
+        event.from_a_name = acceptor.name;
+        Self::notify_event(&event).await;
+        Ok(Self::ok_resp())
     }
 
     async fn delete_friend(&self, request: Request<DeleteFriendReqMsg>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        FriendEventService::get().remove_friend(&req.from_uid, &req.to_uid).await.map_err(|e| Status::internal(e.to_string()))?;
+        Self::sync_friend_cache(&req.from_uid, &req.to_uid, false).await?;
+        Ok(Self::ok_resp())
     }
 
     async fn get_friend_list(&self, request: Request<IdReq>) -> Result<Response<FriendListRespMsg>, Status> {
-        todo!()
+        let req = request.into_inner();
+        let friends = UserManager::get().get_friends(&req.ref_id).await.map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(FriendListRespMsg {
+            uid: friends,
+        }))
     }
 
-    async fn send_message(&self, request: Request<SendMessageRespMsg>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+    async fn send_message(&self, request: Request<SendMessageRespMsg>) -> Result<Response<SendMessageResp>, Status> {
+        let req = request.into_inner();
+        let user_manager = UserManager::get();
+        if !user_manager.is_friend(&req.from_uid, &req.to_uid).await.map_err(|e| Status::internal(e.to_string()))? {
+            return Ok(Self::send_rejected("friend.not.exists"));
+        }
+        if user_manager.is_blocked(&req.to_uid, &req.from_uid).await.map_err(|e| Status::internal(e.to_string()))? {
+            return Ok(Self::send_rejected("friend.blocked"));
+        }
+        let message = UserMsgEntityService::get()
+            .send_user_message(&req.from_uid, &req.to_uid, &req.contents)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(SendMessageResp {
+            success: true,
+            message: String::new(),
+            message_id: message.message_id,
+            seq: message.seq,
+        }))
     }
 
     async fn change_friend(&self, request: Request<ChangeFriendReqMsg>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        UserFriendService::get()
+            .change_friend_info(&req.from_uid, &req.to_uid, req.name.as_deref(), req.remark.as_deref())
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Self::ok_resp())
     }
-}
\ No newline at end of file
+}
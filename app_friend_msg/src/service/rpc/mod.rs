@@ -1 +1,8 @@
-mod friend_rpc_service_impl;
\ No newline at end of file
+pub mod arb_client_service_impl;
+pub mod friend_rpc_service_impl;
+
+use crate::service::rpc::arb_client_service_impl::ArbClientServiceImpl;
+
+pub async fn init() {
+    ArbClientServiceImpl::init().await.expect("init arb client service error");
+}
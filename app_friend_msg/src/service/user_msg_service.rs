@@ -3,11 +3,15 @@ use std::sync::Arc;
 use mongodb::Database;
 use once_cell::sync::OnceCell;
 use biz_core::kafka_util::kafka_producer::KafkaInstanceService;
-use biz_core::protocol::common::ByteMessageType;
+use biz_core::protocol::common::{ByteMessageType, ChatTargetType};
 use biz_core::protocol::msg::message::Segment;
+use biz_core::service::inbox_service::{InboxEntry, InboxService};
+use biz_core::service::presence_service::{node_topic, PresenceService};
+use biz_core::service::read_index_service::ReadIndexService;
+use biz_core::service::seq_service::SeqService;
 use common::config::AppConfig;
 use common::errors::AppError;
-use common::repository_util::{BaseRepository, Repository};
+use common::repository_util::BaseRepository;
 use common::util::common_utils::build_snow_id;
 use common::util::date_util::now;
 use crate::domain::user_msg_entity::UserMsgEntity;
@@ -33,6 +37,8 @@ impl UserMsgEntityService {
         INSTANCE.get().expect("INSTANCE is not initialized").clone()
     }
     /// 构造并保存一条用户消息，返回完整 UserMessage
+    ///
+    /// 消息 ID 与会话序号在所有校验通过后、落库前才分配，被拒绝的消息不会占用序号
     pub async fn send_user_message(
         &self,
         from: &String,
@@ -54,12 +60,29 @@ impl UserMsgEntityService {
             })
             .collect();
 
-        // 构造 UserMessage 对象
-        let message = UserMsgEntity {
+        let message = self.persist(from, to, segments, now_time).await?;
+        // 写入接收方收件箱，客户端 ACK 后移除
+        InboxService::get().push(to, &InboxEntry::single(message.message_id)).await?;
+        // 只投递到接收方在线连接所在的 socket 节点
+        let kafka_service = KafkaInstanceService::get();
+        let topic = &AppConfig::get().get_kafka().topic_single;
+        for node_addr in PresenceService::get().nodes_of(to).await? {
+            kafka_service
+                .send_proto(&ByteMessageType::UserMsgType, &message, &message.message_id, &node_topic(topic, &node_addr))
+                .await?;
+        }
+        // 发送者视为已读到该序号
+        ReadIndexService::get().update_read_seq(from, to, ChatTargetType::Single, message.seq).await?;
+        Ok(message)
+    }
+
+    /// 分配消息 ID 与会话序号并落库（先落库再写收件箱，保证重连补发时能查到原文）
+    async fn persist(&self, from: &str, to: &str, content: Vec<Segment>, now_time: i64) -> Result<UserMsgEntity, AppError> {
+        let mut message = UserMsgEntity {
             message_id: build_snow_id(),
-            from: from.clone(),
-            to: to.clone(),
-            content: segments,
+            from: from.to_string(),
+            to: to.to_string(),
+            content,
             created_time: now_time,
             updated_time: now_time,
             sync_mq_status: false,
@@ -67,21 +90,11 @@ impl UserMsgEntityService {
             is_system: false,
             delivered: false,
             read_time: now_time,
+            seq: 0,
         };
-        let kafka_service = KafkaInstanceService::get();
-        // 发送到 Kafka
-        let app_config = AppConfig::get();
-        let msg_type: ByteMessageType = ByteMessageType::UserMsgType;
-        kafka_service
-            .send_proto(
-                &msg_type,
-                &message,
-                &message.message_id,
-                &app_config.get_kafka().topic_single,
-            )
-            .await?;
-        // 持久化
-        self.dao.insert(&message).await?;
+        // 序号与消息同一事务提交，落库失败不占用序号
+        let doc = mongodb::bson::to_document(&message).map_err(anyhow::Error::from)?;
+        message.seq = SeqService::get().insert_with_seq(&SeqService::single_key(from, to), &self.dao.collection, doc).await?;
         Ok(message)
     }
 }
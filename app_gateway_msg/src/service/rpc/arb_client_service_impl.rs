@@ -1,5 +1,4 @@
 use crate::service::kafka_service::KafkaService;
-use crate::service::rpc::arb_server_client::ArbServerClient;
 use biz_core::service::rpc_server_client_service::ArbServerRpcServiceClientService;
 use biz_core::kafka_util::node_util::NodeUtil;
 use biz_core::protocol::common::CommonResp;
@@ -10,9 +9,9 @@ use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tonic::{transport::Server, Request, Response, Status};
+use tonic::{transport::Server, Request, Response, Status, Streaming};
 use biz_core::protocol::arb::arb_client::arb_client_service_server::{ArbClientService, ArbClientServiceServer};
-use biz_core::protocol::arb::arb_client::UpdateVersionReq;
+use biz_core::protocol::arb::arb_client::{SyncGroupChunk, SyncStreamResp, UpdateVersionReq};
 use biz_core::protocol::arb::arb_models::{NodeType, QueryNodeReq, RegRequest, SyncListGroup};
 
 /// ArbClientServiceImpl: 与 ArbServer 交互并提供 gRPC 服务接口
@@ -52,10 +51,12 @@ impl ArbClientServiceImpl {
             node_type: NodeType::MsgGateway as i32,
             node_addr: addr.to_string(),
             kafka_addr: None,
+            socket_addr: None,
+            ws_addr: None,
         };
         let arb_server_client = ArbServerRpcServiceClientService::get();
+        arb_server_client.register(reg_req).await.expect("Failed to register node");
         let mut client = arb_server_client.client.lock().await;
-        client.register_node(reg_req).await.expect("Failed to register node");
 
         // 首次刷新 SocketNode 列表
         let socket_resp = client
@@ -105,6 +106,10 @@ impl ArbClientService for ArbClientServiceImpl {
         }))
     }
 
+    async fn sync_data_stream(&self, _request: Request<Streaming<SyncGroupChunk>>) -> Result<Response<SyncStreamResp>, Status> {
+        Err(Status::unimplemented("sync_data_stream is only served by group shard nodes"))
+    }
+
     async fn flush_nodes(&self, _req: Request<()>) -> Result<Response<CommonResp>, Status> {
         let arb_server_client = ArbServerRpcServiceClientService::get();
         let mut client = arb_server_client.client.lock().await;
@@ -1,6 +1,23 @@
 mod domain;
 mod service;
 
-fn main() {
-    println!("Hello, world!");
+use biz_core::kafka_util::kafka_producer::KafkaInstanceService;
+use common::config::AppConfig;
+use log::warn;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // 读取配置文件
+    AppConfig::init(&"./app_group_msg/group-msg-config.toml".to_string()).await;
+    let config = AppConfig::get();
+    //初始化 kafka
+    KafkaInstanceService::init_instance(&config.get_kafka()).await?;
+    //初始化业务
+    biz_core::init_service().await;
+    biz_core::manager::init();
+    //group rpc 服务 + arb 注册
+    service::init_service().await;
+    warn!("app_group_msg started");
+    tokio::signal::ctrl_c().await?;
+    Ok(())
 }
@@ -1,2 +1,10 @@
-mod mq_message_group_service;
-mod rpc;
\ No newline at end of file
+use crate::service::mq_message_group_service::GroupMessageService;
+use common::db::Db;
+
+pub mod mq_message_group_service;
+pub mod rpc;
+
+pub async fn init_service() {
+    GroupMessageService::init(Db::get().clone()).await;
+    rpc::init().await;
+}
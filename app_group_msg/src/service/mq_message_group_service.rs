@@ -1,6 +1,6 @@
 use common::config::AppConfig;
 use common::errors::AppError;
-use common::repository_util::{BaseRepository, Repository};
+use common::repository_util::BaseRepository;
 use common::util::common_utils::{build_snow_id, build_uuid};
 use common::util::date_util::now;
 use mongodb::Database;
@@ -8,8 +8,14 @@ use once_cell::sync::OnceCell;
 use std::collections::HashMap;
 use std::sync::Arc;
 use biz_core::kafka_util::kafka_producer::KafkaInstanceService;
-use biz_core::protocol::common::ByteMessageType;
+use biz_core::protocol::common::{ByteMessageType, ChatTargetType};
 use biz_core::protocol::msg::message::Segment;
+use biz_core::service::group_member_service::GroupMemberService;
+use biz_core::service::inbox_service::{InboxEntry, InboxService};
+use biz_core::service::presence_service::{node_topic, PresenceService};
+use biz_core::service::read_index_service::ReadIndexService;
+use biz_core::service::seq_service::SeqService;
+use biz_core::entitys::group_msg_entity::{GroupMsgDelivery, GroupMsgRecipient};
 use crate::domain::group_msg_entity::GroupMsgEntity;
 
 #[derive(Debug)]
@@ -32,6 +38,9 @@ impl GroupMessageService {
     pub fn get() -> Arc<Self> {
         INSTANCE.get().expect("INSTANCE is not initialized").clone()
     }
+    /// 构造并保存一条群消息
+    ///
+    /// 消息 ID 与群内序号在所有校验通过后、落库前才分配，被拒绝的消息不会占用序号
     pub async fn send_group_message(
         &self,
         from: &String,
@@ -52,33 +61,57 @@ impl GroupMessageService {
                 metadata: { HashMap::new() },
             })
             .collect();
-        // 构造 UserMessage 对象
-        let message = GroupMsgEntity {
+        // 收件人在分配序号前确定，成员查询失败时不占用序号
+        let receivers: Vec<String> = GroupMemberService::get()
+            .get_all_members_by_group_id(to)
+            .await?
+            .into_iter()
+            .map(|m| m.uid)
+            .filter(|uid| uid != from)
+            .collect();
+        let message = self.persist(from, to, segments, now_time).await?;
+        // 扇出写入除发送者外所有成员的收件箱
+        InboxService::get().push_many(&receivers, &InboxEntry::group(message.message_id)).await?;
+        // 只投递到在线成员所在的 socket 节点，记录携带该节点上的接收者及设备，节点按此扇出给本地连接
+        let kafka_service = KafkaInstanceService::get();
+        let topic = &AppConfig::get().get_kafka().topic_group;
+        for (node_addr, users) in PresenceService::get().route_devices(&receivers).await? {
+            let delivery = GroupMsgDelivery {
+                message: Some(message.clone()),
+                recipients: users
+                    .into_iter()
+                    .map(|(uid, devices)| GroupMsgRecipient {
+                        uid,
+                        device_types: devices.into_iter().map(|device| device as i32).collect(),
+                    })
+                    .collect(),
+            };
+            kafka_service
+                .send_proto(&ByteMessageType::GroupMsgType, &delivery, &message.message_id, &node_topic(topic, &node_addr))
+                .await?;
+        }
+        // 发送者视为已读到该序号
+        ReadIndexService::get().update_read_seq(from, to, ChatTargetType::Group, message.seq).await?;
+        Ok(message)
+    }
+
+    /// 分配消息 ID 与群内序号并落库（先落库再写收件箱，保证重连补发时能查到原文）
+    async fn persist(&self, from: &str, to: &str, content: Vec<Segment>, now_time: i64) -> Result<GroupMsgEntity, AppError> {
+        let mut message = GroupMsgEntity {
             message_id: build_snow_id(),
             from: from.to_string(),
             sync_mq_status: true,
             to: to.to_string(),
-            content: segments,
+            content,
             create_time: now_time,
             update_time: now_time,
             revoked: false,
             is_system: false,
             seq: 0,
         };
-        let kafka_service = KafkaInstanceService::get();
-        // 发送到 Kafka
-        let app_config = AppConfig::get();
-        let message_type = ByteMessageType::GroupMsgType;
-        kafka_service
-            .send_proto(
-                &message_type,
-                &message,
-                &message.message_id,
-                &app_config.get_kafka().topic_group,
-            )
-            .await?;
-        // 持久化
-        self.dao.insert(&message).await?;
+        // 序号与消息同一事务提交，落库失败不占用序号
+        let doc = mongodb::bson::to_document(&message).map_err(anyhow::Error::from)?;
+        message.seq = SeqService::get().insert_with_seq(&SeqService::group_key(to), &self.dao.collection, doc).await?;
         Ok(message)
     }
 }
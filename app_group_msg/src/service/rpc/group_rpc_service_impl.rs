@@ -1,43 +1,353 @@
-use tonic::{Request, Response, Status};
-use biz_core::protocol::common::CommonResp;
-use biz_core::protocol::msg::group_msg_server::{CreateGroupReq, DismissGroupReq, GroupMessageReq, InviteMemberReq, JoinGroupReq, KickMemberReq, QuitGroupReq, UpdateGroupProfileReq};
+use crate::service::mq_message_group_service::GroupMessageService;
+use biz_core::entitys::group_entity::GroupEntity;
+use biz_core::entitys::group_join_req_entity::RequestJoinGroupEntity;
+use biz_core::entitys::group_member_entity::GroupMemberEntity;
+use biz_core::kafka_util::kafka_producer::KafkaInstanceService;
+use biz_core::protocol::arb::shard_service::{AddMemberReq, RemoveMemberReq};
+use biz_core::protocol::common::{ByteMessageType, CommonResp, GroupRoleType, GroupType, IdReq, JoinPermission, SendMessageResp};
+use biz_core::protocol::msg::group::{CreateGroupMsg, DestroyGroupMsg};
 use biz_core::protocol::msg::group_msg_server::group_rpc_service_server::GroupRpcService;
+use biz_core::protocol::msg::group_msg_server::{CreateGroupReq, DismissGroupReq, GroupMessageReq, InviteMemberReq, JoinGroupReq, KickMemberReq, QuitGroupReq, UpdateGroupProfileReq};
+use biz_core::service::group_member_service::GroupMemberService;
+use biz_core::service::group_service::GroupService;
+use biz_core::service::shard_client_service::ShardClientService;
+use common::config::AppConfig;
+use common::db::Db;
+use common::repository_util::{BaseRepository, Repository};
+use common::util::common_utils::build_uuid;
+use common::util::date_util::now;
+use log::{error, warn};
+use mongodb::bson::oid::ObjectId;
+use prost::Message;
+use tonic::{Request, Response, Status};
 
 #[derive(Debug)]
-pub struct GroupRpcServiceImpl{
-    
+pub struct GroupRpcServiceImpl {
+    /// 入群申请记录
+    join_req_dao: BaseRepository<RequestJoinGroupEntity>,
 }
+
+impl GroupRpcServiceImpl {
+    pub async fn new() -> Self {
+        Self {
+            join_req_dao: BaseRepository::new(Db::get().clone(), "group_join_req").await,
+        }
+    }
+
+    fn ok_resp() -> Response<CommonResp> {
+        Response::new(CommonResp {
+            success: true,
+            message: String::new(),
+        })
+    }
+
+    fn fail_resp(message: &str) -> Response<CommonResp> {
+        Response::new(CommonResp {
+            success: false,
+            message: message.to_string(),
+        })
+    }
+
+    fn send_rejected(message: &str) -> Response<SendMessageResp> {
+        Response::new(SendMessageResp {
+            success: false,
+            message: message.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// 查询成员在群内的角色，非成员返回 None
+    async fn member_role(group_id: &str, uid: &str) -> Option<GroupRoleType> {
+        let member = GroupMemberService::get().find_by_group_id_and_uid(group_id, &uid.to_string()).await.ok()?;
+        GroupRoleType::try_from(member.role).ok()
+    }
+
+    fn build_member(group_id: &str, uid: &str, role: GroupRoleType) -> GroupMemberEntity {
+        let time = now() as u64;
+        GroupMemberEntity {
+            id: build_uuid(),
+            group_id: group_id.to_string(),
+            uid: uid.to_string(),
+            alias: "".to_string(),
+            role: role as i32,
+            is_muted: false,
+            mute_until: 0,
+            avatar: "".to_string(),
+            create_time: time,
+            update_time: time,
+        }
+    }
+
+    /// 同步成员到分片节点；数据库已提交，失败不回报调用方，只记录日志，由分片节点跟随变更流（续传失败时全量对账）补齐
+    async fn shard_add_members(group_id: &str, members: &[(String, GroupRoleType)]) {
+        let shard_client = ShardClientService::get();
+        for (uid, role) in members {
+            let req = AddMemberReq {
+                group_id: group_id.to_string(),
+                user_id: uid.clone(),
+                role: *role as i32,
+            };
+            let result = shard_client
+                .call(group_id, |mut client| {
+                    let req = req.clone();
+                    async move { client.add_member(req).await }
+                })
+                .await;
+            if let Err(e) = result {
+                error!("❌ 分片添加成员失败 group_id={} uid={}: {:?}", group_id, uid, e);
+            }
+        }
+    }
+
+    async fn shard_remove_member(group_id: &str, uid: &str) {
+        let req = RemoveMemberReq {
+            group_id: group_id.to_string(),
+            user_id: uid.to_string(),
+        };
+        let result = ShardClientService::get()
+            .call(group_id, |mut client| {
+                let req = req.clone();
+                async move { client.remove_member(req).await }
+            })
+            .await;
+        if let Err(e) = result {
+            error!("❌ 分片移除成员失败 group_id={} uid={}: {:?}", group_id, uid, e);
+        }
+    }
+
+    /// 群事件推送到群聊 topic，由 socket 节点投递给在线成员
+    async fn notify_group<M: Message>(msg_type: &ByteMessageType, msg: &M, message_id: &u64) {
+        let topic = AppConfig::get().get_kafka().topic_group;
+        if let Err(e) = KafkaInstanceService::get().send_proto(msg_type, msg, message_id, &topic).await {
+            warn!("⚠️ 群事件推送失败 [{}]: {:?}", message_id, e);
+        }
+    }
+
+    /// 批量加入成员（跳过已在群内的用户），返回实际加入的 uid
+    async fn add_members(group_id: &str, uids: &[String]) -> Result<Vec<String>, Status> {
+        let mut new_members = Vec::with_capacity(uids.len());
+        for uid in uids {
+            if Self::member_role(group_id, uid).await.is_some() {
+                continue;
+            }
+            new_members.push(Self::build_member(group_id, uid, GroupRoleType::Member));
+        }
+        if new_members.is_empty() {
+            return Ok(vec![]);
+        }
+        GroupMemberService::get().add_members(&new_members).await.map_err(|e| Status::internal(e.to_string()))?;
+        let shard_members: Vec<(String, GroupRoleType)> = new_members.iter().map(|m| (m.uid.clone(), GroupRoleType::Member)).collect();
+        Self::shard_add_members(group_id, &shard_members).await;
+        Ok(new_members.into_iter().map(|m| m.uid).collect())
+    }
+}
+
 #[tonic::async_trait]
-impl GroupRpcService for GroupRpcServiceImpl{
+impl GroupRpcService for GroupRpcServiceImpl {
     async fn create_group(&self, request: Request<CreateGroupReq>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        let time = now() as u64;
+        let group = GroupEntity {
+            id: ObjectId::new().to_hex(),
+            name: req.name.clone(),
+            avatar: req.avatar.clone().unwrap_or_default(),
+            description: req.intro.clone().unwrap_or_default(),
+            notice: "".to_string(),
+            join_permission: JoinPermission::Anyone as i32,
+            owner_id: req.creator_uid.clone(),
+            group_type: GroupType::NormalGroup as i32,
+            allow_search: true,
+            enable: true,
+            create_time: time,
+            update_time: time,
+        };
+        if let Err(e) = GroupService::get().create_group(&group, &req.members).await {
+            return Ok(Self::fail_resp(&e.to_string()));
+        }
+
+        // 分片：创建群 + 群主 + 初始成员
+        let result = ShardClientService::get()
+            .call(&group.id, |mut client| {
+                let req = IdReq { ref_id: group.id.clone() };
+                async move { client.create(req).await }
+            })
+            .await;
+        if let Err(e) = result {
+            error!("❌ 分片创建群失败 group_id={}: {:?}", group.id, e);
+        }
+        let mut members = vec![(req.creator_uid.clone(), GroupRoleType::Owner)];
+        members.extend(req.members.iter().filter(|uid| **uid != req.creator_uid).map(|uid| (uid.clone(), GroupRoleType::Member)));
+        Self::shard_add_members(&group.id, &members).await;
+
+        let msg = CreateGroupMsg {
+            message_id: req.message_id,
+            group_id: group.id.clone(),
+            uids: members.into_iter().map(|(uid, _)| uid).collect(),
+            name: group.name.clone(),
+            avatar: group.avatar.clone(),
+            creator_id: req.creator_uid.clone(),
+        };
+        Self::notify_group(&ByteMessageType::GroupCreateMsgType, &msg, &msg.message_id).await;
+        Ok(Response::new(CommonResp {
+            success: true,
+            message: group.id,
+        }))
     }
 
     async fn update_group_profile(&self, request: Request<UpdateGroupProfileReq>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        match Self::member_role(&req.group_id, &req.operator_uid).await {
+            Some(GroupRoleType::Owner) | Some(GroupRoleType::Admin) => {}
+            _ => return Ok(Self::fail_resp("group.permission.denied")),
+        }
+        GroupService::get()
+            .update_group_profile(&req.group_id, req.name.as_deref(), req.avatar.as_deref(), req.intro.as_deref())
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Self::ok_resp())
     }
 
     async fn dismiss_group(&self, request: Request<DismissGroupReq>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        let group_service = GroupService::get();
+        let group = match group_service.find_by_group_id(&req.group_id).await {
+            Ok(group) => group,
+            Err(_) => return Ok(Self::fail_resp("group.not.found")),
+        };
+        if group.owner_id != req.owner_uid {
+            return Ok(Self::fail_resp("group.permission.denied"));
+        }
+        // 成员记录随解散删除，先取出成员列表用于推送解散通知
+        let uids: Vec<String> = GroupMemberService::get()
+            .get_all_members_by_group_id(&req.group_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .into_iter()
+            .map(|member| member.uid)
+            .collect();
+        group_service.dismiss_group(&req.group_id, &req.owner_uid).await.map_err(|e| Status::internal(e.to_string()))?;
+
+        let result = ShardClientService::get()
+            .call(&req.group_id, |mut client| {
+                let dismiss = IdReq { ref_id: req.group_id.clone() };
+                async move { client.dismiss(dismiss).await }
+            })
+            .await;
+        if let Err(e) = result {
+            error!("❌ 分片解散群失败 group_id={}: {:?}", req.group_id, e);
+        }
+
+        let msg = DestroyGroupMsg {
+            message_id: req.message_id,
+            group_id: req.group_id.clone(),
+            operator_id: req.owner_uid.clone(),
+            uids,
+        };
+        Self::notify_group(&ByteMessageType::GroupDismissMsgType, &msg, &msg.message_id).await;
+        Ok(Self::ok_resp())
     }
 
     async fn join_group(&self, request: Request<JoinGroupReq>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        let group = match GroupService::get().find_by_group_id(&req.group_id).await {
+            Ok(group) => group,
+            Err(_) => return Ok(Self::fail_resp("group.not.found")),
+        };
+        if Self::member_role(&req.group_id, &req.uid).await.is_some() {
+            return Ok(Self::fail_resp("group.member.already.exists"));
+        }
+        match JoinPermission::try_from(group.join_permission).unwrap_or(JoinPermission::Closed) {
+            JoinPermission::Anyone => {
+                Self::add_members(&req.group_id, &[req.uid.clone()]).await?;
+                Ok(Self::ok_resp())
+            }
+            JoinPermission::NeedApproval => {
+                let time = now();
+                let join_req = RequestJoinGroupEntity {
+                    id: ObjectId::new().to_hex(),
+                    group_id: req.group_id.clone(),
+                    applicant_id: req.uid.clone(),
+                    apply_reason: Some(req.reason.clone()),
+                    status: 0,
+                    reviewed_by: None,
+                    reviewed_at: 0,
+                    create_time: time,
+                    update_time: time,
+                };
+                self.join_req_dao.insert(&join_req).await.map_err(|e| Status::internal(e.to_string()))?;
+                Ok(Self::fail_resp("group.join.pending"))
+            }
+            JoinPermission::InviteOnly | JoinPermission::Closed => Ok(Self::fail_resp("group.join.not.allowed")),
+        }
     }
 
     async fn quit_group(&self, request: Request<QuitGroupReq>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        match Self::member_role(&req.group_id, &req.uid).await {
+            None => return Ok(Self::fail_resp("group.member.notfound")),
+            Some(GroupRoleType::Owner) => return Ok(Self::fail_resp("group.owner.cannot.quit")),
+            Some(_) => {}
+        }
+        GroupMemberService::get().remove(&req.group_id, &req.uid).await.map_err(|e| Status::internal(e.to_string()))?;
+        Self::shard_remove_member(&req.group_id, &req.uid).await;
+        Ok(Self::ok_resp())
     }
 
     async fn kick_member(&self, request: Request<KickMemberReq>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        let operator_role = Self::member_role(&req.group_id, &req.operator_uid).await;
+        let target_role = match Self::member_role(&req.group_id, &req.target_uid).await {
+            Some(role) => role,
+            None => return Ok(Self::fail_resp("group.member.notfound")),
+        };
+        // 群主可踢管理员和成员，管理员只能踢普通成员
+        let allowed = match (operator_role, target_role) {
+            (Some(GroupRoleType::Owner), GroupRoleType::Admin | GroupRoleType::Member) => true,
+            (Some(GroupRoleType::Admin), GroupRoleType::Member) => true,
+            _ => false,
+        };
+        if !allowed {
+            return Ok(Self::fail_resp("group.permission.denied"));
+        }
+        GroupMemberService::get().remove(&req.group_id, &req.target_uid).await.map_err(|e| Status::internal(e.to_string()))?;
+        Self::shard_remove_member(&req.group_id, &req.target_uid).await;
+        Ok(Self::ok_resp())
     }
 
     async fn invite_member(&self, request: Request<InviteMemberReq>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+        let req = request.into_inner();
+        let group = match GroupService::get().find_by_group_id(&req.group_id).await {
+            Ok(group) => group,
+            Err(_) => return Ok(Self::fail_resp("group.not.found")),
+        };
+        let allowed = match Self::member_role(&req.group_id, &req.inviter_uid).await {
+            Some(GroupRoleType::Owner) | Some(GroupRoleType::Admin) => true,
+            // 普通成员仅在非封闭群内可邀请
+            Some(_) => group.join_permission != JoinPermission::Closed as i32,
+            None => false,
+        };
+        if !allowed {
+            return Ok(Self::fail_resp("group.permission.denied"));
+        }
+        Self::add_members(&req.group_id, &req.invitee_uid).await?;
+        Ok(Self::ok_resp())
     }
 
-    async fn send_group_message(&self, request: Request<GroupMessageReq>) -> Result<Response<CommonResp>, Status> {
-        todo!()
+    async fn send_group_message(&self, request: Request<GroupMessageReq>) -> Result<Response<SendMessageResp>, Status> {
+        let req = request.into_inner();
+        let speak = ShardClientService::get().check_can_speak(&req.group_id, &req.from_uid).await;
+        if !speak.can_speak {
+            return Ok(Self::send_rejected(&speak.reason));
+        }
+        let message = GroupMessageService::get()
+            .send_group_message(&req.from_uid, &req.group_id, &req.contents)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(SendMessageResp {
+            success: true,
+            message: String::new(),
+            message_id: message.message_id,
+            seq: message.seq,
+        }))
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,9 @@
 use crate::db::intern_pool::InternPool;
+use crate::db::member::member_index::{MemberCursor, MemberPage};
 use crate::db::member::member_list_wrapper::MemberListWrapper;
 use crate::error::member_list_error::MemberListError;
 use arc_swap::ArcSwap;
+use biz_core::protocol::arb::shard_service::MemberOrder;
 use biz_core::protocol::common::GroupRoleType;
 use dashmap::{DashMap, DashSet};
 use rand::{rng, Rng};
@@ -128,11 +130,18 @@ impl HashShardMap {
         Ok(())
     }
 
-    /// 获取分页成员
-    pub fn get_page(&self, key: &str, page: usize, page_size: usize) -> Option<Vec<MemberRef>> {
+    /// 获取分页成员：从 `after` 之后（为 None 时从头跳过 `skip` 个）按 `order` 取一页
+    pub fn get_page(&self, key: &str, order: MemberOrder, after: Option<&MemberCursor>, skip: usize, limit: usize) -> Option<MemberPage> {
         let gkey = self.pool.intern(key);
         let shard = &self.shards[self.get_shard_index(key)];
-        shard.inner.load().get(&gkey).map(|w| w.get_page(page, page_size))
+        shard.inner.load().get(&gkey).map(|w| w.get_page(order, after, skip, limit))
+    }
+
+    /// 按角色获取成员（按入群时间），`online_only` 时只取在线成员
+    pub fn get_members_by_role(&self, key: &str, role: GroupRoleType, online_only: bool) -> Vec<MemberRef> {
+        let gkey = self.pool.intern(key);
+        let shard = &self.shards[self.get_shard_index(key)];
+        shard.inner.load().get(&gkey).map(|w| w.members_with_role(role, online_only)).unwrap_or_default()
     }
 
     /// 获取在线ID
@@ -189,6 +198,23 @@ impl HashShardMap {
         Ok(())
     }
 
+    /// 修改成员的部分字段，群组或成员不存在时返回 false
+    pub fn update_member(&self, key: &str, user_id: &str, f: impl Fn(&mut MemberRef)) -> Result<bool, MemberListError> {
+        let gkey = self.pool.intern(key);
+        let shard = &self.shards[self.get_shard_index(key)];
+        if let Some(wrapper) = shard.inner.load().get(&gkey) {
+            return self.retry_op(|| wrapper.update(user_id, &f));
+        }
+        Ok(false)
+    }
+
+    /// 获取单个成员
+    pub fn get_member(&self, key: &str, user_id: &str) -> Option<MemberRef> {
+        let gkey = self.pool.intern(key);
+        let shard = &self.shards[self.get_shard_index(key)];
+        shard.inner.load().get(&gkey).and_then(|w| w.get(user_id))
+    }
+
     /// 清空 group
     pub fn clear(&self, key: &str) {
         let gkey = self.pool.intern(key);
@@ -244,6 +270,13 @@ impl HashShardMap {
         shard.inner.load().get(&gkey).map(|w| w.get_all()).unwrap_or_default()
     }
 
+    /// 是否持有该 group（成员为空的 group 也算）
+    pub fn contains_key(&self, key: &str) -> bool {
+        let gkey = self.pool.intern(key);
+        let shard = &self.shards[self.get_shard_index(key)];
+        shard.inner.load().contains_key(&gkey)
+    }
+
     /// 根据 group key 获取成员数量
     pub fn get_member_count_by_key(&self, key: &str) -> usize {
         let gkey = self.pool.intern(key);
@@ -0,0 +1,243 @@
+use biz_core::protocol::arb::arb_models::MemberRef;
+use biz_core::protocol::arb::shard_service::MemberOrder;
+use bytes::{Buf, BufMut, BytesMut};
+use std::collections::BTreeSet;
+use std::ops::Bound;
+use std::sync::Arc;
+
+const CURSOR_UID: u8 = 1;
+const CURSOR_ROLE: u8 = 2;
+
+/// 按角色排序的键：角色（群主在前）→ 入群时间 → user_id
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RoleKey {
+    pub role: i32,
+    pub join_time: u64,
+    pub id: Arc<str>,
+}
+
+impl RoleKey {
+    fn of(member: &MemberRef) -> Self {
+        Self {
+            role: member.role,
+            join_time: member.join_time,
+            id: Arc::from(member.id.as_str()),
+        }
+    }
+}
+
+/// 分页位置：上一页最后一个成员在对应排序下的键，下一页从其之后开始
+///
+/// 位置按键而非序号定位，翻页期间其他成员的加入、移除不会使后续页重复或遗漏；
+/// 只有位置之后的成员改变了排序键（改角色）才可能换到已翻过的页。
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemberCursor {
+    Uid(Arc<str>),
+    Role(RoleKey),
+}
+
+impl MemberCursor {
+    /// 成员在 `order` 排序下的键
+    pub fn of(order: MemberOrder, member: &MemberRef) -> Self {
+        match order {
+            MemberOrder::ByUid => MemberCursor::Uid(Arc::from(member.id.as_str())),
+            MemberOrder::ByRole => MemberCursor::Role(RoleKey::of(member)),
+        }
+    }
+
+    pub fn order(&self) -> MemberOrder {
+        match self {
+            MemberCursor::Uid(_) => MemberOrder::ByUid,
+            MemberCursor::Role(_) => MemberOrder::ByRole,
+        }
+    }
+
+    /// 编码为对调用方不透明的游标（十六进制）
+    pub fn encode(&self) -> String {
+        let mut buf = BytesMut::new();
+        match self {
+            MemberCursor::Uid(id) => {
+                buf.put_u8(CURSOR_UID);
+                buf.put_slice(id.as_bytes());
+            }
+            MemberCursor::Role(key) => {
+                buf.put_u8(CURSOR_ROLE);
+                buf.put_i32(key.role);
+                buf.put_u64(key.join_time);
+                buf.put_slice(key.id.as_bytes());
+            }
+        }
+        buf.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// 解析游标，格式不对时返回 None
+    pub fn decode(cursor: &str) -> Option<Self> {
+        if cursor.len() % 2 != 0 {
+            return None;
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let mut buf = bytes.as_slice();
+        if !buf.has_remaining() {
+            return None;
+        }
+        match buf.get_u8() {
+            CURSOR_UID => Some(MemberCursor::Uid(Arc::from(std::str::from_utf8(buf).ok()?))),
+            CURSOR_ROLE if buf.remaining() >= 12 => {
+                let role = buf.get_i32();
+                let join_time = buf.get_u64();
+                let id = Arc::from(std::str::from_utf8(buf).ok()?);
+                Some(MemberCursor::Role(RoleKey { role, join_time, id }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 一页成员及下一页的位置（没有更多时为 None）
+#[derive(Debug, Clone, Default)]
+pub struct MemberPage {
+    pub members: Vec<MemberRef>,
+    pub next_cursor: Option<MemberCursor>,
+}
+
+impl MemberPage {
+    /// 由按序取出的至多 `limit + 1` 个成员组成一页：多取的一个只用于判断是否还有下一页
+    pub fn from_sorted(order: MemberOrder, mut members: Vec<MemberRef>, limit: usize) -> Self {
+        let next_cursor = if members.len() > limit {
+            members.truncate(limit);
+            members.last().map(|member| MemberCursor::of(order, member))
+        } else {
+            None
+        };
+        Self { members, next_cursor }
+    }
+}
+
+/// 成员的有序索引：按 user_id、按角色各一棵有序集合，定位分页位置后顺序读取，
+/// 取一页的开销与页大小相关而与成员总数无关；按角色的集合同时用于按角色查询
+#[derive(Debug, Default)]
+pub struct MemberIndex {
+    by_id: BTreeSet<Arc<str>>,
+    by_role: BTreeSet<RoleKey>,
+}
+
+impl MemberIndex {
+    pub fn insert(&mut self, member: &MemberRef) {
+        let key = RoleKey::of(member);
+        self.by_id.insert(key.id.clone());
+        self.by_role.insert(key);
+    }
+
+    pub fn remove(&mut self, member: &MemberRef) {
+        self.by_id.remove(member.id.as_str());
+        self.by_role.remove(&RoleKey::of(member));
+    }
+
+    pub fn clear(&mut self) {
+        self.by_id.clear();
+        self.by_role.clear();
+    }
+
+    /// 指定角色的 user_id（按入群时间），开销与结果数相关
+    pub fn ids_with_role(&self, role: i32) -> Vec<Arc<str>> {
+        let from = RoleKey {
+            role,
+            join_time: 0,
+            id: Arc::from(""),
+        };
+        self.by_role.range(from..).take_while(|key| key.role == role).map(|key| key.id.clone()).collect()
+    }
+
+    /// 按 `order` 排序、位于 `after` 之后的 user_id，跳过 `skip` 个后至多取 `limit` 个
+    pub fn ids_after(&self, order: MemberOrder, after: Option<&MemberCursor>, skip: usize, limit: usize) -> Vec<Arc<str>> {
+        match (order, after) {
+            (MemberOrder::ByUid, Some(MemberCursor::Uid(id))) => self
+                .by_id
+                .range::<str, _>((Bound::Excluded(id.as_ref()), Bound::Unbounded))
+                .skip(skip)
+                .take(limit)
+                .cloned()
+                .collect(),
+            (MemberOrder::ByRole, Some(MemberCursor::Role(key))) => self
+                .by_role
+                .range((Bound::Excluded(key), Bound::Unbounded))
+                .skip(skip)
+                .take(limit)
+                .map(|key| key.id.clone())
+                .collect(),
+            (MemberOrder::ByUid, _) => self.by_id.iter().skip(skip).take(limit).cloned().collect(),
+            (MemberOrder::ByRole, _) => self.by_role.iter().skip(skip).take(limit).map(|key| key.id.clone()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemberCursor, MemberIndex, RoleKey};
+    use biz_core::protocol::arb::arb_models::MemberRef;
+    use biz_core::protocol::arb::shard_service::MemberOrder;
+    use std::sync::Arc;
+
+    fn member(id: &str, role: i32, join_time: u64) -> MemberRef {
+        MemberRef {
+            id: id.to_string(),
+            role,
+            join_time,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursors = [
+            MemberCursor::Uid(Arc::from("u1")),
+            MemberCursor::Role(RoleKey {
+                role: 2,
+                join_time: 1_700_000_000_000,
+                id: Arc::from("用户"),
+            }),
+        ];
+        for cursor in cursors {
+            assert_eq!(MemberCursor::decode(&cursor.encode()), Some(cursor));
+        }
+        assert!(MemberCursor::decode("").is_none());
+        assert!(MemberCursor::decode("02ff").is_none());
+        assert!(MemberCursor::decode("zz").is_none());
+    }
+
+    #[test]
+    fn test_pages_stay_stable_under_concurrent_changes() {
+        let mut index = MemberIndex::default();
+        for i in 0..10 {
+            index.insert(&member(&format!("u{}", i), 2, 100 + i));
+        }
+        index.insert(&member("owner", 0, 500));
+
+        let first = index.ids_after(MemberOrder::ByRole, None, 0, 3);
+        assert_eq!(first.iter().map(|id| id.as_ref()).collect::<Vec<_>>(), ["owner", "u0", "u1"]);
+
+        // 翻页期间已翻过的成员退出、有新成员加入
+        index.remove(&member("u0", 2, 100));
+        index.insert(&member("late", 2, 1_000));
+        let cursor = MemberCursor::Role(RoleKey {
+            role: 2,
+            join_time: 101,
+            id: Arc::from("u1"),
+        });
+        let second = index.ids_after(MemberOrder::ByRole, Some(&cursor), 0, 3);
+        assert_eq!(second.iter().map(|id| id.as_ref()).collect::<Vec<_>>(), ["u2", "u3", "u4"]);
+
+        let by_uid = index.ids_after(MemberOrder::ByUid, Some(&MemberCursor::Uid(Arc::from("u8"))), 0, 10);
+        assert_eq!(by_uid.iter().map(|id| id.as_ref()).collect::<Vec<_>>(), ["u9"]);
+
+        // 改角色后出现在新角色下
+        index.remove(&member("u5", 2, 105));
+        index.insert(&member("u5", 1, 105));
+        assert_eq!(index.ids_with_role(0).iter().map(|id| id.as_ref()).collect::<Vec<_>>(), ["owner"]);
+        assert_eq!(index.ids_with_role(1).iter().map(|id| id.as_ref()).collect::<Vec<_>>(), ["u5"]);
+        assert_eq!(index.ids_with_role(2).len(), 9);
+    }
+}
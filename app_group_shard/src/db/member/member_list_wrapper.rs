@@ -1,7 +1,9 @@
+use crate::db::member::member_index::{MemberCursor, MemberPage};
 use crate::db::member::sharded_member_list::ShardedMemberList;
 use crate::db::member::simple_member_list::SimpleMemberList;
 use crate::error::member_list_error::MemberListError;
 use arc_swap::ArcSwap;
+use biz_core::protocol::arb::shard_service::MemberOrder;
 use biz_core::protocol::common::GroupRoleType;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
@@ -119,8 +121,12 @@ impl MemberListWrapper {
         self.mutate_bool_with_retry(|s| s.remove(id), |sh| sh.remove(id))
     }
 
-    pub fn get_page(&self, page: usize, page_size: usize) -> Vec<MemberRef> {
-        self.with(|s| s.get_page(page, page_size), |sh| sh.get_page(page, page_size))
+    pub fn get_page(&self, order: MemberOrder, after: Option<&MemberCursor>, skip: usize, limit: usize) -> MemberPage {
+        self.with(|s| s.get_page(order, after, skip, limit), |sh| sh.get_page(order, after, skip, limit))
+    }
+
+    pub fn members_with_role(&self, role: GroupRoleType, online_only: bool) -> Vec<MemberRef> {
+        self.with(|s| s.members_with_role(role, online_only), |sh| sh.members_with_role(role, online_only))
     }
 
     pub fn clear(&self) -> Result<(), MemberListError> {
@@ -173,6 +179,15 @@ impl MemberListWrapper {
         self.mutate_with_retry(|s| s.set_role(user_id, role), |sh| sh.set_role(user_id, role))
     }
 
+    /// 修改成员的部分字段（禁言、别名等），成员不存在时返回 false
+    pub fn update(&self, id: &str, f: impl Fn(&mut MemberRef)) -> Result<bool, MemberListError> {
+        self.mutate_bool_with_retry(|s| s.update(id, &f), |sh| sh.update(id, &f))
+    }
+
+    pub fn get(&self, id: &str) -> Option<MemberRef> {
+        self.with(|s| s.get(id), |sh| sh.get(id))
+    }
+
     /// 根据当前大小和 hysteresis 规则决定是否要平滑调整（升级/扩容/收缩/降级），成功替换时 bump epoch
     fn try_resize_if_needed(&self) {
         let current_len = self.len();
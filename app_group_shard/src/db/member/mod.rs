@@ -1,3 +1,4 @@
+pub mod member_index;
 pub mod member_list_wrapper;
 pub mod sharded_member_list;
 pub mod simple_member_list;
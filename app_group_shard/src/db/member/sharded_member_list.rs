@@ -2,7 +2,9 @@ use std::hash::Hasher;
 use std::sync::Arc;
 use twox_hash::XxHash64;
 use biz_core::protocol::arb::arb_models::MemberRef;
+use crate::db::member::member_index::{MemberCursor, MemberPage};
 use crate::db::member::simple_member_list::SimpleMemberList;
+use biz_core::protocol::arb::shard_service::MemberOrder;
 use biz_core::protocol::common::GroupRoleType;
 
 /// 支持分片的成员列表，内部每个 shard 是一个 SimpleMemberList。
@@ -57,15 +59,20 @@ impl ShardedMemberList {
         self.shards.iter().flat_map(|s| s.get_all()).collect()
     }
 
-    /// 分页：合并后统一分页
-    pub fn get_page(&self, page: usize, page_size: usize) -> Vec<MemberRef> {
-        let mut all = self.get_all();
-        let start = page.saturating_mul(page_size);
-        if start >= all.len() {
-            return Vec::new();
-        }
-        let end = (start + page_size).min(all.len());
-        all.drain(start..end).collect()
+    /// 游标分页：各 shard 各自按序取出 `after` 之后的前 `skip + limit + 1` 个，归并后跳过 `skip` 个取一页
+    pub fn get_page(&self, order: MemberOrder, after: Option<&MemberCursor>, skip: usize, limit: usize) -> MemberPage {
+        let take = skip.saturating_add(limit).saturating_add(1);
+        let mut merged: Vec<MemberRef> = self.shards.iter().flat_map(|s| s.members_after(order, after, 0, take)).collect();
+        merged.sort_by_cached_key(|member| MemberCursor::of(order, member));
+        let page = merged.into_iter().skip(skip).take(limit.saturating_add(1)).collect();
+        MemberPage::from_sorted(order, page, limit)
+    }
+
+    /// 指定角色的成员（合并各 shard 后按入群时间排序）
+    pub fn members_with_role(&self, role: GroupRoleType, online_only: bool) -> Vec<MemberRef> {
+        let mut members: Vec<MemberRef> = self.shards.iter().flat_map(|s| s.members_with_role(role, online_only)).collect();
+        members.sort_by_cached_key(|member| MemberCursor::of(MemberOrder::ByRole, member));
+        members
     }
 
     /// 在线 id 列表（合并所有 shard）
@@ -103,6 +110,15 @@ impl ShardedMemberList {
         self.shard(id).set_role(id, role);
     }
 
+    /// 修改成员的部分字段
+    pub fn update(&self, id: &str, f: impl Fn(&mut MemberRef)) -> bool {
+        self.shard(id).update(id, f)
+    }
+
+    pub fn get(&self, id: &str) -> Option<MemberRef> {
+        self.shard(id).get(id)
+    }
+
     /// 清空所有 shard（保留 shard_count 结构）
     pub fn clear(&self) {
         for shard in &self.shards {
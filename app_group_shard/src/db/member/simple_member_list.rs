@@ -1,6 +1,8 @@
+use crate::db::member::member_index::{MemberCursor, MemberIndex, MemberPage};
+use biz_core::protocol::arb::shard_service::MemberOrder;
 use biz_core::protocol::common::GroupRoleType;
 use dashmap::{DashMap, DashSet};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use biz_core::protocol::arb::arb_models::MemberRef;
 
 /// 简单的成员列表，适用于成员量较小时，内部用 dashmap 做并发。
@@ -10,13 +12,20 @@ pub struct SimpleMemberList {
     members: DashMap<Arc<str>, Arc<MemberRef>>,
     /// 在线 user_id 集合 (Arc<str>)
     online: DashSet<Arc<str>>,
+    /// 有序索引（分页用）；修改成员时先取此写锁，与 members 保持一致
+    index: RwLock<MemberIndex>,
 }
 
 impl SimpleMemberList {
     pub fn add(&self, member: MemberRef) {
         // 将 String 转为 Arc<str> 作为键
         let id_arc: Arc<str> = Arc::from(member.id.clone().into_boxed_str());
-        self.members.insert(id_arc.clone(), Arc::new(member));
+        let member = Arc::new(member);
+        let mut index = self.index.write().unwrap();
+        if let Some(old) = self.members.insert(id_arc, member.clone()) {
+            index.remove(&old);
+        }
+        index.insert(&member);
     }
 
     pub fn add_many(&self, items: Vec<MemberRef>) {
@@ -27,7 +36,14 @@ impl SimpleMemberList {
 
     pub fn remove(&self, id: &str) -> bool {
         let key = Arc::from(id.to_string().into_boxed_str());
-        let removed = self.members.remove(&key).is_some();
+        let mut index = self.index.write().unwrap();
+        let removed = match self.members.remove(&key) {
+            Some((_, old)) => {
+                index.remove(&old);
+                true
+            }
+            None => false,
+        };
         self.online.remove(&key);
         removed
     }
@@ -36,16 +52,30 @@ impl SimpleMemberList {
         self.members.len()
     }
 
-    /// 按 user_id 排序后的分页（保证结果稳定性）
-    pub fn get_page(&self, page: usize, page_size: usize) -> Vec<MemberRef> {
-        let mut all: Vec<MemberRef> = self.members.iter().map(|entry| entry.value().as_ref().clone()).collect();
-        all.sort_by(|a, b| a.id.cmp(&b.id));
-        let start = page.saturating_mul(page_size);
-        if start >= all.len() {
-            return Vec::new();
-        }
-        let end = (start + page_size).min(all.len());
-        all[start..end].to_vec()
+    /// 按 `order` 排序、位于 `after` 之后跳过 `skip` 个的至多 `limit` 个成员（按序）
+    pub fn members_after(&self, order: MemberOrder, after: Option<&MemberCursor>, skip: usize, limit: usize) -> Vec<MemberRef> {
+        let index = self.index.read().unwrap();
+        index
+            .ids_after(order, after, skip, limit)
+            .into_iter()
+            .filter_map(|id| self.members.get(&id).map(|entry| entry.value().as_ref().clone()))
+            .collect()
+    }
+
+    /// 游标分页：从 `after` 之后（为 None 时从头跳过 `skip` 个）取一页
+    pub fn get_page(&self, order: MemberOrder, after: Option<&MemberCursor>, skip: usize, limit: usize) -> MemberPage {
+        MemberPage::from_sorted(order, self.members_after(order, after, skip, limit.saturating_add(1)), limit)
+    }
+
+    /// 指定角色的成员（按入群时间），`online_only` 时只取在线成员
+    pub fn members_with_role(&self, role: GroupRoleType, online_only: bool) -> Vec<MemberRef> {
+        let index = self.index.read().unwrap();
+        index
+            .ids_with_role(role as i32)
+            .into_iter()
+            .filter(|id| !online_only || self.online.contains(id))
+            .filter_map(|id| self.members.get(&id).map(|entry| entry.value().as_ref().clone()))
+            .collect()
     }
 
     pub fn get_all(&self) -> Vec<MemberRef> {
@@ -83,15 +113,34 @@ impl SimpleMemberList {
     }
 
     pub fn set_role(&self, id: &str, role: GroupRoleType) {
-        let key = Arc::from(id.to_string().into_boxed_str());
-        if let Some(mut entry) = self.members.get_mut(&key) {
-            let mut updated = (*entry).as_ref().clone();
-            updated.role = role as i32;
-            *entry = Arc::new(updated);
+        self.update(id, |member| member.role = role as i32);
+    }
+
+    /// 修改成员的部分字段，成员不存在时返回 false
+    pub fn update(&self, id: &str, f: impl Fn(&mut MemberRef)) -> bool {
+        let key: Arc<str> = Arc::from(id.to_string().into_boxed_str());
+        let mut index = self.index.write().unwrap();
+        match self.members.get_mut(&key) {
+            Some(mut entry) => {
+                let mut updated = (*entry).as_ref().clone();
+                f(&mut updated);
+                index.remove(&entry);
+                index.insert(&updated);
+                *entry = Arc::new(updated);
+                true
+            }
+            None => false,
         }
     }
 
+    pub fn get(&self, id: &str) -> Option<MemberRef> {
+        let key: Arc<str> = Arc::from(id.to_string().into_boxed_str());
+        self.members.get(&key).map(|entry| entry.value().as_ref().clone())
+    }
+
     pub fn clear(&self) {
+        let mut index = self.index.write().unwrap();
+        index.clear();
         self.members.clear();
         self.online.clear();
     }
@@ -1,3 +1,4 @@
 pub mod hash_shard_map;
 pub mod intern_pool;
 pub mod member;
+pub mod shard_store;
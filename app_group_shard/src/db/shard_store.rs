@@ -0,0 +1,515 @@
+use crate::db::hash_shard_map::HashShardMap;
+use biz_core::protocol::arb::arb_models::MemberRef;
+use biz_core::protocol::common::GroupRoleType;
+use bytes::{Buf, BufMut, BytesMut};
+use common::util::date_util::now;
+use common::util::hash_ring::HashRing;
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hasher;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use twox_hash::XxHash64;
+
+const SNAPSHOT_FILE: &str = "shard.snapshot";
+const SNAPSHOT_MAGIC: &[u8; 4] = b"GSNP";
+/// 2：成员增加禁言截止时间、别名、入群时间；3：头部增加快照时的哈希环
+const SNAPSHOT_VERSION: u8 = 3;
+const LOG_PREFIX: &str = "shard.log.";
+
+/// 变更日志中的一条操作，重放时按顺序作用在快照上（同一成员以最后一次操作为准，可重复重放）
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShardOp {
+    /// 添加成员（已存在则覆盖）
+    Add { group_id: String, member: MemberRef },
+    Remove { group_id: String, uid: String },
+    ChangeRole { group_id: String, uid: String, role: i32 },
+    /// 禁言截止时间（毫秒，0 为解除禁言）
+    Mute { group_id: String, uid: String, mute_until: u64 },
+    SetAlias { group_id: String, uid: String, alias: String },
+    /// 整组覆盖（移交接收、从数据库重新加载）
+    Replace { group_id: String, members: Vec<MemberRef> },
+    /// 删除群组
+    Clear { group_id: String },
+}
+
+impl ShardOp {
+    pub fn group_id(&self) -> &str {
+        match self {
+            ShardOp::Add { group_id, .. }
+            | ShardOp::Remove { group_id, .. }
+            | ShardOp::ChangeRole { group_id, .. }
+            | ShardOp::Mute { group_id, .. }
+            | ShardOp::SetAlias { group_id, .. }
+            | ShardOp::Replace { group_id, .. }
+            | ShardOp::Clear { group_id } => group_id,
+        }
+    }
+
+    fn encode(&self, buf: &mut BytesMut) {
+        match self {
+            ShardOp::Add { group_id, member } => {
+                buf.put_u8(1);
+                put_str(buf, group_id);
+                put_member(buf, member);
+            }
+            ShardOp::Remove { group_id, uid } => {
+                buf.put_u8(2);
+                put_str(buf, group_id);
+                put_str(buf, uid);
+            }
+            ShardOp::ChangeRole { group_id, uid, role } => {
+                buf.put_u8(3);
+                put_str(buf, group_id);
+                put_str(buf, uid);
+                buf.put_i32(*role);
+            }
+            ShardOp::Replace { group_id, members } => {
+                buf.put_u8(4);
+                put_str(buf, group_id);
+                put_members(buf, members);
+            }
+            ShardOp::Clear { group_id } => {
+                buf.put_u8(5);
+                put_str(buf, group_id);
+            }
+            ShardOp::Mute { group_id, uid, mute_until } => {
+                buf.put_u8(6);
+                put_str(buf, group_id);
+                put_str(buf, uid);
+                buf.put_u64(*mute_until);
+            }
+            ShardOp::SetAlias { group_id, uid, alias } => {
+                buf.put_u8(7);
+                put_str(buf, group_id);
+                put_str(buf, uid);
+                put_str(buf, alias);
+            }
+        }
+    }
+
+    fn decode(buf: &mut &[u8]) -> Option<Self> {
+        let op = get_u8(buf)?;
+        let group_id = get_str(buf)?;
+        Some(match op {
+            1 => ShardOp::Add {
+                group_id,
+                member: get_member(buf)?,
+            },
+            2 => ShardOp::Remove {
+                group_id,
+                uid: get_str(buf)?,
+            },
+            3 => ShardOp::ChangeRole {
+                group_id,
+                uid: get_str(buf)?,
+                role: get_i32(buf)?,
+            },
+            4 => ShardOp::Replace {
+                group_id,
+                members: get_members(buf)?,
+            },
+            5 => ShardOp::Clear { group_id },
+            6 => ShardOp::Mute {
+                group_id,
+                uid: get_str(buf)?,
+                mute_until: get_u64(buf)?,
+            },
+            7 => ShardOp::SetAlias {
+                group_id,
+                uid: get_str(buf)?,
+                alias: get_str(buf)?,
+            },
+            _ => return None,
+        })
+    }
+
+    /// 作用到 `map`
+    pub fn apply(self, map: &HashShardMap) {
+        let result = match self {
+            ShardOp::Add { group_id, member } => map.insert(group_id, member),
+            ShardOp::Remove { group_id, uid } => map.remove(&group_id, &uid).map(|_| ()),
+            ShardOp::ChangeRole { group_id, uid, role } => match GroupRoleType::try_from(role) {
+                Ok(role) => map.change_role(&group_id, &uid, role),
+                Err(_) => Ok(()),
+            },
+            ShardOp::Mute { group_id, uid, mute_until } => map.update_member(&group_id, &uid, |member| member.mute_until = mute_until).map(|_| ()),
+            ShardOp::SetAlias { group_id, uid, alias } => map.update_member(&group_id, &uid, |member| member.alias = alias.clone()).map(|_| ()),
+            ShardOp::Replace { group_id, members } => {
+                map.clear(&group_id);
+                map.insert_many(&group_id, members)
+            }
+            ShardOp::Clear { group_id } => {
+                map.clear(&group_id);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            log::warn!("⚠️ 重放变更日志失败: {:?}", e);
+        }
+    }
+}
+
+/// 当前写入的日志段
+#[derive(Debug)]
+struct LogSegment {
+    seq: u64,
+    writer: BufWriter<File>,
+}
+
+/// 群组成员的本地持久化：紧凑的二进制快照 + 追加写的变更日志，节点重启时据此恢复，
+/// 只需与数据库对账快照之后变化过的群组，不必全量扫描。
+///
+/// - `shard.snapshot`：快照时间、已并入快照的日志段序号、快照时的哈希环（虚拟节点数及节点）、
+///   各群组的成员及角色，末尾为整体校验和；
+/// - `shard.log.{seq}`：日志段，每条记录为 `长度 | 校验和 | 操作`。
+///
+/// 做快照时先切换到新的日志段再读取内存，写入成功后删除已并入的旧日志段；
+/// 快照中可能已包含新日志段开头的部分操作，重放时重复作用不影响结果。
+/// 在线状态不落盘，由上线请求重新建立。
+#[derive(Debug)]
+pub struct ShardStore {
+    dir: PathBuf,
+    log: Mutex<LogSegment>,
+    /// 串行化快照，避免两次快照交错删除日志段
+    snapshot_lock: Mutex<()>,
+}
+
+impl ShardStore {
+    /// 打开数据目录；总是新开一个日志段，不在上次可能写了一半的日志段后追加
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let seq = log_segments(&dir)?.last().map_or(1, |(seq, _)| seq + 1);
+        let writer = open_segment(&dir, seq)?;
+        Ok(Self {
+            dir,
+            log: Mutex::new(LogSegment { seq, writer }),
+            snapshot_lock: Mutex::new(()),
+        })
+    }
+
+    /// 追加一条变更（先修改内存再记录，快照才不会漏掉已切走的日志段中的操作）；
+    /// 写入失败只记录日志，重启时由数据库对账兜底
+    pub fn append(&self, op: &ShardOp) {
+        let mut payload = BytesMut::new();
+        op.encode(&mut payload);
+        let mut record = BytesMut::with_capacity(payload.len() + 12);
+        record.put_u32(payload.len() as u32);
+        record.put_u64(checksum(&payload));
+        record.put_slice(&payload);
+        let mut segment = self.log.lock().unwrap();
+        if let Err(e) = segment.writer.write_all(&record) {
+            log::warn!("⚠️ 写入变更日志 {} 失败: {:?}", segment.seq, e);
+        }
+    }
+
+    /// 把缓冲的变更写入文件
+    pub fn flush(&self) {
+        let mut segment = self.log.lock().unwrap();
+        if let Err(e) = segment.writer.flush() {
+            log::warn!("⚠️ 刷新变更日志 {} 失败: {:?}", segment.seq, e);
+        }
+    }
+
+    /// 加载快照并重放其后的日志段，只保留 `keep` 为真的群组；
+    /// 返回快照时间（毫秒）及快照时的哈希环，没有快照时返回 None
+    pub fn load(&self, map: &HashShardMap, keep: impl Fn(&str) -> bool) -> io::Result<Option<(u64, HashRing)>> {
+        let path = self.dir.join(SNAPSHOT_FILE);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let (created_at, next_seq, ring) = decode_snapshot(&data, |group_id, members| {
+            if keep(&group_id) {
+                if let Err(e) = map.insert_many(&group_id, members) {
+                    log::warn!("⚠️ 加载快照群组 {} 失败: {:?}", group_id, e);
+                }
+            }
+        })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("corrupted snapshot {}", path.display())))?;
+
+        let mut replayed = 0;
+        for (seq, path) in log_segments(&self.dir)? {
+            if seq < next_seq {
+                continue;
+            }
+            let data = fs::read(&path)?;
+            let (ops, complete) = decode_log(&data);
+            if !complete {
+                log::warn!("⚠️ 变更日志 {} 末尾不完整，已忽略", path.display());
+            }
+            for op in ops.into_iter().filter(|op| keep(op.group_id())) {
+                op.apply(map);
+                replayed += 1;
+            }
+        }
+        log::info!("💾 从本地快照恢复 {} 个群组，重放变更 {} 条", map.all_keys().len(), replayed);
+        Ok(Some((created_at, ring)))
+    }
+
+    /// 把 `map` 连同其所依据的哈希环写为新快照，返回写入的群组数
+    pub fn save_snapshot(&self, map: &HashShardMap, ring: &HashRing) -> io::Result<usize> {
+        let _guard = self.snapshot_lock.lock().unwrap();
+        // 先切换日志段：此后的变更写入新段，快照读取的内存已包含旧段中的全部变更
+        let next_seq = {
+            let mut segment = self.log.lock().unwrap();
+            segment.writer.flush()?;
+            let seq = segment.seq + 1;
+            segment.writer = open_segment(&self.dir, seq)?;
+            segment.seq = seq;
+            seq
+        };
+        let created_at = now() as u64;
+        let groups: Vec<(String, Vec<MemberRef>)> = map.all_keys().into_iter().map(|key| (key.to_string(), map.get_member_by_key(&key))).collect();
+        let data = encode_snapshot(created_at, next_seq, ring, &groups);
+
+        let tmp = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let mut file = File::create(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.dir.join(SNAPSHOT_FILE))?;
+
+        for (seq, path) in log_segments(&self.dir)? {
+            if seq < next_seq {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(groups.len())
+    }
+}
+
+fn open_segment(dir: &Path, seq: u64) -> io::Result<BufWriter<File>> {
+    let file = OpenOptions::new().create(true).append(true).open(dir.join(format!("{}{}", LOG_PREFIX, seq)))?;
+    Ok(BufWriter::new(file))
+}
+
+/// 目录中的日志段，按序号升序
+fn log_segments(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let seq = path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_prefix(LOG_PREFIX)).and_then(|seq| seq.parse().ok());
+        if let Some(seq) = seq {
+            segments.push((seq, path));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+fn checksum(data: &[u8]) -> u64 {
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write(data);
+    hasher.finish()
+}
+
+fn encode_snapshot(created_at: u64, next_seq: u64, ring: &HashRing, groups: &[(String, Vec<MemberRef>)]) -> BytesMut {
+    let mut buf = BytesMut::new();
+    buf.put_slice(SNAPSHOT_MAGIC);
+    buf.put_u8(SNAPSHOT_VERSION);
+    buf.put_u64(created_at);
+    buf.put_u64(next_seq);
+    buf.put_u32(ring.virtual_nodes() as u32);
+    buf.put_u32(ring.len() as u32);
+    for node in ring.nodes() {
+        put_str(&mut buf, node);
+    }
+    buf.put_u32(groups.len() as u32);
+    for (group_id, members) in groups {
+        put_str(&mut buf, group_id);
+        put_members(&mut buf, members);
+    }
+    let sum = checksum(&buf);
+    buf.put_u64(sum);
+    buf
+}
+
+/// 校验并解码快照，逐个群组交给 `on_group`；返回 (快照时间, 已并入的日志段序号, 快照时的哈希环)
+fn decode_snapshot(data: &[u8], mut on_group: impl FnMut(String, Vec<MemberRef>)) -> Option<(u64, u64, HashRing)> {
+    let (body, mut sum) = data.split_at_checked(data.len().checked_sub(8)?)?;
+    if sum.get_u64() != checksum(body) {
+        return None;
+    }
+    let mut buf = body.strip_prefix(SNAPSHOT_MAGIC.as_slice())?;
+    if get_u8(&mut buf)? != SNAPSHOT_VERSION {
+        return None;
+    }
+    let created_at = get_u64(&mut buf)?;
+    let next_seq = get_u64(&mut buf)?;
+    let mut ring = HashRing::new(get_u32(&mut buf)? as usize);
+    for _ in 0..get_u32(&mut buf)? {
+        ring.add_node(get_str(&mut buf)?);
+    }
+    let count = get_u32(&mut buf)?;
+    for _ in 0..count {
+        let group_id = get_str(&mut buf)?;
+        on_group(group_id, get_members(&mut buf)?);
+    }
+    Some((created_at, next_seq, ring))
+}
+
+/// 解码日志段，遇到不完整或校验失败的记录（写到一半时宕机）即停止；返回操作及是否完整
+fn decode_log(mut data: &[u8]) -> (Vec<ShardOp>, bool) {
+    let mut ops = Vec::new();
+    while data.has_remaining() {
+        let Some(op) = decode_record(&mut data) else {
+            return (ops, false);
+        };
+        ops.push(op);
+    }
+    (ops, true)
+}
+
+fn decode_record(buf: &mut &[u8]) -> Option<ShardOp> {
+    let len = get_u32(buf)? as usize;
+    let sum = get_u64(buf)?;
+    let (mut payload, rest) = buf.split_at_checked(len)?;
+    if checksum(payload) != sum {
+        return None;
+    }
+    *buf = rest;
+    ShardOp::decode(&mut payload)
+}
+
+fn put_str(buf: &mut BytesMut, value: &str) {
+    buf.put_u32(value.len() as u32);
+    buf.put_slice(value.as_bytes());
+}
+
+fn put_member(buf: &mut BytesMut, member: &MemberRef) {
+    put_str(buf, &member.id);
+    buf.put_i32(member.role);
+    buf.put_u64(member.mute_until);
+    put_str(buf, &member.alias);
+    buf.put_u64(member.join_time);
+}
+
+fn put_members(buf: &mut BytesMut, members: &[MemberRef]) {
+    buf.put_u32(members.len() as u32);
+    for member in members {
+        put_member(buf, member);
+    }
+}
+
+fn get_u8(buf: &mut &[u8]) -> Option<u8> {
+    (buf.remaining() >= 1).then(|| buf.get_u8())
+}
+
+fn get_u32(buf: &mut &[u8]) -> Option<u32> {
+    (buf.remaining() >= 4).then(|| buf.get_u32())
+}
+
+fn get_i32(buf: &mut &[u8]) -> Option<i32> {
+    (buf.remaining() >= 4).then(|| buf.get_i32())
+}
+
+fn get_u64(buf: &mut &[u8]) -> Option<u64> {
+    (buf.remaining() >= 8).then(|| buf.get_u64())
+}
+
+fn get_str(buf: &mut &[u8]) -> Option<String> {
+    let len = get_u32(buf)? as usize;
+    let (value, rest) = buf.split_at_checked(len)?;
+    let value = String::from_utf8(value.to_vec()).ok()?;
+    *buf = rest;
+    Some(value)
+}
+
+fn get_member(buf: &mut &[u8]) -> Option<MemberRef> {
+    Some(MemberRef {
+        id: get_str(buf)?,
+        role: get_i32(buf)?,
+        mute_until: get_u64(buf)?,
+        alias: get_str(buf)?,
+        join_time: get_u64(buf)?,
+    })
+}
+
+fn get_members(buf: &mut &[u8]) -> Option<Vec<MemberRef>> {
+    let count = get_u32(buf)? as usize;
+    // 长度字段损坏时不按其预分配
+    let mut members = Vec::with_capacity(count.min(buf.remaining() / 8));
+    for _ in 0..count {
+        members.push(get_member(buf)?);
+    }
+    Some(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_log, decode_snapshot, encode_snapshot, ShardOp};
+    use biz_core::protocol::arb::arb_models::MemberRef;
+    use bytes::{BufMut, BytesMut};
+    use common::util::hash_ring::HashRing;
+
+    fn member(id: &str, role: i32) -> MemberRef {
+        MemberRef {
+            id: id.to_string(),
+            role,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_and_corruption() {
+        let groups = vec![("g1".to_string(), vec![member("u1", 0), member("u2", 2)]), ("g2".to_string(), Vec::new())];
+        let ring = HashRing::with_nodes(32, ["127.0.0.1:50001", "127.0.0.1:50002"]);
+        let data = encode_snapshot(1_700_000_000_000, 7, &ring, &groups);
+        let mut decoded = Vec::new();
+        let (created_at, next_seq, decoded_ring) = decode_snapshot(&data, |group_id, members| decoded.push((group_id, members))).unwrap();
+        assert_eq!((created_at, next_seq), (1_700_000_000_000, 7));
+        assert_eq!(decoded_ring.virtual_nodes(), 32);
+        assert!(decoded_ring.diff(&ring).is_empty());
+        assert_eq!(decoded, groups);
+
+        let mut corrupted = data.to_vec();
+        corrupted[10] ^= 0xff;
+        assert!(decode_snapshot(&corrupted, |_, _| {}).is_none());
+        assert!(decode_snapshot(&data[..3], |_, _| {}).is_none());
+    }
+
+    #[test]
+    fn test_log_stops_at_torn_record() {
+        let ops = vec![
+            ShardOp::Add {
+                group_id: "g1".to_string(),
+                member: member("u1", 1),
+            },
+            ShardOp::ChangeRole {
+                group_id: "g1".to_string(),
+                uid: "u1".to_string(),
+                role: 2,
+            },
+            ShardOp::Replace {
+                group_id: "g2".to_string(),
+                members: vec![member("u3", 0)],
+            },
+            ShardOp::Mute {
+                group_id: "g2".to_string(),
+                uid: "u3".to_string(),
+                mute_until: 1_700_000_600_000,
+            },
+            ShardOp::SetAlias {
+                group_id: "g2".to_string(),
+                uid: "u3".to_string(),
+                alias: "小王".to_string(),
+            },
+            ShardOp::Clear { group_id: "g3".to_string() },
+        ];
+        let mut data = BytesMut::new();
+        for op in &ops {
+            let mut payload = BytesMut::new();
+            op.encode(&mut payload);
+            data.put_u32(payload.len() as u32);
+            data.put_u64(super::checksum(&payload));
+            data.put_slice(&payload);
+        }
+        assert_eq!(decode_log(&data), (ops.clone(), true));
+
+        // 最后一条只写了一半
+        let torn = &data[..data.len() - 3];
+        assert_eq!(decode_log(torn), (ops[..ops.len() - 1].to_vec(), false));
+    }
+}
@@ -30,11 +30,12 @@ async fn main() -> std::io::Result<()> {
     manager::init();
     init_service().await;
     service::init_service().await.expect("init service failed");
-    start_grpc(&app_cfg).await;
+    let grpc_cfg = app_cfg.clone();
+    tokio::spawn(async move { start_grpc(&grpc_cfg).await });
     // 2. 构建 ShardManager 实例
     let mut job = ArbManagerJob::new();
-    // 启动任务
-    job.start().await;
+    // 启动任务（状态自检任务常驻，需与 gRPC、HTTP 服务并行）
+    tokio::spawn(async move { job.start().await });
 
     HttpServer::new(move || {
         App::new()
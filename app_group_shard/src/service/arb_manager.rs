@@ -1,23 +1,29 @@
+use crate::service::rebalance_plan::RebalancePlan;
 use crate::service::shard_manager::ShardManager;
 use async_trait::async_trait;
 use common::config::AppConfig;
+use common::util::date_util::now;
+use common::util::hash_ring::HashRing;
 use once_cell::sync::OnceCell;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
 use tokio::time::sleep;
 use tonic::transport::Channel;
-use biz_core::protocol::arb::arb_models::ShardState::{Migrating, Normal, Preparing, Ready, Registered};
+use biz_core::protocol::arb::arb_models::ShardState::{Migrating, Normal, Preparing, Ready, Registered, Syncing};
+use biz_core::protocol::arb::arb_models::{NodeType, QueryNodeReq, ShardState, UpdateShardStateRequest};
 use biz_core::protocol::arb::arb_server::arb_server_rpc_service_client::ArbServerRpcServiceClient;
+use biz_core::service::rpc_server_client_service::ArbServerRpcServiceClientService;
 
 #[derive(Debug)]
 pub struct ArbManagerJob {
     pub arb_client: Option<ArbServerRpcServiceClient<Channel>>,
-    pub server_host: String,
     pub shard_address: String,
     pub total: usize,
+    /// 当前再均衡纪元的迁移计划
+    pub plan: Option<RebalancePlan>,
 }
 
 #[async_trait]
@@ -31,10 +37,9 @@ pub trait ManagerJobOpt: Send + Sync {
     /// 将群组分片状态设置为“迁移中”
     /// 通常意味着不再接受新写入，同时准备数据转移
     async fn change_migrating(&mut self) -> anyhow::Result<()>;
-    /// 同步当前群组列表（通常从仲裁服务或中心节点拉取最新群组分配情况）
+    /// 将待移交群组推送到新归属节点，并等待其他节点移交到本节点
     async fn sync_data(&mut self) -> anyhow::Result<()>;
-    /// 设置群组状态为“迁移失败”
-    /// 可用于回滚操作或触发异常迁移重试逻辑
+    /// 迁移失败：从快照回滚，下一轮以新的尝试重试
     async fn change_failed(&mut self) -> anyhow::Result<()>;
 
     /// 设置为“就绪”状态，表示目标节点已接管数据并可激活群组
@@ -55,9 +60,9 @@ impl ArbManagerJob {
         let config1 = &AppConfig::get().clone().shard.clone().unwrap();
         Self {
             arb_client: None,
-            server_host: config1.server_addr.clone().unwrap().clone(),
             shard_address: config1.client_addr.clone().unwrap().clone(),
             total: 0,
+            plan: None,
         }
     }
 
@@ -66,35 +71,41 @@ impl ArbManagerJob {
         self.start_heartbeat_loop();
         self.check_status_task().await;
     }
-    /// 启动分片状态自检任务（后台常驻）
+    /// 启动分片状态自检任务（后台常驻）：每 15 秒执行一次，收到纪元推送等事件时立即执行
     pub async fn check_status_task(&mut self) {
         loop {
-            sleep(Duration::from_secs(15)).await;
-            let shard_manager = ShardManager::get();
-            let current = shard_manager.current.load();
-            let shard_info = current.shard_info.read().await;
+            tokio::select! {
+                _ = sleep(Duration::from_secs(15)) => {}
+                _ = status_wake().notified() => {}
+            }
+            let state = Self::local_state().await;
 
-            if shard_info.state == Registered {
+            if state == Registered {
                 if let Err(e) = self.register_node().await {
                     log::error!("register_node error: {:?}", e);
                 }
             }
-            if shard_info.state == Migrating {
+            if state == Migrating {
                 if let Err(e) = self.change_migrating().await {
                     log::error!("change_migrating error: {:?}", e);
                 }
             }
-            if shard_info.state == Preparing {
+            if state == Preparing {
                 if let Err(e) = self.change_preparing().await {
                     log::error!("Preparing error: {:?}", e);
                 }
             }
-            if shard_info.state == Ready {
+            if state == Syncing {
+                if let Err(e) = self.sync_data().await {
+                    log::error!("Syncing error: {:?}", e);
+                }
+            }
+            if state == Ready {
                 if let Err(e) = self.change_ready().await {
                     log::error!("Ready error: {:?}", e);
                 }
             }
-            if shard_info.state == Normal {
+            if state == Normal {
                 if let Err(e) = self.change_normal().await {
                     log::error!("Normal error: {:?}", e);
                 }
@@ -103,15 +114,62 @@ impl ArbManagerJob {
     }
     /// 停止所有任务
     pub fn stop(&self) {}
+
+    /// 唤醒状态自检任务立即执行一轮（任务正在执行时，结束后马上再执行一轮）
+    pub fn wake() {
+        status_wake().notify_one();
+    }
+
+    /// 本节点状态（存于 current，替换 current 时沿用）
+    pub async fn local_state() -> ShardState {
+        ShardManager::get().current.load_full().shard_info.read().await.state
+    }
+
+    pub async fn set_local_state(state: ShardState) {
+        let current = ShardManager::get().current.load_full();
+        let mut shard_info = current.shard_info.write().await;
+        shard_info.state = state;
+        shard_info.last_update_time = now() as u64;
+    }
+
+    /// 向仲裁上报本节点状态
+    pub async fn report_state(&mut self, state: ShardState) -> anyhow::Result<()> {
+        let node_addr = self.shard_address.clone();
+        let client = self.init_arb_client().await?;
+        let response = client
+            .update_shard_state(UpdateShardStateRequest {
+                node_addr,
+                new_state: state as i32,
+            })
+            .await?;
+        if !response.into_inner().success {
+            log::warn!("⚠️ 节点 {} 上报状态 {:?} 失败", self.shard_address, state);
+        }
+        Ok(())
+    }
+    /// 仲裁客户端：复用进程内共享的连接（`ArbServerRpcServiceClientService`）
     pub async fn init_arb_client(&mut self) -> anyhow::Result<&mut ArbServerRpcServiceClient<Channel>> {
         if self.arb_client.is_none() {
-            let node_addr = self.server_host.clone();
-            let client = ArbServerRpcServiceClient::connect(format!("http://{}", node_addr)).await?;
+            let client = ArbServerRpcServiceClientService::get().client.lock().await.clone();
             self.arb_client = Some(client);
         }
         Ok(self.arb_client.as_mut().unwrap())
     }
 
+    /// 拉取当前全部群分片节点并构建一致性哈希环（包含本节点），同时返回仲裁的分片纪元
+    pub async fn load_ring(&mut self) -> anyhow::Result<(HashRing, u64)> {
+        let client = self.init_arb_client().await?;
+        let response = client
+            .list_all_nodes(QueryNodeReq {
+                node_type: NodeType::GroupNode as i32,
+            })
+            .await?
+            .into_inner();
+        let mut ring = HashRing::from_arb(response.virtual_nodes, response.nodes.into_iter().map(|node| node.node_addr));
+        ring.add_node(self.shard_address.clone());
+        Ok((ring, response.shard_epoch))
+    }
+
     fn start_heartbeat_loop(&mut self) {
         let mut this = self.clone_light();
         Some(tokio::spawn(async move {
@@ -134,9 +192,9 @@ impl ArbManagerJob {
     pub fn clone_light(&self) -> ArbManagerJob {
         ArbManagerJob {
             arb_client: None, // 避免 tonic 客户端跨线程问题
-            server_host: self.server_host.clone(),
             shard_address: self.shard_address.clone(),
             total: 0,
+            plan: None,
         }
     }
 
@@ -153,3 +211,8 @@ impl ArbManagerJob {
     }
 }
 static INSTANCE_COUNTRY: OnceCell<Arc<ArbManagerJob>> = OnceCell::new();
+static STATUS_WAKE: OnceCell<Notify> = OnceCell::new();
+
+fn status_wake() -> &'static Notify {
+    STATUS_WAKE.get_or_init(Notify::new)
+}
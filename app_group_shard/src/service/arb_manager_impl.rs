@@ -1,10 +1,17 @@
 use crate::service::arb_manager::{ArbManagerJob, ManagerJobOpt};
-use crate::service::shard_manager::{ShardManager, MEMBER_SHARD_SIZE};
+use crate::service::rebalance_plan::{PlanStep, RebalancePlan};
+use crate::service::shard_manager::{MigrationView, ShardManager};
+use crate::service::shard_sync::stream_groups;
 use common::config::AppConfig;
-use common::util::common_utils::hash_index;
 use common::util::date_util::now;
 use tonic::async_trait;
-use biz_core::protocol::arb::arb_models::{BaseRequest, NodeType, QueryNodeReq, RegRequest, ShardState, SyncListGroup, UpdateShardStateRequest};
+use biz_core::protocol::arb::arb_models::{BaseRequest, NodeType, RegRequest, ShardState, UpdateShardStateRequest};
+use std::collections::HashSet;
+
+/// 等待其他节点移交的最长时间（毫秒），超时后缺失的群组从数据库加载
+const MIGRATION_WAIT_MS: u64 = 120_000;
+/// 同一纪元连续回滚的上限，达到后上报 Failed
+const MAX_MIGRATION_ATTEMPTS: u32 = 3;
 
 #[async_trait]
 impl ManagerJobOpt for ArbManagerJob {
@@ -18,6 +25,8 @@ impl ManagerJobOpt for ArbManagerJob {
             node_addr: config1.clone().client_addr.unwrap(),
             node_type: NodeType::GroupNode as i32,
             kafka_addr: Some(kafka_config.clone().unwrap().brokers),
+            socket_addr: None,
+            ws_addr: None,
         };
         let response = client.register_node(request).await?;
 
@@ -32,186 +41,193 @@ impl ManagerJobOpt for ArbManagerJob {
         Ok(())
     }
 
-    /// 设置某群组迁移状态为“准备中”
-    /// 表示目标节点已准备好接收群组（例如缓存准备、校验完成等）
+    /// 开始（或续跑）一次再均衡：以仲裁当前的分片纪元加载本节点的迁移计划，
+    /// 同一纪元已提交则直接回到正常状态；与迁移前相比本节点归属无变化时直接提交。
     async fn change_preparing(&mut self) -> anyhow::Result<()> {
         let shard_manager = ShardManager::get();
-        let current = shard_manager.current.load();
-        let shard_address = self.shard_address.clone();
-        let mut shard_info = current.shard_info.write().await;
-        let client = self.init_arb_client().await?;
-        let state_request = UpdateShardStateRequest {
-            node_addr: shard_address,
-            new_state: ShardState::Preparing as i32,
-        };
-        shard_info.state = ShardState::Preparing;
-        let response = client.update_shard_state(state_request).await?;
-        if response.into_inner().success {
-            let current = shard_manager.current.load();
-            let mut shard_info = current.shard_info.write().await;
-            shard_info.state = ShardState::Preparing;
-            shard_info.last_update_time = now() as u64;
-            shard_info.last_heartbeat = shard_info.last_update_time;
-            // ✅ 存储快照
-            shard_manager.clone_current_to_snapshot();
-            shard_info.state = ShardState::Migrating;
-            // ✅ 清空 current
-            shard_manager.clear_current();
-            // ✅ 迁移状态已设置为 Preparing 开始同步数据
-            if let Err(e) = self.change_migrating().await {
-                log::error!("❌ sync groups error: {:?}", e);
-            }
-
-            log::info!("🔄 current 分片数据已清空，准备进入迁移流程");
-        }
-        Ok(())
-    }
-    /// 将群组分片状态设置为“迁移中”
-    /// 通常意味着不再接受新写入，同时准备数据转移
-    /// 将分片状态更新为 “迁移中”
-    /// 表示当前分片处于不接受新写入、数据即将转移状态
-    async fn change_migrating(&mut self) -> anyhow::Result<()> {
-        let shard_manager = ShardManager::get();
-        let shard_addr = self.shard_address.clone();
-        // 1️⃣ 获取当前分片快照（snapshot）和当前结构（current）
-        let current = shard_manager.current.load();
-        let guard = shard_manager.snapshot.load();
+        let node_addr = self.shard_address.clone();
+        let before = shard_manager.ring.load_full();
+        let (after, epoch) = self.load_ring().await?;
+        shard_manager.observe_epoch(epoch);
+        let before_nodes: Vec<String> = before.nodes().cloned().collect();
+        let resumable = shard_manager.migration.load_full().is_some_and(|view| view.epoch == epoch);
 
-        // 2️⃣ 获取当前状态写锁
-        let mut shard_info = current.shard_info.write().await;
-
-        let total = self.total;
-        let node_index = hash_index(&self.shard_address, total as i32);
-        let key_list = guard.shard_map.all_keys();
-        for key in key_list.iter() {
-            let hash_index = hash_index(&key, total as i32);
-            if hash_index != node_index {
-                continue; // 只处理当前节点的在线成员
+        let mut plan = match RebalancePlan::load(epoch, &node_addr).await? {
+            // 本纪元已提交（重复触发）
+            Some(plan) if plan.step == PlanStep::Normal && plan.after == before_nodes => {
+                self.plan = Some(plan);
+                return self.change_normal().await;
             }
-            let member_list = guard.shard_map.get_member_by_key(&key);
-            current.shard_map.insert_many(&key, member_list);
-            let on_line_ids = guard.shard_map.get_online_ids(&key);
-            for id in on_line_ids {
-                current.shard_map.set_online(&key, &id, true);
+            // 同一纪元的迁移仍在进行（上一轮某一步失败），从上次的步骤续跑
+            Some(plan) if plan.before == before_nodes && resumable && plan.step != PlanStep::RolledBack => {
+                log::info!("🔁 续跑纪元 {} 的迁移计划，当前步骤 {:?}", epoch, plan.step);
+                plan
             }
-        }
-
-        // 5️⃣ 设置当前状态为 Migrating
-        shard_info.state = ShardState::Migrating;
-        shard_info.last_update_time = now() as u64;
-        shard_info.last_heartbeat = shard_info.last_update_time;
-
-        // 6️⃣ 向仲裁服务上报当前节点状态
-        let client = self.init_arb_client().await?;
-        let state_request = UpdateShardStateRequest {
-            node_addr: shard_addr,
-            new_state: ShardState::Migrating as i32,
+            // 已回滚或计划与本地状态不符（如进程重启），重新开始
+            Some(previous) => {
+                let mut plan = RebalancePlan::new(epoch, &node_addr, &before, &after);
+                plan.attempt = previous.attempt + 1;
+                plan
+            }
+            None => RebalancePlan::new(epoch, &node_addr, &before, &after),
         };
 
-        let response = client.update_shard_state(state_request).await?;
-        if response.into_inner().success {
-            log::info!("✅ 节点 {} 成功设置为迁移中", self.shard_address);
-        } else {
-            log::warn!("⚠️ 节点 {} 设置迁移状态失败", self.shard_address);
+        let involved = before
+            .diff(&after)
+            .iter()
+            .any(|m| m.from.as_deref() == Some(node_addr.as_str()) || m.to.as_deref() == Some(node_addr.as_str()));
+        if !involved {
+            if shard_manager.migration.load().is_some() {
+                // 上一次未完成的迁移已被新的成员变更取代
+                shard_manager.restore_from_snapshot(&before, Some(&after)).await;
+            }
+            shard_manager.commit_migration(after, epoch);
+            plan.advance(PlanStep::Normal).await?;
+            self.plan = Some(plan);
+            return self.change_normal().await;
         }
 
-        Ok(())
+        if plan.step == PlanStep::Preparing {
+            plan.advance(PlanStep::Preparing).await?;
+            Self::set_local_state(ShardState::Preparing).await;
+            self.report_state(ShardState::Preparing).await?;
+            shard_manager
+                .begin_migration(MigrationView {
+                    epoch,
+                    before: (*before).clone(),
+                    after,
+                })
+                .await;
+            log::info!("🔄 纪元 {} 开始迁移（第 {} 次尝试）", epoch, plan.attempt);
+        }
+        let step = plan.step;
+        self.plan = Some(plan);
+        match step {
+            PlanStep::Syncing => self.sync_data().await,
+            PlanStep::Ready => self.change_ready().await,
+            _ => self.change_migrating().await,
+        }
+    }
+
+    /// 快照已切分：待移交群组留在 snapshot 中服务，current 只含仍归属本节点的群组
+    async fn change_migrating(&mut self) -> anyhow::Result<()> {
+        let Some(plan) = self.plan.as_mut() else {
+            Self::set_local_state(ShardState::Preparing).await;
+            return Ok(());
+        };
+        plan.advance(PlanStep::Migrating).await?;
+        Self::set_local_state(ShardState::Migrating).await;
+        self.report_state(ShardState::Migrating).await?;
+        self.sync_data().await
     }
 
+    /// 向新归属节点移交群组（按目标节点记录完成情况，重试时跳过已完成的目标），
+    /// 再等待其他节点移交到本节点；任一目标移交失败即回滚。
     async fn sync_data(&mut self) -> anyhow::Result<()> {
         let shard_manager = ShardManager::get();
-        let shard_addr = self.shard_address.clone();
-        let client = self.init_arb_client().await?;
-
-        let request = QueryNodeReq {
-            node_type: NodeType::GroupNode as i32,
+        let node_addr = self.shard_address.clone();
+        let (Some(view), Some(plan)) = (shard_manager.migration.load_full(), self.plan.as_mut()) else {
+            Self::set_local_state(ShardState::Preparing).await;
+            return Ok(());
         };
-        let response = client.list_all_nodes(request).await?;
-        let nodes = response.into_inner().nodes;
-
-        let endpoints: Vec<String> = nodes.iter().map(|node| node.clone().node_addr).collect();
-        let group_rpc_clients = shard_manager.init_grpc_clients(endpoints).await.expect("init grpc clients error");
+        if plan.step != PlanStep::Syncing {
+            plan.advance(PlanStep::Syncing).await?;
+            Self::set_local_state(ShardState::Syncing).await;
+            self.report_state(ShardState::Syncing).await?;
+        }
+        let plan = self.plan.clone().expect("plan must be set");
 
-        let snapshot = shard_manager.snapshot.load();
-        let current = shard_manager.current.load();
-        let total = self.total;
-        let node_index = hash_index(&self.shard_address, total as i32);
+        if let Err(e) = Self::hand_over(&plan, &node_addr).await {
+            log::error!("❌ 纪元 {} 移交失败，回滚: {:?}", plan.epoch, e);
+            self.change_failed().await?;
+            return Err(e);
+        }
 
-        for key in snapshot.shard_map.all_keys() {
-            let hash_index = hash_index(&key, total as i32);
-            if hash_index != node_index {
-                continue; // 只处理当前节点的在线成员
+        // 仍在环上的源节点需完成移交；已离开环的源节点的群组从数据库加载
+        let sources: HashSet<String> = view
+            .before
+            .diff(&view.after)
+            .into_iter()
+            .filter(|m| m.to.as_deref() == Some(node_addr.as_str()))
+            .filter_map(|m| m.from)
+            .filter(|from| view.after.contains_node(from))
+            .collect();
+        let received = plan.handed_over_to(&node_addr).await?;
+        let mut pending = Vec::new();
+        for source in sources.iter().filter(|source| !received.contains(*source)) {
+            // 源节点已提交却未移交到本节点（本节点回滚过），不再等待
+            let committed = RebalancePlan::load(plan.epoch, source).await?.is_some_and(|p| p.step == PlanStep::Normal);
+            if !committed {
+                pending.push(source.clone());
             }
-
-            let member_list = snapshot.shard_map.get_member_by_key(&key);
-            let sync_data = SyncListGroup {
-                group_id: key.to_string(),
-                members: member_list,
-                on_line_ids: snapshot.shard_map.get_online_ids(&key),
-            };
-            let mut option = group_rpc_clients.get(&hash_index).unwrap().clone();
-            option.sync_data(sync_data).await?;
         }
-
-        let mut shard_info = current.shard_info.write().await;
-        // 5️⃣ 设置当前状态为 Migrating
-        shard_info.state = ShardState::Ready;
-        shard_info.last_update_time = now() as u64;
-        shard_info.last_heartbeat = shard_info.last_update_time;
-
-        // 6️⃣ 向仲裁服务上报当前节点状态
-        let client = self.init_arb_client().await?;
-        let state_request = UpdateShardStateRequest {
-            node_addr: shard_addr,
-            new_state: ShardState::Ready as i32,
-        };
-
-        let response = client.update_shard_state(state_request).await?;
-        if response.into_inner().success {
-            log::info!("✅ 节点 {} 成功设置为迁移中", self.shard_address);
-        } else {
-            log::warn!("⚠️ 节点 {} 设置迁移状态失败", self.shard_address);
+        if !pending.is_empty() {
+            if (now() as u64).saturating_sub(plan.updated_at) < MIGRATION_WAIT_MS {
+                log::info!("⏳ 纪元 {} 等待节点 {:?} 移交群组", plan.epoch, pending);
+                return Ok(());
+            }
+            log::warn!("⚠️ 纪元 {} 等待节点 {:?} 移交超时，缺失的群组从数据库加载", plan.epoch, pending);
         }
 
-        if let Err(e) = self.change_ready().await {
-            let shard_manager = ShardManager::get();
-            let current = shard_manager.current.load();
-            let mut shard_info = current.shard_info.write().await;
-            shard_info.state = ShardState::Syncing;
-            log::error!("❌ sync groups error: {:?}", e);
+        if let Some(plan) = self.plan.as_mut() {
+            plan.advance(PlanStep::Ready).await?;
         }
-
-        Ok(())
+        self.change_ready().await
     }
 
+    /// 回滚：current 从快照恢复为迁移前归属本节点的群组，并清空本节点的接收记录，
+    /// 下一轮以新的尝试重新迁移（迁移视图保留，期间仍按新旧环转发）；
+    /// 连续失败达到上限时上报 Failed，由仲裁摘除后重新加入。
     async fn change_failed(&mut self) -> anyhow::Result<()> {
         let shard_manager = ShardManager::get();
-        let shard_address = self.shard_address.clone();
-        let client = self.init_arb_client().await?;
-        let state_request = UpdateShardStateRequest {
-            node_addr: shard_address,
-            new_state: ShardState::Failed as i32,
+        if let Some(view) = shard_manager.migration.load_full() {
+            shard_manager.restore_from_snapshot(&view.before, None).await;
+        }
+        Self::set_local_state(ShardState::Preparing).await;
+        let Some(plan) = self.plan.as_mut() else {
+            return Ok(());
         };
-        client.update_shard_state(state_request).await?;
+        plan.reset_received().await?;
+        plan.advance(PlanStep::RolledBack).await?;
+        log::warn!("↩️ 纪元 {} 第 {} 次迁移已回滚", plan.epoch, plan.attempt);
+        if plan.attempt >= MAX_MIGRATION_ATTEMPTS {
+            self.report_state(ShardState::Failed).await?;
+        }
         Ok(())
     }
 
+    /// 提交：补齐未完成移交的源节点迁往本节点的群组，再切换到新环并丢弃快照
     async fn change_ready(&mut self) -> anyhow::Result<()> {
-        let shard_address = self.shard_address.clone();
         let shard_manager = ShardManager::get();
-        let current = shard_manager.current.load();
-        let mut shard_info = current.shard_info.write().await;
-        shard_info.state = ShardState::Ready;
-        let client = self.init_arb_client().await?;
-        let state_request = UpdateShardStateRequest {
-            node_addr: shard_address,
-            new_state: ShardState::Ready as i32,
-        };
-        let shard_manager = ShardManager::get();
-        shard_manager.clean_snapshot();
-        client.update_shard_state(state_request).await?;
-        self.change_normal().await?;
-        Ok(())
+        Self::set_local_state(ShardState::Ready).await;
+        if let Some(view) = shard_manager.migration.load_full() {
+            let received = match self.plan.as_ref() {
+                Some(plan) => plan.handed_over_to(&self.shard_address).await?,
+                None => HashSet::new(),
+            };
+            // 源节点均已移交时不查数据库；已离开环、超时或回滚的源节点的群组才从数据库补齐
+            let missing: HashSet<String> = view
+                .before
+                .diff(&view.after)
+                .into_iter()
+                .filter(|m| m.to.as_deref() == Some(self.shard_address.as_str()))
+                .filter_map(|m| m.from)
+                .filter(|from| !received.contains(from))
+                .collect();
+            if !missing.is_empty() {
+                let loaded = shard_manager.load_moved_groups(&view, &missing).await?;
+                log::warn!("📦 纪元 {} 从数据库补齐节点 {:?} 的群组 {} 个", view.epoch, missing, loaded);
+            }
+            shard_manager.commit_migration(view.after.clone(), view.epoch);
+            // 补齐的群组不写变更日志，且归属已变化，立即落一次快照
+            shard_manager.save_snapshot().await;
+        }
+        if let Some(plan) = self.plan.as_mut() {
+            plan.advance(PlanStep::Normal).await?;
+            log::info!("✅ 纪元 {} 迁移完成", plan.epoch);
+        }
+        self.report_state(ShardState::Ready).await?;
+        self.change_normal().await
     }
 
     async fn change_normal(&mut self) -> anyhow::Result<()> {
@@ -253,13 +269,43 @@ impl ManagerJobOpt for ArbManagerJob {
     }
 
     async fn heartbeat(&mut self) -> anyhow::Result<()> {
-        let shard_address = self.server_host.clone();
+        let shard_address = self.shard_address.clone();
         let client = self.init_arb_client().await?;
         let request = BaseRequest {
             node_addr: shard_address,
             node_type: NodeType::GroupNode as i32,
         };
-        client.heartbeat(request).await?;
+        let response = client.heartbeat(request).await?.into_inner();
+        if !response.success {
+            // 已被仲裁判定失联并下线，回到已注册前状态，由状态自检任务重新注册
+            log::warn!("⚠️ 仲裁未识别本节点（{}），准备重新注册", response.message);
+            let current = ShardManager::get().current.load();
+            current.shard_info.write().await.state = ShardState::Registered;
+        }
+        Ok(())
+    }
+}
+
+impl ArbManagerJob {
+    /// 把 snapshot 中待移交的群组以流式调用推送到各自的新归属节点，已完成的目标节点跳过
+    async fn hand_over(plan: &RebalancePlan, node_addr: &str) -> anyhow::Result<()> {
+        let shard_manager = ShardManager::get();
+        let snapshot = shard_manager.snapshot.load_full();
+        for (owner, groups) in shard_manager.outgoing_groups() {
+            if plan.handed_over_to(&owner).await?.contains(node_addr) {
+                continue;
+            }
+            let mut clients = shard_manager
+                .init_grpc_clients(vec![owner.clone()])
+                .await
+                .map_err(|e| anyhow::anyhow!("init grpc clients error: {}", e))?;
+            let Some(client) = clients.get_mut(&owner) else {
+                continue;
+            };
+            let sent = stream_groups(client, plan, &owner, snapshot.clone(), groups).await?;
+            plan.mark_handed_over(&owner).await?;
+            log::info!("📦 纪元 {} 已移交 {} 个群组到节点 {}", plan.epoch, sent, owner);
+        }
         Ok(())
     }
 }
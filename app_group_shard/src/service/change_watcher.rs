@@ -0,0 +1,335 @@
+use crate::db::shard_store::ShardOp;
+use crate::service::shard_manager::ShardManager;
+use anyhow::{anyhow, Result};
+use biz_core::entitys::group_member_entity::GroupMemberEntity;
+use biz_core::protocol::arb::arb_models::MemberRef;
+use biz_core::service::group_member_service::GroupMemberService;
+use common::redis::redis_pool::RedisPoolTools;
+use deadpool_redis::redis::cmd;
+use futures_util::StreamExt;
+use mongodb::bson::{doc, from_document, Bson, Document};
+use mongodb::change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken};
+use mongodb::change_stream::ChangeStream;
+use mongodb::options::{ChangeStreamOptions, FullDocumentBeforeChangeType, FullDocumentType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const GROUP_MEMBER_COLL: &str = "group_member";
+const GROUP_INFO_COLL: &str = "group_info";
+/// 续传位置：哈希，node_addr → 续传位置（JSON）
+const CHECKPOINT_KEY: &str = "shard:change_stream";
+/// 暂缓的变更重试及续传位置保存间隔
+const TICK_INTERVAL: Duration = Duration::from_millis(500);
+/// 变更流中断后重新打开前的等待
+const REOPEN_BACKOFF: Duration = Duration::from_secs(3);
+
+/// 变更流续传位置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Checkpoint {
+    token: ResumeToken,
+}
+
+/// 群组变更在本节点如何处理
+enum Route {
+    /// 本节点受理，立即应用
+    Apply,
+    /// 群组正在移交（本节点发出中，或新归属本节点但尚未接收），移交结束后再判断
+    Defer,
+    /// 不归属本节点
+    Skip,
+}
+
+/// 跟随 MongoDB 变更流（`group_member` 的增删改、`group_info` 的删除），
+/// 把绕过 `ShardRpcService` 直接写库的变更（业务服务、运维工具）应用到本节点受理的群组。
+///
+/// 变更按完整文档覆盖，重复应用不影响结果：续传位置每隔一段时间保存到 Redis（先刷新变更日志，
+/// 保证位置之前的变更都已落在本地快照或日志中），重启后从该位置继续（其间的变更可能再应用一次），
+/// 快照之后的成员删除据此重放。成员删除依赖 `group_member` 的删除前镜像（由运维脚本
+/// `scripts/mongo/group_member.js` 开启），未开启时不跟随；个别删除拿不到镜像时无法定位群组，
+/// 全量重新加载归属本节点的群组。变更流要求 MongoDB 以副本集部署，打开失败时不跟随。
+pub struct ChangeWatcher {
+    stream: ChangeStream<ChangeStreamEvent<Document>>,
+    node_addr: String,
+    /// 最近处理到的位置
+    checkpoint: Option<Checkpoint>,
+    /// 是否从已保存的续传位置打开
+    resumed: bool,
+    /// 已保存的位置是否落后
+    dirty: bool,
+    /// 暂缓的变更，按群组保持顺序
+    deferred: HashMap<String, Vec<ShardOp>>,
+    /// 出现无法定位群组的删除，需要全量重新加载
+    reload_required: bool,
+}
+
+impl ChangeWatcher {
+    /// 打开变更流：有续传位置时从该位置继续，续传失败（历史已被覆盖等）时从当前开始
+    ///
+    /// 应在加载数据之前打开，加载期间的变更会在开始跟随后应用。
+    pub async fn open(node_addr: &str) -> Result<Self> {
+        if !Self::pre_images_enabled().await? {
+            return Err(anyhow!(
+                "{} 未开启删除前镜像（changeStreamPreAndPostImages），成员删除无法跟随，请先执行 scripts/mongo/group_member.js",
+                GROUP_MEMBER_COLL
+            ));
+        }
+        let checkpoint = Self::load_checkpoint(node_addr).await.unwrap_or_else(|e| {
+            log::warn!("⚠️ 读取变更流续传位置失败: {:?}", e);
+            None
+        });
+        let (stream, resumed) = match Self::watch(checkpoint.as_ref()).await {
+            Ok(stream) => (stream, checkpoint.is_some()),
+            Err(e) if checkpoint.is_some() => {
+                log::warn!("⚠️ 变更流续传失败，从当前开始跟随: {:?}", e);
+                (Self::watch(None).await?, false)
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            stream,
+            node_addr: node_addr.to_string(),
+            checkpoint,
+            resumed,
+            dirty: false,
+            deferred: HashMap::new(),
+            reload_required: false,
+        })
+    }
+
+    /// `group_member` 是否已开启删除前镜像
+    async fn pre_images_enabled() -> Result<bool> {
+        let db = &GroupMemberService::get().db;
+        let reply = db.run_command(doc! { "listCollections": 1, "filter": { "name": GROUP_MEMBER_COLL } }).await?;
+        let enabled = reply
+            .get_document("cursor")
+            .and_then(|cursor| cursor.get_array("firstBatch"))
+            .ok()
+            .and_then(|batch| batch.first())
+            .and_then(Bson::as_document)
+            .and_then(|coll| coll.get_document("options").ok())
+            .and_then(|options| options.get_document("changeStreamPreAndPostImages").ok())
+            .and_then(|images| images.get_bool("enabled").ok())
+            .unwrap_or(false);
+        Ok(enabled)
+    }
+
+    /// 是否从已保存的续传位置继续；否则上次停止之后的成员删除不会重放，加载时不能依赖本地快照
+    pub fn resumed(&self) -> bool {
+        self.resumed
+    }
+
+    async fn watch(checkpoint: Option<&Checkpoint>) -> Result<ChangeStream<ChangeStreamEvent<Document>>> {
+        let pipeline = vec![doc! {
+            "$match": { "$or": [
+                { "ns.coll": GROUP_MEMBER_COLL },
+                { "ns.coll": GROUP_INFO_COLL, "operationType": "delete" },
+            ] }
+        }];
+        let options = ChangeStreamOptions::builder()
+            .full_document(Some(FullDocumentType::UpdateLookup))
+            .full_document_before_change(Some(FullDocumentBeforeChangeType::WhenAvailable))
+            .resume_after(checkpoint.map(|checkpoint| checkpoint.token.clone()))
+            .build();
+        let db = &GroupMemberService::get().db;
+        let stream = db.watch().pipeline(pipeline).with_options(options).await?;
+        Ok(stream)
+    }
+
+    /// 持续跟随变更流
+    pub async fn run(mut self) {
+        log::info!("👀 开始跟随群组变更流");
+        let mut ticker = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            let event = tokio::select! {
+                event = self.stream.next() => Some(event),
+                _ = ticker.tick() => None,
+            };
+            match event {
+                Some(Some(Ok(event))) => self.on_event(event),
+                Some(Some(Err(e))) => {
+                    log::warn!("⚠️ 群组变更流中断: {:?}", e);
+                    self.reopen().await;
+                }
+                Some(None) => self.reopen().await,
+                None => {
+                    if self.reload_required {
+                        self.reload_required = !Self::catch_up().await;
+                    }
+                    self.retry_deferred();
+                    self.save_checkpoint().await;
+                }
+            }
+        }
+    }
+
+    fn on_event(&mut self, event: ChangeStreamEvent<Document>) {
+        let coll = event.ns.as_ref().and_then(|ns| ns.coll.as_deref());
+        let op = match (coll, &event.operation_type) {
+            (Some(GROUP_INFO_COLL), OperationType::Delete) => event.document_key.as_ref().and_then(document_id).map(|group_id| ShardOp::Clear { group_id }),
+            (Some(GROUP_MEMBER_COLL), OperationType::Delete) => match event.full_document_before_change.and_then(member_of) {
+                Some(member) => Some(ShardOp::Remove {
+                    group_id: member.group_id,
+                    uid: member.uid,
+                }),
+                None => {
+                    log::error!("❌ 群成员删除缺少删除前镜像，无法定位群组，将全量重新加载: {:?}", event.document_key);
+                    self.reload_required = true;
+                    None
+                }
+            },
+            (Some(GROUP_MEMBER_COLL), OperationType::Insert | OperationType::Update | OperationType::Replace) => {
+                // 文档已被后续操作删除时没有完整文档，删除事件随后到达
+                event.full_document.and_then(member_of).map(|member| ShardOp::Add {
+                    group_id: member.group_id.clone(),
+                    member: MemberRef::from(member),
+                })
+            }
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.dispatch(op);
+        }
+        if let Some(token) = self.stream.resume_token() {
+            self.checkpoint = Some(Checkpoint { token });
+            self.dirty = true;
+        }
+    }
+
+    /// 应用或暂缓一条变更；群组已有暂缓的变更时继续排在其后
+    fn dispatch(&mut self, op: ShardOp) {
+        if let Some(ops) = self.deferred.get_mut(op.group_id()) {
+            ops.push(op);
+            return;
+        }
+        match route(op.group_id()) {
+            Route::Apply => ShardManager::get().apply_op(op),
+            Route::Defer => self.deferred.entry(op.group_id().to_string()).or_default().push(op),
+            Route::Skip => {}
+        }
+    }
+
+    /// 移交结束的群组：归属本节点的按顺序应用暂缓的变更，否则丢弃（由新归属节点应用）
+    fn retry_deferred(&mut self) {
+        self.deferred.retain(|group_id, ops| match route(group_id) {
+            Route::Apply => {
+                let shard_manager = ShardManager::get();
+                for op in ops.drain(..) {
+                    shard_manager.apply_op(op);
+                }
+                false
+            }
+            Route::Defer => true,
+            Route::Skip => false,
+        });
+    }
+
+    /// 保存续传位置；有暂缓的变更或待完成的全量重新加载时不推进，重启后从此前的位置重新跟随
+    async fn save_checkpoint(&mut self) {
+        if !self.dirty || !self.deferred.is_empty() || self.reload_required {
+            return;
+        }
+        let Some(checkpoint) = &self.checkpoint else {
+            return;
+        };
+        if let Some(store) = &ShardManager::get().store {
+            store.flush();
+        }
+        match Self::store_checkpoint(&self.node_addr, checkpoint).await {
+            Ok(()) => self.dirty = false,
+            Err(e) => log::warn!("⚠️ 保存变更流续传位置失败: {:?}", e),
+        }
+    }
+
+    /// 变更流中断：从最近的位置重新打开；续传失败时从当前开始并全量重新加载
+    async fn reopen(&mut self) {
+        loop {
+            tokio::time::sleep(REOPEN_BACKOFF).await;
+            match Self::watch(self.checkpoint.as_ref()).await {
+                Ok(stream) => {
+                    self.stream = stream;
+                    return;
+                }
+                Err(e) if self.checkpoint.is_some() => {
+                    log::warn!("⚠️ 变更流续传失败，从当前开始跟随并全量重新加载: {:?}", e);
+                    let Ok(stream) = Self::watch(None).await else {
+                        continue;
+                    };
+                    self.stream = stream;
+                    self.checkpoint = None;
+                    self.reload_required = !Self::catch_up().await;
+                    return;
+                }
+                Err(e) => log::warn!("⚠️ 重新打开群组变更流失败: {:?}", e),
+            }
+        }
+    }
+
+    /// 续传失败或删除无法定位群组时，其间的删除无从得知，全量重新加载归属本节点的群组，返回是否完成；
+    /// 迁移期间数据分散在新旧快照中，推迟到迁移结束后的下一次检查
+    async fn catch_up() -> bool {
+        let shard_manager = ShardManager::get();
+        if shard_manager.migration.load().is_some() {
+            log::warn!("⚠️ 迁移期间无法全量重新加载，迁移结束后重试");
+            return false;
+        }
+        let ring = shard_manager.ring.load_full();
+        match shard_manager.reload_all_owned(&ring).await {
+            Ok(reloaded) => {
+                log::info!("🔁 全量重新加载 {} 个群组", reloaded);
+                true
+            }
+            Err(e) => {
+                log::warn!("⚠️ 全量重新加载出错: {:?}", e);
+                false
+            }
+        }
+    }
+
+    async fn load_checkpoint(node_addr: &str) -> Result<Option<Checkpoint>> {
+        let mut conn = RedisPoolTools::get().get().await?;
+        let value: Option<String> = cmd("HGET").arg(CHECKPOINT_KEY).arg(node_addr).query_async(&mut conn).await?;
+        Ok(value.map(|v| serde_json::from_str(&v)).transpose()?)
+    }
+
+    async fn store_checkpoint(node_addr: &str, checkpoint: &Checkpoint) -> Result<()> {
+        let value = serde_json::to_string(checkpoint)?;
+        let mut conn = RedisPoolTools::get().get().await?;
+        let _: () = cmd("HSET").arg(CHECKPOINT_KEY).arg(node_addr).arg(value).query_async(&mut conn).await?;
+        Ok(())
+    }
+}
+
+fn route(group_id: &str) -> Route {
+    let shard_manager = ShardManager::get();
+    if shard_manager.owner_of(group_id).as_deref() != Some(shard_manager.get_node_addr()) {
+        return Route::Skip;
+    }
+    if shard_manager.is_handing_over(group_id) || shard_manager.forward_addr(group_id).is_some() {
+        return Route::Defer;
+    }
+    Route::Apply
+}
+
+/// 文档 `_id`（ObjectId 取十六进制）
+fn document_id(doc: &Document) -> Option<String> {
+    match doc.get("_id")? {
+        Bson::ObjectId(oid) => Some(oid.to_hex()),
+        Bson::String(id) => Some(id.clone()),
+        _ => None,
+    }
+}
+
+/// 变更流中的群成员文档
+fn member_of(mut doc: Document) -> Option<GroupMemberEntity> {
+    let id = document_id(&doc).unwrap_or_default();
+    doc.remove("_id");
+    doc.insert("id", id);
+    match from_document::<GroupMemberEntity>(doc) {
+        Ok(member) => Some(member),
+        Err(e) => {
+            log::warn!("⚠️ 无法解析群成员变更: {:?}", e);
+            None
+        }
+    }
+}
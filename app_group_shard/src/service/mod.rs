@@ -1,12 +1,19 @@
 use crate::service::arb_manager::{ArbManagerJob, ManagerJobOpt};
+use biz_core::service::rpc_server_client_service::ArbServerRpcServiceClientService;
+use biz_core::service::shard_client_service::ShardClientService;
 
 pub mod arb_manager;
 mod arb_manager_impl;
+pub mod change_watcher;
+pub mod rebalance_plan;
 pub mod rpc;
 pub mod shard_manager;
 pub mod shard_manager_impl;
 pub mod shard_manager_opt;
+pub mod shard_sync;
 pub async fn init_service() -> anyhow::Result<()> {
+    ArbServerRpcServiceClientService::init().await?;
+    ShardClientService::init();
     shard_manager::ShardManager::init().await;
     ArbManagerJob::init().await?;
     Ok(())
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use common::redis::redis_pool::RedisPoolTools;
+use common::util::date_util::now;
+use common::util::hash_ring::HashRing;
+use deadpool_redis::redis::cmd;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// 计划记录保留时长（秒），只用于中断后续跑与排查
+const PLAN_TTL_SECS: u64 = 24 * 3600;
+
+/// 迁移步骤，与上报仲裁的 `ShardState` 一一对应
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlanStep {
+    /// 已确定纪元与新旧成员，尚未切分快照
+    Preparing,
+    /// 快照已切分，current 只保留仍归属本节点的群组
+    Migrating,
+    /// 正在向新归属节点移交群组，并等待其他节点移交到本节点
+    Syncing,
+    /// 移交完成，等待提交
+    Ready,
+    /// 已提交，本纪元迁移结束
+    Normal,
+    /// 移交失败已回滚，等待下一次尝试
+    RolledBack,
+}
+
+/// 某一再均衡纪元内本节点的迁移计划（持久化在 Redis），纪元由仲裁在分片成员变化时分配
+///
+/// - `shard:rebalance:{epoch}`：哈希，node_addr → 计划（JSON）；
+/// - `shard:rebalance:{epoch}:recv:{node_addr}`：集合，已完成向该节点移交的源节点；
+/// - `shard:rebalance:{epoch}:cursor:{node_addr}`：哈希，源节点 → 向该节点移交的续传位置。
+///
+/// 每一步都可重入：移交按目标节点粒度记录完成情况，重试时跳过已完成的目标；
+/// 目标节点回滚时清空自己的接收记录，源节点下次尝试会重新移交。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebalancePlan {
+    pub epoch: u64,
+    pub node_addr: String,
+    /// 第几次尝试（回滚后加一）
+    pub attempt: u32,
+    pub step: PlanStep,
+    /// 迁移前的分片节点
+    pub before: Vec<String>,
+    /// 迁移后的分片节点
+    pub after: Vec<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl RebalancePlan {
+    pub fn new(epoch: u64, node_addr: &str, before: &HashRing, after: &HashRing) -> Self {
+        let time = now() as u64;
+        Self {
+            epoch,
+            node_addr: node_addr.to_string(),
+            attempt: 1,
+            step: PlanStep::Preparing,
+            before: before.nodes().cloned().collect(),
+            after: after.nodes().cloned().collect(),
+            created_at: time,
+            updated_at: time,
+        }
+    }
+
+    fn plan_key(epoch: u64) -> String {
+        format!("shard:rebalance:{}", epoch)
+    }
+
+    fn received_key(epoch: u64, node_addr: &str) -> String {
+        format!("shard:rebalance:{}:recv:{}", epoch, node_addr)
+    }
+
+    fn cursor_key(epoch: u64, node_addr: &str) -> String {
+        format!("shard:rebalance:{}:cursor:{}", epoch, node_addr)
+    }
+
+    pub async fn load(epoch: u64, node_addr: &str) -> Result<Option<Self>> {
+        let mut conn = RedisPoolTools::get().get().await?;
+        let value: Option<String> = cmd("HGET").arg(Self::plan_key(epoch)).arg(node_addr).query_async(&mut conn).await?;
+        Ok(value.map(|v| serde_json::from_str(&v)).transpose()?)
+    }
+
+    /// 推进到 `step` 并持久化
+    pub async fn advance(&mut self, step: PlanStep) -> Result<()> {
+        self.step = step;
+        self.updated_at = now() as u64;
+        let value = serde_json::to_string(self)?;
+        let key = Self::plan_key(self.epoch);
+        let mut conn = RedisPoolTools::get().get().await?;
+        let _: () = deadpool_redis::redis::pipe()
+            .cmd("HSET")
+            .arg(&key)
+            .arg(&self.node_addr)
+            .arg(value)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(PLAN_TTL_SECS)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// 记录本节点已完成向 `target` 的移交
+    pub async fn mark_handed_over(&self, target: &str) -> Result<()> {
+        let key = Self::received_key(self.epoch, target);
+        let mut conn = RedisPoolTools::get().get().await?;
+        let _: () = deadpool_redis::redis::pipe()
+            .cmd("SADD")
+            .arg(&key)
+            .arg(&self.node_addr)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(PLAN_TTL_SECS)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// 本纪元内已完成向 `node_addr` 移交的源节点
+    pub async fn handed_over_to(&self, node_addr: &str) -> Result<HashSet<String>> {
+        let mut conn = RedisPoolTools::get().get().await?;
+        Ok(cmd("SMEMBERS").arg(Self::received_key(self.epoch, node_addr)).query_async(&mut conn).await?)
+    }
+
+    /// 向 `target` 移交的续传位置（已确认写入的最后一个群组）
+    pub async fn resume_token(&self, target: &str) -> Result<Option<String>> {
+        let mut conn = RedisPoolTools::get().get().await?;
+        Ok(cmd("HGET").arg(Self::cursor_key(self.epoch, target)).arg(&self.node_addr).query_async(&mut conn).await?)
+    }
+
+    pub async fn save_resume_token(&self, target: &str, group_id: &str) -> Result<()> {
+        let key = Self::cursor_key(self.epoch, target);
+        let mut conn = RedisPoolTools::get().get().await?;
+        let _: () = deadpool_redis::redis::pipe()
+            .cmd("HSET")
+            .arg(&key)
+            .arg(&self.node_addr)
+            .arg(group_id)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(PLAN_TTL_SECS)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// 回滚时清空本节点的接收记录与续传位置，源节点下次尝试会从头重新移交
+    pub async fn reset_received(&self) -> Result<()> {
+        let mut conn = RedisPoolTools::get().get().await?;
+        let _: i64 = cmd("DEL")
+            .arg(Self::received_key(self.epoch, &self.node_addr))
+            .arg(Self::cursor_key(self.epoch, &self.node_addr))
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
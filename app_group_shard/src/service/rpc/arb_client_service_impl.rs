@@ -1,15 +1,15 @@
+use crate::db::shard_store::ShardOp;
+use crate::service::arb_manager::ArbManagerJob;
 use crate::service::shard_manager::ShardManager;
+use crate::service::shard_sync::GroupChecksum;
 use common::config::AppConfig;
-use common::util::common_utils::hash_index;
-use common::util::date_util::now;
 use log::info;
 use std::net::SocketAddr;
 use std::str::FromStr;
-use tonic::{Request, Response, Status};
-use biz_core::kafka_util::node_util::NodeUtil;
+use tonic::{Request, Response, Status, Streaming};
 use biz_core::protocol::arb::arb_client::arb_client_service_server::ArbClientService;
-use biz_core::protocol::arb::arb_client::UpdateVersionReq;
-use biz_core::protocol::arb::arb_models::SyncListGroup;
+use biz_core::protocol::arb::arb_client::{SyncGroupChunk, SyncStreamResp, UpdateVersionReq};
+use biz_core::protocol::arb::arb_models::{ShardState, SyncListGroup};
 use biz_core::protocol::common::CommonResp;
 
 /// arb 组 客户端接口
@@ -27,16 +27,19 @@ impl ArbClientService for ArbClientServiceImpl {
     ) -> Result<Response<CommonResp>, Status> {
         let req = request.into_inner();
         let shard_manager = ShardManager::get();
-
-        {
-            // 加写锁并更新 version 字段
-            let guard = shard_manager.current.load();
-            let mut current = guard.shard_info.write().await;
-            let time = now() as u64;
-            let i = hash_index(req.node_addr.as_str(), req.total);
-            current.last_update_time = time;
-            info!("🔄 版本更新成功: 新版本={} 更新时间={}", req.version, time);
+        // CAS：只接受比本地已知更大的纪元，重复或乱序到达的推送忽略
+        if !shard_manager.observe_epoch(req.version) {
+            return Ok(Response::new(CommonResp {
+                success: false,
+                message: format!("stale shard epoch {}", req.version),
+            }));
         }
+        // 分片归属变化：进入新一轮再均衡，立即唤醒状态自检任务按迁移计划执行
+        if ArbManagerJob::local_state().await != ShardState::Registered {
+            ArbManagerJob::set_local_state(ShardState::Preparing).await;
+            ArbManagerJob::wake();
+        }
+        info!("🔄 分片纪元更新为 {}（虚拟节点数 {}）", req.version, req.virtual_nodes);
 
         Ok(Response::new(CommonResp {
             success: true,
@@ -53,7 +56,16 @@ impl ArbClientService for ArbClientServiceImpl {
 
         let guard = shard_manager.current.load();
         let member_len = sync_data.members.len();
-        guard.shard_map.insert_many(&sync_data.group_id, sync_data.members);
+        // 整组覆盖，源节点重试移交时可重复执行
+        guard.shard_map.clear(&sync_data.group_id);
+        guard
+            .shard_map
+            .insert_many(&sync_data.group_id, sync_data.members.clone())
+            .map_err(|e| Status::internal(format!("sync group {} error: {:?}", sync_data.group_id, e)))?;
+        shard_manager.record(ShardOp::Replace {
+            group_id: sync_data.group_id.clone(),
+            members: sync_data.members,
+        });
         sync_data.on_line_ids.iter().for_each(|user_id| {
             guard.shard_map.set_online(&sync_data.group_id, user_id, true);
         });
@@ -63,8 +75,97 @@ impl ArbClientService for ArbClientServiceImpl {
         }))
     }
 
+    async fn sync_data_stream(&self, request: Request<Streaming<SyncGroupChunk>>) -> Result<Response<SyncStreamResp>, Status> {
+        let shard_manager = ShardManager::get();
+        let mut stream = request.into_inner();
+        let mut resp = SyncStreamResp::default();
+        // 正在接收的群组：(group_id, 已写入成员数, 校验和)；成员按块直接写入，不缓存整组
+        let mut receiving: Option<(String, usize, GroupChecksum)> = None;
+
+        loop {
+            let chunk = match stream.message().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(status) => {
+                    if let Some((incomplete, _, _)) = receiving.take() {
+                        discard_incoming(&incomplete);
+                    }
+                    return Err(status);
+                }
+            };
+            if let Some(view) = shard_manager.migration.load_full() {
+                if chunk.epoch < view.epoch {
+                    if let Some((incomplete, _, _)) = receiving.take() {
+                        discard_incoming(&incomplete);
+                    }
+                    return Err(Status::failed_precondition(format!("stale rebalance epoch {} < {}", chunk.epoch, view.epoch)));
+                }
+            }
+            let guard = shard_manager.current.load();
+            let (group_id, mut received, mut checksum) = match receiving.take() {
+                Some(group) if group.0 == chunk.group_id => group,
+                previous => {
+                    if let Some((incomplete, _, _)) = previous {
+                        // 上一个群组未收到最后一块
+                        discard_incoming(&incomplete);
+                        resp.failed_group_ids.push(incomplete);
+                    }
+                    // 整组覆盖，源节点重传时可重复执行；接收完成前请求仍转发到源节点
+                    shard_manager.receiving.insert(chunk.group_id.clone());
+                    guard.shard_map.clear(&chunk.group_id);
+                    shard_manager.record(ShardOp::Clear {
+                        group_id: chunk.group_id.clone(),
+                    });
+                    (chunk.group_id.clone(), 0, GroupChecksum::default())
+                }
+            };
+            received += chunk.members.len();
+            chunk.members.iter().for_each(|member| checksum.add_member(member));
+            if let Err(e) = guard.shard_map.insert_many(&group_id, chunk.members.clone()) {
+                discard_incoming(&group_id);
+                return Err(Status::internal(format!("sync group {} error: {:?}", group_id, e)));
+            }
+            for member in chunk.members {
+                shard_manager.record(ShardOp::Add {
+                    group_id: group_id.clone(),
+                    member,
+                });
+            }
+            for user_id in &chunk.on_line_ids {
+                checksum.add_online(user_id);
+                let _ = guard.shard_map.set_online(&group_id, user_id, true);
+            }
+            if !chunk.last {
+                receiving = Some((group_id, received, checksum));
+                continue;
+            }
+
+            if received != chunk.member_total as usize || checksum.value() != chunk.checksum {
+                log::warn!("⚠️ 群组 {} 校验失败（来自 {}，{} / {} 成员）", group_id, chunk.source_addr, received, chunk.member_total);
+                discard_incoming(&group_id);
+                resp.failed_group_ids.push(group_id);
+                continue;
+            }
+            shard_manager.receiving.remove(&group_id);
+            resp.applied += 1;
+            // 续传位置只推进到第一个失败的群组之前
+            if resp.failed_group_ids.is_empty() {
+                resp.resume_token = group_id;
+            }
+        }
+        if let Some((incomplete, _, _)) = receiving {
+            discard_incoming(&incomplete);
+            resp.failed_group_ids.push(incomplete);
+        }
+        Ok(Response::new(resp))
+    }
+
     async fn flush_nodes(&self, request: Request<()>) -> Result<Response<CommonResp>, Status> {
-        let node_util = NodeUtil::get();
+        // 分片节点变更（加入 / 失联）：进入新一轮再均衡，由状态自检任务按迁移计划执行
+        if ArbManagerJob::local_state().await != ShardState::Registered {
+            ArbManagerJob::set_local_state(ShardState::Preparing).await;
+            ArbManagerJob::wake();
+        }
 
         Ok(Response::new(CommonResp {
             success: true,
@@ -72,3 +173,13 @@ impl ArbClientService for ArbClientServiceImpl {
         }))
     }
 }
+
+/// 丢弃接收不完整或校验失败的群组，由源节点重传
+fn discard_incoming(group_id: &str) {
+    let shard_manager = ShardManager::get();
+    shard_manager.current.load().shard_map.clear(group_id);
+    shard_manager.record(ShardOp::Clear {
+        group_id: group_id.to_string(),
+    });
+    shard_manager.receiving.remove(group_id);
+}
@@ -1,7 +1,6 @@
 use biz_core::kafka_util::node_util::NodeUtil;
 
 pub mod arb_client_service_impl;
-mod arb_server_client;
 pub mod shard_rpc_service_impl;
 
 pub async fn init_service() {
@@ -1,36 +1,119 @@
+use crate::db::member::member_index::MemberCursor;
+use crate::service::arb_manager::ArbManagerJob;
 use crate::service::shard_manager::{ShardManager, ShardManagerOpt};
+use biz_core::consts::shard_const::{SHARD_EPOCH_AHEAD, SHARD_EPOCH_HEADER, SHARD_EPOCH_MISSING, SHARD_GROUP_MIGRATING, SHARD_MOVED, SHARD_OWNER_HEADER};
+use biz_core::protocol::arb::arb_models::ShardState;
+use biz_core::service::shard_client_service::{ShardClientService, ShardRpcClient};
 use std::sync::Arc;
-use tonic::{Request, Response, Status};
-use biz_core::protocol::arb::shard_service::{AddMemberReq, ChangeRoleReq, GetGroupsResp, GetMemberCountReq, GetMemberPageReq, MemberCountResp, MemberListResp, OnlineReq, RemoveMemberReq, UserIdListResp};
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::{Code, Request, Response, Status};
+use biz_core::protocol::arb::shard_service::{AddMemberReq, ChangeRoleReq, CheckCanSpeakReq, CheckCanSpeakResp, GetGroupsResp, GetMemberCountReq, GetMembersByRoleReq, GetMemberPageReq, MemberCountResp, MemberListResp, MuteMemberReq, OnlineReq, RemoveMemberReq, SetAliasReq, UserIdListResp};
 use biz_core::protocol::arb::shard_service::shard_rpc_service_server::ShardRpcService;
 use biz_core::protocol::common::IdReq;
 
+/// 转发出去的请求带此标记，接收方一律本地处理，避免两端环视图不一致时来回转发
+const FORWARDED_HEADER: &str = "x-shard-forwarded";
+
 pub struct ShardRpcServiceImpl {
     pub shard_manager: Arc<ShardManager>,
+    /// 迁移期间转发用的客户端（连接按节点地址复用）
+    shard_client: Arc<ShardClientService>,
 }
 
 impl ShardRpcServiceImpl {
     pub fn new() -> Self {
         Self {
             shard_manager: ShardManager::get(),
+            shard_client: ShardClientService::get(),
+        }
+    }
+
+    /// 分片纪元与归属校验（fencing）：群组不归属本节点时重定向到归属节点；
+    /// 本节点纪元落后于调用方、或写入正在移交的群组时，要求调用方稍后重试；
+    /// 未携带纪元的写请求直接拒绝（读请求按纪元 0 处理）
+    fn fence<T>(&self, request: &Request<T>, group_id: &str, write: bool) -> Result<(), Status> {
+        let epoch = self.shard_manager.serving_epoch();
+        let caller_epoch = match request.metadata().get(SHARD_EPOCH_HEADER).and_then(|value| value.to_str().ok()).and_then(|value| value.parse::<u64>().ok()) {
+            Some(caller_epoch) => caller_epoch,
+            None if write => return Err(Status::invalid_argument(SHARD_EPOCH_MISSING)),
+            None => 0,
+        };
+        if caller_epoch > epoch {
+            tokio::spawn(catch_up());
+            return Err(fenced(Code::Unavailable, SHARD_EPOCH_AHEAD, epoch, None));
+        }
+        match self.shard_manager.owner_of(group_id) {
+            Some(owner) if owner != self.shard_manager.get_node_addr() => {
+                if request.metadata().contains_key(FORWARDED_HEADER) {
+                    // 转发来的请求不再重定向，避免两端之间来回
+                    return Err(fenced(Code::Unavailable, SHARD_GROUP_MIGRATING, epoch, None));
+                }
+                Err(fenced(Code::FailedPrecondition, SHARD_MOVED, epoch, Some(&owner)))
+            }
+            _ if write && self.shard_manager.is_handing_over(group_id) => Err(fenced(Code::Unavailable, SHARD_GROUP_MIGRATING, epoch, None)),
+            _ => Ok(()),
+        }
+    }
+
+    /// 迁移期间群组尚未从原归属节点移交过来时，返回原归属节点的客户端
+    fn forward_client<T>(&self, request: &Request<T>, group_id: &str) -> Result<Option<ShardRpcClient>, Status> {
+        if request.metadata().contains_key(FORWARDED_HEADER) {
+            return Ok(None);
         }
+        let Some(owner) = self.shard_manager.forward_addr(group_id) else {
+            return Ok(None);
+        };
+        self.shard_client.client(&owner).map(Some)
+    }
+}
+
+fn forwarded<T>(message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request.metadata_mut().insert(FORWARDED_HEADER, MetadataValue::from_static("1"));
+    request
+}
+
+/// 拒绝请求，元数据携带本节点纪元及（重定向时）归属节点
+fn fenced(code: Code, message: &str, epoch: u64, owner: Option<&str>) -> Status {
+    let mut metadata = MetadataMap::new();
+    metadata.insert(SHARD_EPOCH_HEADER, MetadataValue::from(epoch));
+    if let Some(value) = owner.and_then(|owner| MetadataValue::try_from(owner).ok()) {
+        metadata.insert(SHARD_OWNER_HEADER, value);
+    }
+    Status::with_metadata(code, message, metadata)
+}
+
+/// 调用方纪元领先：本节点正常服务时进入再均衡，立即唤醒状态自检任务拉取最新的环
+async fn catch_up() {
+    if ArbManagerJob::local_state().await == ShardState::Normal {
+        ArbManagerJob::set_local_state(ShardState::Preparing).await;
+        ArbManagerJob::wake();
     }
 }
 #[tonic::async_trait]
 impl ShardRpcService for ShardRpcServiceImpl {
     async fn create(&self, request: Request<IdReq>) -> Result<Response<()>, Status> {
+        self.fence(&request, &request.get_ref().ref_id, true)?;
         let req = request.into_inner();
         self.shard_manager.create(&req.ref_id).await.unwrap();
         Ok(Response::new(()))
     }
 
     async fn dismiss(&self, request: Request<IdReq>) -> Result<Response<()>, Status> {
+        self.fence(&request, &request.get_ref().ref_id, true)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().ref_id)? {
+            return client.dismiss(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
         self.shard_manager.dismiss(&req.ref_id);
         Ok(Response::new(()))
     }
 
     async fn add_member(&self, request: Request<AddMemberReq>) -> Result<Response<()>, Status> {
+        self.fence(&request, &request.get_ref().group_id, true)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.add_member(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
         self.shard_manager.add_member(&req.group_id, &req.user_id, req.role()).unwrap();
         Ok(Response::new(()))
@@ -40,6 +123,10 @@ impl ShardRpcService for ShardRpcServiceImpl {
         &self,
         request: Request<RemoveMemberReq>,
     ) -> Result<Response<()>, Status> {
+        self.fence(&request, &request.get_ref().group_id, true)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.remove_member(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
         self.shard_manager.remove_member(&req.group_id, &req.user_id).unwrap();
         Ok(Response::new(()))
@@ -49,10 +136,15 @@ impl ShardRpcService for ShardRpcServiceImpl {
         &self,
         request: Request<IdReq>,
     ) -> Result<Response<MemberListResp>, Status> {
+        self.fence(&request, &request.get_ref().ref_id, false)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().ref_id)? {
+            return client.get_member(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
         let member_list = self.shard_manager.get_member(&req.ref_id).unwrap();
         Ok(Response::new(MemberListResp {
             members: member_list.into_iter().map(|m| m.into()).collect(),
+            ..Default::default()
         }))
     }
 
@@ -60,13 +152,27 @@ impl ShardRpcService for ShardRpcServiceImpl {
         &self,
         request: Request<GetMemberPageReq>,
     ) -> Result<Response<MemberListResp>, Status> {
+        self.fence(&request, &request.get_ref().group_id, false)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.get_member_page(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
-        let member_list = self
+        let order = req.order();
+        let cursor = match req.cursor.as_str() {
+            "" => None,
+            cursor => match MemberCursor::decode(cursor) {
+                Some(cursor) if cursor.order() == order => Some(cursor),
+                _ => return Err(Status::invalid_argument("group.member.cursor.invalid")),
+            },
+        };
+        let page = self
             .shard_manager
-            .get_member_page(&req.group_id, req.offset as usize, req.limit as usize)
-            .unwrap();
+            .get_member_page(&req.group_id, order, cursor.as_ref(), req.offset as usize, req.limit as usize)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .unwrap_or_default();
         Ok(Response::new(MemberListResp {
-            members: member_list.unwrap(),
+            members: page.members,
+            next_cursor: page.next_cursor.map(|cursor| cursor.encode()).unwrap_or_default(),
         }))
     }
 
@@ -74,6 +180,10 @@ impl ShardRpcService for ShardRpcServiceImpl {
         &self,
         request: Request<GetMemberCountReq>,
     ) -> Result<Response<MemberCountResp>, Status> {
+        self.fence(&request, &request.get_ref().group_id, false)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.get_member_count(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
         let member_count = self.shard_manager.get_member_count(&req.group_id).unwrap();
         Ok(Response::new(MemberCountResp {
@@ -82,6 +192,10 @@ impl ShardRpcService for ShardRpcServiceImpl {
     }
 
     async fn online(&self, request: Request<OnlineReq>) -> Result<Response<()>, Status> {
+        self.fence(&request, &request.get_ref().group_id, true)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.online(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
         self.shard_manager.online(&req.user_id, &req.group_id).unwrap();
         Ok(Response::new(()))
@@ -91,6 +205,10 @@ impl ShardRpcService for ShardRpcServiceImpl {
         &self,
         request: Request<IdReq>,
     ) -> Result<Response<UserIdListResp>, Status> {
+        self.fence(&request, &request.get_ref().ref_id, false)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().ref_id)? {
+            return client.get_online_member(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
         Ok(Response::new(UserIdListResp {
             user_ids: self.shard_manager.get_on_line_member(&req.ref_id),
@@ -98,20 +216,85 @@ impl ShardRpcService for ShardRpcServiceImpl {
     }
 
     async fn change_role(&self, request: Request<ChangeRoleReq>) -> Result<Response<()>, Status> {
+        self.fence(&request, &request.get_ref().group_id, true)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.change_role(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
         self.shard_manager.change_role(&req.group_id, &req.user_id, req.role()).unwrap();
         Ok(Response::new(()))
     }
 
+    async fn mute_member(&self, request: Request<MuteMemberReq>) -> Result<Response<()>, Status> {
+        self.fence(&request, &request.get_ref().group_id, true)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.mute_member(forwarded(request.into_inner())).await;
+        }
+        let req = request.into_inner();
+        let updated = self
+            .shard_manager
+            .mute_member(&req.group_id, &req.user_id, req.mute_until)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        if !updated {
+            return Err(Status::not_found("group.member.notfound"));
+        }
+        Ok(Response::new(()))
+    }
+
+    async fn set_alias(&self, request: Request<SetAliasReq>) -> Result<Response<()>, Status> {
+        self.fence(&request, &request.get_ref().group_id, true)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.set_alias(forwarded(request.into_inner())).await;
+        }
+        let req = request.into_inner();
+        let updated = self
+            .shard_manager
+            .set_alias(&req.group_id, &req.user_id, &req.alias)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        if !updated {
+            return Err(Status::not_found("group.member.notfound"));
+        }
+        Ok(Response::new(()))
+    }
+
+    async fn check_can_speak(
+        &self,
+        request: Request<CheckCanSpeakReq>,
+    ) -> Result<Response<CheckCanSpeakResp>, Status> {
+        self.fence(&request, &request.get_ref().group_id, false)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.check_can_speak(forwarded(request.into_inner())).await;
+        }
+        let req = request.into_inner();
+        Ok(Response::new(self.shard_manager.check_can_speak(&req.group_id, &req.user_id)))
+    }
+
     async fn get_admin_member(
         &self,
         request: Request<IdReq>,
     ) -> Result<Response<UserIdListResp>, Status> {
+        self.fence(&request, &request.get_ref().ref_id, false)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().ref_id)? {
+            return client.get_admin_member(forwarded(request.into_inner())).await;
+        }
         let req = request.into_inner();
-        let user_ids = self.shard_manager.get_admin_member(&req.ref_id).await;
-        let option = user_ids.expect("get_admin_member.error");
         Ok(Response::new(UserIdListResp {
-            user_ids: option.unwrap(),
+            user_ids: self.shard_manager.get_admin_member(&req.ref_id),
+        }))
+    }
+
+    async fn get_members_by_role(
+        &self,
+        request: Request<GetMembersByRoleReq>,
+    ) -> Result<Response<MemberListResp>, Status> {
+        self.fence(&request, &request.get_ref().group_id, false)?;
+        if let Some(mut client) = self.forward_client(&request, &request.get_ref().group_id)? {
+            return client.get_members_by_role(forwarded(request.into_inner())).await;
+        }
+        let req = request.into_inner();
+        Ok(Response::new(MemberListResp {
+            members: self.shard_manager.get_members_by_role(&req.group_id, req.role(), req.online_only),
+            ..Default::default()
         }))
     }
 
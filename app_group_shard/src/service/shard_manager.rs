@@ -1,13 +1,20 @@
 use crate::db::hash_shard_map::HashShardMap;
+use crate::db::member::member_index::{MemberCursor, MemberPage};
+use crate::db::shard_store::ShardStore;
 use anyhow::Result;
-use arc_swap::ArcSwap;
+use arc_swap::{ArcSwap, ArcSwapOption};
 use async_trait::async_trait;
 use biz_core::protocol::common::GroupRoleType;
 use common::config::ShardConfig;
+use common::util::hash_ring::HashRing;
 use common::{GroupId, UserId};
+use dashmap::{DashMap, DashSet};
 use std::hash::Hash;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
 use tokio::sync::RwLock;
 use biz_core::protocol::arb::arb_models::{MemberRef, ShardState};
+use biz_core::protocol::arb::shard_service::{CheckCanSpeakResp, MemberOrder};
 
 pub const GROUP_SHARD_SIZE: usize = 64;
 pub const MEMBER_SHARD_SIZE: usize = 16;
@@ -35,22 +42,61 @@ impl Clone for MemData {
 
 impl MemData {
     pub fn new() -> Self {
+        Self::with_info(ShardInfo::default())
+    }
+
+    /// 替换 current 时沿用原状态
+    pub fn with_info(shard_info: ShardInfo) -> Self {
         Self {
             shard_map: HashShardMap::new(GROUP_SHARD_SIZE, MEMBER_SHARD_SIZE),
-            shard_info: RwLock::new(ShardInfo::default()),
+            shard_info: RwLock::new(shard_info),
         }
     }
 }
+
+/// 进行中的再均衡：迁移期间按新旧两个环判断群组由谁服务
+#[derive(Debug)]
+pub struct MigrationView {
+    pub epoch: u64,
+    pub before: HashRing,
+    pub after: HashRing,
+}
+
+/// 迁移期间待移交群组的移交进度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandOver {
+    /// 正在发送，暂停写入
+    InFlight,
+    /// 新归属节点已写入，本节点不再受理
+    Done,
+}
+
 #[derive(Debug)]
 pub struct ShardManager {
+    /// 迁移期间为迁移前的数据，待移交的群组仍在此处服务；其余时间为空
     pub snapshot: ArcSwap<MemData>,
     pub current: ArcSwap<MemData>,
+    /// 最近一次提交的分片环，current 中的群组按此环归属本节点
+    pub ring: ArcSwap<HashRing>,
+    /// 进行中的迁移，未迁移时为 None
+    pub migration: ArcSwapOption<MigrationView>,
+    /// `ring` 对应的分片纪元
+    pub epoch: AtomicU64,
+    /// 仲裁推送的最新分片纪元（只增不减）
+    pub latest_epoch: AtomicU64,
+    /// 本次迁移中已开始移交的群组
+    pub hand_overs: DashMap<String, HandOver>,
+    /// 正在从原归属节点接收（已写入部分成员）的群组，接收完成前请求仍转发到原归属节点
+    pub receiving: DashSet<String>,
+    /// 本地快照及变更日志，未配置 `data_dir` 时为 None
+    pub store: Option<Arc<ShardStore>>,
     pub shard_config: ShardConfig,
 }
 
 #[async_trait]
 pub trait ShardManagerOpt: Send + Sync {
-    async fn load_from_data(&self) -> anyhow::Result<()>;
+    /// 加载归属本节点的群组；`follow_deletes` 表示变更流已从续传位置打开，快照之后的成员删除会由其重放
+    async fn load_from_data(&self, follow_deletes: bool) -> anyhow::Result<()>;
     ///创建群组
     async fn create(&self, group_id: &str) -> anyhow::Result<()>;
     ///删除群组
@@ -61,13 +107,15 @@ pub trait ShardManagerOpt: Send + Sync {
     fn remove_member(&self, group_id: &GroupId, uid: &UserId) -> anyhow::Result<()>;
     /// 获取某个群组的所有成员 ID 列表
     fn get_member(&self, group_id: &GroupId) -> Result<Vec<MemberRef>>;
-    /// 获取群组成员分页列表
+    /// 获取群组成员分页列表：有游标时从游标之后取，否则从头跳过 `offset` 个成员
     fn get_member_page(
         &self,
         group_id: &GroupId,
+        order: MemberOrder,
+        cursor: Option<&MemberCursor>,
         offset: usize,
         limit: usize,
-    ) -> Result<Option<Vec<MemberRef>>>;
+    ) -> Result<Option<MemberPage>>;
     fn get_member_count(&self, group_id: &GroupId) -> Result<usize>;
     /// 标记用户在线
     fn online(&self, group_id: &GroupId, uid: &UserId) -> Result<()>;
@@ -77,9 +125,18 @@ pub trait ShardManagerOpt: Send + Sync {
     fn get_on_line_member(&self, group_id: &GroupId) -> Vec<UserId>;
     ///修改角色
     fn change_role(&self, group_id: &GroupId, uid: &UserId, role: GroupRoleType) -> Result<()>;
+    /// 设置禁言截止时间（毫秒，0 为解除禁言），成员不存在时返回 false
+    fn mute_member(&self, group_id: &GroupId, uid: &UserId, mute_until: u64) -> Result<bool>;
+    /// 设置群内别名，成员不存在时返回 false
+    fn set_alias(&self, group_id: &GroupId, uid: &UserId, alias: &str) -> Result<bool>;
+    /// 判断成员能否发言（只查内存）
+    fn check_can_speak(&self, group_id: &GroupId, uid: &UserId) -> CheckCanSpeakResp;
     /// 获取用户所在的群组
     fn get_user_groups(&self, uid: &UserId) -> anyhow::Result<Vec<String>>;
 
-    /// 获取在线管理员
-    async fn get_admin_member(&self, group_id: &GroupId) -> Result<Option<Vec<UserId>>>;
+    /// 按角色获取成员（按入群时间），`online_only` 时只取在线成员
+    fn get_members_by_role(&self, group_id: &GroupId, role: GroupRoleType, online_only: bool) -> Vec<MemberRef>;
+
+    /// 获取在线管理员（含群主）
+    fn get_admin_member(&self, group_id: &GroupId) -> Vec<UserId>;
 }
@@ -1,17 +1,27 @@
 use crate::db::hash_shard_map::HashShardMap;
-use crate::service::shard_manager::{MemData, ShardInfo, ShardManager, ShardManagerOpt, GROUP_SHARD_SIZE, MEMBER_SHARD_SIZE};
-use arc_swap::ArcSwap;
+use crate::db::shard_store::{ShardOp, ShardStore};
+use crate::service::change_watcher::ChangeWatcher;
+use crate::service::shard_manager::{HandOver, MemData, MigrationView, ShardInfo, ShardManager, ShardManagerOpt, GROUP_SHARD_SIZE, MEMBER_SHARD_SIZE};
+use arc_swap::{ArcSwap, ArcSwapOption};
 use common::config::AppConfig;
-use common::util::common_utils::hash_index;
+use common::util::hash_ring::{HashRing, DEFAULT_VIRTUAL_NODES};
+use dashmap::{DashMap, DashSet};
 use once_cell::sync::OnceCell;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
 use tonic::transport::Channel;
 use twox_hash::XxHash64;
 use biz_core::protocol::arb::arb_client::arb_client_service_client::ArbClientServiceClient;
 use biz_core::protocol::arb::arb_models::ShardState;
+use biz_core::service::shard_client_service::ShardClientService;
+
+/// 默认本地快照间隔（秒）
+const DEFAULT_SNAPSHOT_INTERVAL_SECS: u64 = 300;
+/// 变更日志刷盘间隔，宕机最多丢失这段时间内的变更（重启对账时由数据库补齐）
+const LOG_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
 
 impl ShardManager {
     pub fn new() -> Self {
@@ -19,10 +29,24 @@ impl ShardManager {
         let shard_info = shard_config.clone();
         let mut info = ShardInfo::default();
         info.state = ShardState::Registered;
+        let store = shard_info.as_ref().and_then(|config| config.data_dir.as_deref()).and_then(|dir| match ShardStore::open(dir) {
+            Ok(store) => Some(Arc::new(store)),
+            Err(e) => {
+                log::error!("❌ 打开本地快照目录 {} 失败，本次不落盘: {:?}", dir, e);
+                None
+            }
+        });
         let manager = Self {
             snapshot: ArcSwap::new(Arc::new(MemData::new())),
             shard_config: shard_info.unwrap(),
-            current: ArcSwap::new(Arc::new(MemData::new())),
+            current: ArcSwap::new(Arc::new(MemData::with_info(info))),
+            ring: ArcSwap::from_pointee(HashRing::new(DEFAULT_VIRTUAL_NODES)),
+            migration: ArcSwapOption::empty(),
+            epoch: AtomicU64::new(0),
+            latest_epoch: AtomicU64::new(0),
+            hand_overs: DashMap::new(),
+            receiving: DashSet::new(),
+            store,
         };
         return manager;
     }
@@ -48,42 +72,266 @@ impl ShardManager {
     pub async fn init_grpc_clients(
         &self,
         endpoints: Vec<String>,
-    ) -> std::result::Result<HashMap<i32, ArbClientServiceClient<Channel>>, Box<dyn std::error::Error>> {
+    ) -> std::result::Result<HashMap<String, ArbClientServiceClient<Channel>>, Box<dyn std::error::Error>> {
         let mut clients = HashMap::new();
-        let size = endpoints.len();
         for endpoint in endpoints {
             //跳过自动节点
             if endpoint == self.shard_config.client_addr.clone().unwrap() {
                 continue;
             }
-            let channel = Channel::from_shared(format!("http://{}", endpoint))?.connect().await?;
-            let client = ArbClientServiceClient::new(channel);
-            clients.insert(hash_index(&endpoint, size as i32), client);
+            let client = ArbClientServiceClient::new(ShardClientService::get().channel(&endpoint)?);
+            clients.insert(endpoint, client);
         }
         Ok(clients)
     }
-    pub fn clear_current(&self) {
-        self.current.store(Arc::new(MemData {
-            shard_map: HashShardMap::new(GROUP_SHARD_SIZE, MEMBER_SHARD_SIZE),
-            shard_info: RwLock::new(ShardInfo::default()),
-        }));
+    pub fn clean_snapshot(&self) {
+        self.snapshot.store(Arc::new(MemData::new()));
+    }
+
+    /// 迁移期间群组所在的数据：已在 current 中的（保留及已接收的）取 current，
+    /// 待移交的仍在 snapshot 中服务
+    pub fn shard_data(&self, group_id: &str) -> Arc<MemData> {
+        let current = self.current.load_full();
+        if self.migration.load().is_none() || current.shard_map.contains_key(group_id) {
+            return current;
+        }
+        let snapshot = self.snapshot.load_full();
+        if snapshot.shard_map.contains_key(group_id) { snapshot } else { current }
+    }
+
+    /// 本节点当前按哪个纪元受理请求：迁移期间为迁移的纪元，否则为已提交环的纪元
+    pub fn serving_epoch(&self) -> u64 {
+        match self.migration.load_full() {
+            Some(view) => view.epoch,
+            None => self.epoch.load(Ordering::Acquire),
+        }
+    }
+
+    /// 记录仲裁推送的纪元（CAS：只接受更大的纪元），返回是否更新
+    pub fn observe_epoch(&self, epoch: u64) -> bool {
+        self.latest_epoch.fetch_max(epoch, Ordering::AcqRel) < epoch
+    }
+
+    /// 本节点视角下当前受理群组请求的节点
+    ///
+    /// 迁移期间，新归属本节点的群组由本节点受理（尚未接收的转发到原归属节点）；
+    /// 待移交的群组在移交完成前仍由本节点受理，完成后由新归属节点受理。
+    /// 迁出区间内本节点没有数据的群组（已移交、迁移期间新建）不会再移交，
+    /// 在本节点写入会在提交时丢失，同样由新归属节点受理。
+    pub fn owner_of(&self, group_id: &str) -> Option<String> {
+        let node_addr = self.get_node_addr();
+        let Some(view) = self.migration.load_full() else {
+            return self.ring.load().locate(group_id).map(str::to_string);
+        };
+        let after = view.after.locate(group_id);
+        if after != Some(node_addr)
+            && view.before.locate(group_id) == Some(node_addr)
+            && self.hand_overs.get(group_id).is_none_or(|state| *state != HandOver::Done)
+            && self.shard_data(group_id).shard_map.contains_key(group_id)
+        {
+            return Some(node_addr.to_string());
+        }
+        after.map(str::to_string)
+    }
+
+    /// 群组是否正在移交（暂停写入）
+    pub fn is_handing_over(&self, group_id: &str) -> bool {
+        self.hand_overs.get(group_id).is_some_and(|state| *state == HandOver::InFlight)
+    }
+
+    /// 开始移交一批群组：发送期间暂停写入，避免发送后的写入在新归属节点丢失
+    pub fn begin_hand_over(&self, groups: &[String]) {
+        for group_id in groups {
+            self.hand_overs.insert(group_id.clone(), HandOver::InFlight);
+        }
+    }
+
+    /// 群组已由新归属节点写入：从 snapshot 移除，此后的请求重定向到新归属节点
+    pub fn finish_hand_over(&self, group_id: &str) {
+        self.snapshot.load().shard_map.clear(group_id);
+        self.hand_overs.insert(group_id.to_string(), HandOver::Done);
+    }
+
+    /// 移交失败：恢复写入，群组仍由本节点受理
+    pub fn abort_hand_over(&self, groups: &[String]) {
+        for group_id in groups {
+            self.hand_overs.remove_if(group_id, |_, state| *state == HandOver::InFlight);
+        }
+    }
+
+    /// 迁移期间新归属本节点、但尚未从原归属节点接收的群组，请求转发到原归属节点
+    pub fn forward_addr(&self, group_id: &str) -> Option<String> {
+        let view = self.migration.load_full()?;
+        let node_addr = self.get_node_addr();
+        if view.after.locate(group_id) != Some(node_addr) {
+            return None;
+        }
+        let owner = view.before.locate(group_id)?;
+        if owner == node_addr || (self.shard_data(group_id).shard_map.contains_key(group_id) && !self.receiving.contains(group_id)) {
+            return None;
+        }
+        Some(owner.to_string())
     }
 
-    pub fn clone_current_to_snapshot(&self) {
+    /// 开始迁移：先把上一次未完成的迁移折回（见 `restore_from_snapshot`，
+    /// 已先一步移交过来的新归属群组保留），再把 current 整体转为 snapshot，
+    /// 新的 current 只复制仍归属本节点的群组。待移交的群组留在 snapshot 中继续服务，直到提交。
+    /// 已移交完成的群组（数据已不在本节点）保持移交完成，直到提交。
+    pub async fn begin_migration(&self, view: MigrationView) {
+        self.restore_from_snapshot(&view.before, Some(&view.after)).await;
+        let node_addr = self.get_node_addr();
+        let current = self.current.load_full();
+        let next = MemData::with_info(current.shard_info.read().await.clone());
+        for key in current.shard_map.all_keys() {
+            if view.after.locate(&key) == Some(node_addr) {
+                copy_group(&current.shard_map, &next.shard_map, &key);
+            }
+        }
+        // 转发到原归属节点的请求携带迁移纪元
+        ShardClientService::get().observe_epoch(view.epoch);
+        self.migration.store(Some(Arc::new(view)));
+        self.snapshot.store(current);
+        self.current.store(Arc::new(next));
+    }
+
+    /// 回滚到快照：current 恢复为迁移前归属本节点（按 `before`）的全部群组——
+    /// 待移交的取自 snapshot，保留的取自 current（迁移期间的写入在 current 上），
+    /// 从其他节点接收的群组丢弃（按 `retain` 归属本节点的除外）。
+    /// 迁移视图保留，下一次尝试前仍按新旧环转发。
+    pub async fn restore_from_snapshot(&self, before: &HashRing, retain: Option<&HashRing>) {
+        let node_addr = self.get_node_addr();
+        let owned = |key: &str| before.locate(key) == Some(node_addr) || retain.is_some_and(|ring| ring.locate(key) == Some(node_addr));
+        let current = self.current.load_full();
+        let snapshot = self.snapshot.load_full();
+        if snapshot.shard_map.all_keys().is_empty() {
+            for key in current.shard_map.all_keys() {
+                if !owned(&key) {
+                    current.shard_map.clear(&key);
+                }
+            }
+            return;
+        }
+        for key in current.shard_map.all_keys() {
+            if owned(&key) {
+                snapshot.shard_map.clear(&key);
+                copy_group(&current.shard_map, &snapshot.shard_map, &key);
+            }
+        }
+        *snapshot.shard_info.write().await = current.shard_info.read().await.clone();
+        self.current.store(snapshot);
+        self.clean_snapshot();
+    }
+
+    /// 提交迁移：current 只保留按 `after` 归属本节点的群组，丢弃快照并切换到新环（纪元 `epoch`）
+    pub fn commit_migration(&self, after: HashRing, epoch: u64) {
+        let node_addr = self.get_node_addr();
         let current = self.current.load();
-        self.snapshot.store(current.clone());
+        for key in current.shard_map.all_keys() {
+            if after.locate(&key) != Some(node_addr) {
+                current.shard_map.clear(&key);
+            }
+        }
+        self.clean_snapshot();
+        self.ring.store(Arc::new(after));
+        self.epoch.store(epoch, Ordering::Release);
+        ShardClientService::get().observe_epoch(epoch);
+        self.migration.store(None);
+        self.hand_overs.clear();
+        self.receiving.clear();
     }
-    pub fn clean_snapshot(&self) {
-        self.snapshot.store(Arc::new(MemData {
-            shard_map: HashShardMap::new(GROUP_SHARD_SIZE, MEMBER_SHARD_SIZE),
-            shard_info: RwLock::new(ShardInfo::default()),
-        }));
+
+    /// 待移交的群组，按新归属节点分组；从本节点接收区间的节点都会列出（群组可能已全部移交完成）
+    pub fn outgoing_groups(&self) -> HashMap<String, Vec<String>> {
+        let mut outgoing: HashMap<String, Vec<String>> = HashMap::new();
+        let Some(view) = self.migration.load_full() else {
+            return outgoing;
+        };
+        let node_addr = self.get_node_addr();
+        for item in view.before.diff(&view.after) {
+            if let Some(to) = item.to.filter(|_| item.from.as_deref() == Some(node_addr)) {
+                outgoing.entry(to).or_default();
+            }
+        }
+        for key in self.snapshot.load().shard_map.all_keys() {
+            match view.after.locate(&key) {
+                Some(owner) if owner != node_addr => outgoing.entry(owner.to_string()).or_default().push(key.to_string()),
+                _ => {}
+            }
+        }
+        outgoing
+    }
+
+    /// 记录一次已作用到内存的变更
+    pub fn record(&self, op: ShardOp) {
+        if let Some(store) = &self.store {
+            store.append(&op);
+        }
+    }
+
+    /// 应用绕过 RPC 的变更（变更流）并记录
+    pub fn apply_op(&self, op: ShardOp) {
+        op.clone().apply(&self.shard_data(op.group_id()).shard_map);
+        self.record(op);
+    }
+
+    /// 把 current 写为本地快照；迁移期间数据分散在 current 与 snapshot 中，跳过（提交后会立即补一次）
+    pub async fn save_snapshot(&self) {
+        let Some(store) = self.store.clone() else {
+            return;
+        };
+        if self.migration.load().is_some() {
+            return;
+        }
+        let data = self.current.load_full();
+        let ring = self.ring.load_full();
+        match tokio::task::spawn_blocking(move || store.save_snapshot(&data.shard_map, &ring)).await {
+            Ok(Ok(groups)) => log::info!("💾 本地快照已写入: {} 个群组", groups),
+            Ok(Err(e)) => log::warn!("⚠️ 写入本地快照失败: {:?}", e),
+            Err(e) => log::warn!("⚠️ 写入本地快照任务异常: {:?}", e),
+        }
+    }
+
+    /// 定期刷新变更日志并写快照
+    async fn run_store(interval_secs: u64) {
+        let shard_manager = Self::get();
+        let Some(store) = shard_manager.store.clone() else {
+            return;
+        };
+        let snapshot_every = (interval_secs / LOG_FLUSH_INTERVAL.as_secs()).max(1);
+        let mut ticker = tokio::time::interval(LOG_FLUSH_INTERVAL);
+        let mut ticks = 0;
+        loop {
+            ticker.tick().await;
+            ticks += 1;
+            if ticks % snapshot_every == 0 {
+                shard_manager.save_snapshot().await;
+            } else {
+                store.flush();
+            }
+        }
     }
 
     pub async fn init() {
         let instance = Self::new();
-        instance.load_from_data().await.expect("load_from redis error");
+        // 先打开变更流再加载，加载期间直接写库的变更不会遗漏
+        let watcher = match ChangeWatcher::open(instance.get_node_addr()).await {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                log::warn!("⚠️ 打开群组变更流失败，直接写库的变更不会同步到本节点: {:?}", e);
+                None
+            }
+        };
+        let follow_deletes = watcher.as_ref().is_some_and(ChangeWatcher::resumed);
+        instance.load_from_data(follow_deletes).await.expect("load_from redis error");
+        let interval_secs = instance.shard_config.snapshot_interval_secs.unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_SECS);
+        let has_store = instance.store.is_some();
         INSTANCE.set(Arc::new(instance)).expect("INSTANCE already initialized");
+        if has_store {
+            tokio::spawn(Self::run_store(interval_secs));
+        }
+        if let Some(watcher) = watcher {
+            tokio::spawn(watcher.run());
+        }
     }
 
     /// 获取单例
@@ -92,3 +340,14 @@ impl ShardManager {
     }
 }
 static INSTANCE: OnceCell<Arc<ShardManager>> = OnceCell::new();
+
+/// 复制群组成员及在线状态
+fn copy_group(from: &HashShardMap, to: &HashShardMap, key: &str) {
+    if let Err(e) = to.insert_many(key, from.get_member_by_key(key)) {
+        log::warn!("⚠️ 复制群组 {} 失败: {:?}", key, e);
+        return;
+    }
+    for id in from.get_online_ids(key) {
+        let _ = to.set_online(key, &id, true);
+    }
+}
@@ -1,86 +1,57 @@
-use crate::service::arb_manager::ArbManagerJob;
-use crate::service::shard_manager::{ShardManager, ShardManagerOpt};
-use anyhow::Result;
+use crate::db::member::member_index::{MemberCursor, MemberPage};
+use crate::db::shard_store::ShardOp;
+use crate::service::shard_manager::{MigrationView, ShardManager, ShardManagerOpt};
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use biz_core::service::group_member_service::GroupMemberService;
 use biz_core::service::group_service::GroupService;
+use biz_core::service::rpc_server_client_service::ArbServerRpcServiceClientService;
+use biz_core::service::shard_client_service::ShardClientService;
 use biz_core::entitys::group_member_entity::GroupMemberEntity;
 use biz_core::protocol::common::GroupRoleType;
-use common::config::AppConfig;
-use common::util::common_utils::hash_index;
+use common::repository_util::Repository;
+use common::util::date_util::now;
+use common::util::hash_ring::{HashRange, HashRing};
 use common::{GroupId, UserId};
 use futures_util::StreamExt;
-use mongodb::bson::doc;
+use mongodb::bson::{doc, Bson};
 use mongodb::options::FindOptions;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use biz_core::protocol::arb::arb_models::{MemberRef, NodeType, QueryNodeReq};
+use biz_core::protocol::arb::shard_service::{CheckCanSpeakResp, MemberOrder};
+
+/// 对账时快照时间向前放宽的余量（毫秒），容忍各服务写入 update_time 时的时钟偏差
+const RECONCILE_CLOCK_SKEW_MS: u64 = 60_000;
+/// 对账时每次查询重新加载的群组数
+const RELOAD_BATCH_GROUPS: usize = 500;
 
 #[async_trait]
 impl ShardManagerOpt for ShardManager {
-    async fn load_from_data(&self) -> anyhow::Result<()> {
-        let group_service = GroupService::get();
-        let group_member_service = GroupMemberService::get();
-        let collection = &group_service.dao.collection;
-        let page_size = 100;
-        let mut page = 0;
-        let mut arb_manager_job = ArbManagerJob::new();
-        arb_manager_job.init_arb_client().await?;
-        let req = QueryNodeReq {
-            node_type: NodeType::GroupNode as i32,
-        };
-        let response = arb_manager_job.arb_client.unwrap().list_all_nodes(req).await?;
-
-        let list = response.get_ref();
-
-        let shard_addr = &AppConfig::get().shard.clone().unwrap().client_addr.unwrap();
-        if list.nodes.len() == 0 {
-            return Ok(());
-        }
-        let shard_index = hash_index(shard_addr, list.nodes.len() as i32);
-
-        loop {
-            let skip = page * page_size;
-
-            let find_options = FindOptions::builder()
-                .projection(doc! { "_id": 1 }) // 只取 _id
-                .limit(page_size as i64)
-                .skip(skip as u64)
-                .build();
-
-            let mut cursor = collection.find(doc! {}).with_options(find_options).await?;
-
-            let mut has_result = false;
-
-            while let Some(doc) = cursor.next().await {
-                has_result = true;
-
-                // 获取 group_id
-                let group_id = match doc {
-                    Ok(d) => match d.get_object_id("_id") {
-                        Ok(oid) => oid.to_hex(),
-                        Err(_) => continue,
-                    },
-                    Err(_) => continue,
-                };
-                let group_index = hash_index(&group_id, list.nodes.len() as i32);
-                if shard_index != group_index {
-                    continue;
-                }
-                // 查询该群的所有成员
-                let members: Vec<GroupMemberEntity> =
-                    group_member_service.get_all_members_by_group_id(&group_id).await?;
-
-                // 将每个成员添加到该群组分片中
-                for member in members {
-                    let role_type = GroupRoleType::try_from(member.role)?;
-                    self.add_member(&group_id, &member.uid, role_type)?;
+    async fn load_from_data(&self, follow_deletes: bool) -> anyhow::Result<()> {
+        let (ring, epoch) = self.fetch_ring().await?;
+        // 对账只能发现有更新时间的变更，快照之后的删除需要变更流重放，无法续传时改为全量加载
+        let restored = if follow_deletes { self.restore_local(&ring) } else { None };
+        match restored {
+            Some((since, snapshot_ring)) => {
+                let reloaded = self.reconcile(&ring, since).await?;
+                log::info!("🔁 与数据库对账完成，重新加载 {} 个群组", reloaded);
+                let gained = self.load_gained_groups(&snapshot_ring, &ring).await?;
+                if gained > 0 {
+                    log::info!("📦 快照之后迁入本节点的群组 {} 个，已从数据库加载", gained);
                 }
             }
-            if !has_result {
-                break;
+            None => {
+                self.load_owned_groups(&ring).await?;
             }
-            page += 1;
         }
-
+        self.ring.store(Arc::new(ring));
+        self.epoch.store(epoch, Ordering::Release);
+        self.observe_epoch(epoch);
+        ShardClientService::get().observe_epoch(epoch);
+        // 加载与对账不写变更日志，直接落一次快照
+        self.save_snapshot().await;
         Ok(())
     }
 
@@ -88,14 +59,23 @@ impl ShardManagerOpt for ShardManager {
         let group_member = GroupMemberService::get();
         let members = group_member.get_all_members_by_group_id(group_id).await?;
         for member in members {
-            let result = GroupRoleType::try_from(member.role)?;
-            self.add_member(group_id, &member.uid, result)?;
+            GroupRoleType::try_from(member.role)?;
+            let member_ref = MemberRef::from(member);
+            self.shard_data(group_id).shard_map.insert(group_id.to_string(), member_ref.clone());
+            self.record(ShardOp::Add {
+                group_id: group_id.to_string(),
+                member: member_ref,
+            });
         }
         Ok(())
     }
 
     fn dismiss(&self, group_id: &str) {
-        self.current.load().shard_map.clear(group_id)
+        self.current.load().shard_map.clear(group_id);
+        self.snapshot.load().shard_map.clear(group_id);
+        self.record(ShardOp::Clear {
+            group_id: group_id.to_string(),
+        });
     }
 
     /// 计算群组分片索引（用于分配 group → shard）
@@ -103,20 +83,30 @@ impl ShardManagerOpt for ShardManager {
         let member_ref = MemberRef {
             id: uid.clone(),
             role: role as i32,
+            join_time: now() as u64,
+            ..Default::default()
         };
-        self.current.load().shard_map.insert(group_id.to_string(), member_ref);
+        self.shard_data(group_id).shard_map.insert(group_id.to_string(), member_ref.clone());
+        self.record(ShardOp::Add {
+            group_id: group_id.to_string(),
+            member: member_ref,
+        });
         Ok(())
     }
 
     /// 从指定群组中移除某个用户（自动计算分片）
     fn remove_member(&self, group_id: &GroupId, uid: &UserId) -> anyhow::Result<()> {
-        self.current.load().shard_map.remove(group_id, uid);
+        self.shard_data(group_id).shard_map.remove(group_id, uid);
+        self.record(ShardOp::Remove {
+            group_id: group_id.clone(),
+            uid: uid.clone(),
+        });
         Ok(())
     }
 
     /// 获取某个群组的所有成员 ID 列表
     fn get_member(&self, group_id: &GroupId) -> Result<Vec<MemberRef>> {
-        let member_list = self.current.load().shard_map.get_member_by_key(group_id);
+        let member_list = self.shard_data(group_id).shard_map.get_member_by_key(group_id);
         return Ok(member_list);
     }
 
@@ -124,42 +114,351 @@ impl ShardManagerOpt for ShardManager {
     fn get_member_page(
         &self,
         group_id: &GroupId,
+        order: MemberOrder,
+        cursor: Option<&MemberCursor>,
         offset: usize,
         limit: usize,
-    ) -> Result<Option<Vec<MemberRef>>> {
-        let result = self.current.load().shard_map.get_page(group_id, offset, limit);
+    ) -> Result<Option<MemberPage>> {
+        let skip = if cursor.is_some() { 0 } else { offset };
+        let result = self.shard_data(group_id).shard_map.get_page(group_id, order, cursor, skip, limit);
         return Ok(result);
     }
     fn get_member_count(&self, group_id: &GroupId) -> Result<usize> {
-        let count = self.current.load().shard_map.get_member_count_by_key(group_id);
+        let count = self.shard_data(group_id).shard_map.get_member_count_by_key(group_id);
         return Ok(count as usize);
     }
     fn online(&self, group_id: &GroupId, uid: &UserId) -> Result<()> {
-        self.current.load().shard_map.set_online(group_id, uid, true).unwrap();
+        self.shard_data(group_id).shard_map.set_online(group_id, uid, true).unwrap();
         return Ok(());
     }
 
     fn offline(&self, group_id: &GroupId, user_id: &UserId) {
-        self.current.load().shard_map.set_online(group_id, user_id, false);
+        self.shard_data(group_id).shard_map.set_online(group_id, user_id, false);
     }
     fn get_on_line_member(&self, group_id: &GroupId) -> Vec<UserId> {
-        self.current.load().shard_map.get_online_ids(group_id)
+        self.shard_data(group_id).shard_map.get_online_ids(group_id)
     }
 
     fn change_role(&self, group_id: &GroupId, uid: &UserId, role: GroupRoleType) -> Result<()> {
-        self.current.load().shard_map.change_role(group_id, uid, role);
+        self.shard_data(group_id).shard_map.change_role(group_id, uid, role);
+        self.record(ShardOp::ChangeRole {
+            group_id: group_id.clone(),
+            uid: uid.clone(),
+            role: role as i32,
+        });
         Ok(())
     }
-    async fn get_admin_member(&self, group_id: &GroupId) -> anyhow::Result<Option<Vec<UserId>>> {
-        let group_service = GroupService::get();
-        let admin_member = group_service.query_admin_member_by_group_id(group_id).await?;
-        Ok(Some(admin_member))
+
+    fn mute_member(&self, group_id: &GroupId, uid: &UserId, mute_until: u64) -> Result<bool> {
+        let updated = self
+            .shard_data(group_id)
+            .shard_map
+            .update_member(group_id, uid, |member| member.mute_until = mute_until)
+            .map_err(|e| anyhow!("mute member {} error: {:?}", uid, e))?;
+        if updated {
+            self.record(ShardOp::Mute {
+                group_id: group_id.clone(),
+                uid: uid.clone(),
+                mute_until,
+            });
+        }
+        Ok(updated)
+    }
+
+    fn set_alias(&self, group_id: &GroupId, uid: &UserId, alias: &str) -> Result<bool> {
+        let updated = self
+            .shard_data(group_id)
+            .shard_map
+            .update_member(group_id, uid, |member| member.alias = alias.to_string())
+            .map_err(|e| anyhow!("set alias {} error: {:?}", uid, e))?;
+        if updated {
+            self.record(ShardOp::SetAlias {
+                group_id: group_id.clone(),
+                uid: uid.clone(),
+                alias: alias.to_string(),
+            });
+        }
+        Ok(updated)
+    }
+
+    fn check_can_speak(&self, group_id: &GroupId, uid: &UserId) -> CheckCanSpeakResp {
+        match self.shard_data(group_id).shard_map.get_member(group_id, uid) {
+            None => CheckCanSpeakResp {
+                can_speak: false,
+                reason: "group.member.notfound".to_string(),
+                mute_until: 0,
+            },
+            Some(member) if member.mute_until > now() as u64 => CheckCanSpeakResp {
+                can_speak: false,
+                reason: "group.member.muted".to_string(),
+                mute_until: member.mute_until,
+            },
+            Some(_) => CheckCanSpeakResp {
+                can_speak: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn get_members_by_role(&self, group_id: &GroupId, role: GroupRoleType, online_only: bool) -> Vec<MemberRef> {
+        self.shard_data(group_id).shard_map.get_members_by_role(group_id, role, online_only)
+    }
+
+    fn get_admin_member(&self, group_id: &GroupId) -> Vec<UserId> {
+        let shard_map = &self.shard_data(group_id).shard_map;
+        [GroupRoleType::Owner, GroupRoleType::Admin]
+            .into_iter()
+            .flat_map(|role| shard_map.get_members_by_role(group_id, role, true))
+            .map(|member| member.id)
+            .collect()
     }
 
     fn get_user_groups(&self, uid: &UserId) -> anyhow::Result<Vec<String>> {
-        let group_service = self.current.load().shard_map.user_group_list(uid);
+        let mut group_service = self.current.load().shard_map.user_group_list(uid);
+        if self.migration.load().is_some() {
+            // 迁移期间待移交的群组仍在 snapshot 中
+            group_service.extend(self.snapshot.load().shard_map.user_group_list(uid));
+            group_service.sort();
+            group_service.dedup();
+        }
         let string_vec: Vec<String> =
             group_service.iter().map(|s| s.as_ref().to_string()).collect();
         Ok(string_vec)
     }
 }
+
+impl ShardManager {
+    /// 从仲裁拉取分片节点构建一致性哈希环（包含本节点），同时返回仲裁的分片纪元
+    async fn fetch_ring(&self) -> anyhow::Result<(HashRing, u64)> {
+        let req = QueryNodeReq {
+            node_type: NodeType::GroupNode as i32,
+        };
+        let response = ArbServerRpcServiceClientService::get().client.lock().await.list_all_nodes(req).await?.into_inner();
+        let mut ring = HashRing::from_arb(response.virtual_nodes, response.nodes.into_iter().map(|node| node.node_addr));
+        ring.add_node(self.get_node_addr());
+        Ok((ring, response.shard_epoch))
+    }
+
+    /// 从本地快照及变更日志恢复归属本节点的群组，返回快照时间及快照时的哈希环；未配置或没有可用快照时返回 None
+    fn restore_local(&self, ring: &HashRing) -> Option<(u64, HashRing)> {
+        let store = self.store.as_ref()?;
+        let shard_addr = self.get_node_addr();
+        let current = self.current.load();
+        match store.load(&current.shard_map, |group_id| ring.locate(group_id) == Some(shard_addr)) {
+            Ok(since) => since,
+            Err(e) => {
+                log::warn!("⚠️ 加载本地快照失败，改为从数据库全量加载: {:?}", e);
+                for key in current.shard_map.all_keys() {
+                    current.shard_map.clear(&key);
+                }
+                None
+            }
+        }
+    }
+
+    /// 与数据库对账：只查询快照之后有更新（加入、改角色等写入 `update_time`）的群组并整组重新加载，
+    /// 返回重新加载的群组数
+    ///
+    /// 按 `update_time` 过滤（`idx_update_time` 索引），开销与变更量相关而与群组总数无关；
+    /// 成员删除、解散不留更新时间，由变更流从续传位置重放，不在此处理。
+    pub(crate) async fn reconcile(&self, ring: &HashRing, since: u64) -> anyhow::Result<usize> {
+        let group_member_service = GroupMemberService::get();
+        let shard_addr = self.get_node_addr();
+        let since = since.saturating_sub(RECONCILE_CLOCK_SKEW_MS);
+        let changed = group_member_service
+            .dao
+            .collection
+            .distinct("group_id", doc! { "update_time": { "$gte": since as i64 } })
+            .await?;
+        let stale: Vec<String> = changed
+            .into_iter()
+            .filter_map(|group_id| match group_id {
+                Bson::String(group_id) => Some(group_id),
+                _ => None,
+            })
+            .filter(|group_id| ring.locate(group_id) == Some(shard_addr))
+            .collect();
+        for batch in stale.chunks(RELOAD_BATCH_GROUPS) {
+            self.reload_groups(batch).await?;
+        }
+        Ok(stale.len())
+    }
+
+    /// 全量重新加载归属本节点的群组（逐组替换），并移除数据库中已不存在的群组，返回加载的群组数
+    ///
+    /// 变更流续传失败、快照之后的删除无从得知时使用。
+    pub(crate) async fn reload_all_owned(&self, ring: &HashRing) -> anyhow::Result<usize> {
+        let group_service = GroupService::get();
+        let shard_addr = self.get_node_addr();
+        let find_options = FindOptions::builder().projection(doc! { "_id": 1 }).build();
+        let mut cursor = group_service.dao.collection.find(doc! {}).with_options(find_options).await?;
+        let mut owned = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let Ok(oid) = doc?.get_object_id("_id") else {
+                continue;
+            };
+            let group_id = oid.to_hex();
+            if ring.locate(&group_id) == Some(shard_addr) {
+                owned.push(group_id);
+            }
+        }
+        for batch in owned.chunks(RELOAD_BATCH_GROUPS) {
+            self.reload_groups(batch).await?;
+        }
+        let owned: HashSet<&str> = owned.iter().map(String::as_str).collect();
+        let current = self.current.load();
+        for key in current.shard_map.all_keys() {
+            if !owned.contains(key.as_ref()) {
+                current.shard_map.clear(&key);
+            }
+        }
+        Ok(owned.len())
+    }
+
+    /// 补齐迁移中从 `sources` 移交到本节点、但未收到的群组，返回加载的群组数
+    ///
+    /// 只用于源节点已离开环、等待移交超时或源节点回滚的情况；全部移交完成时调用方不调用。
+    pub(crate) async fn load_moved_groups(&self, view: &MigrationView, sources: &HashSet<String>) -> anyhow::Result<usize> {
+        let group_service = GroupService::get();
+        let shard_addr = self.get_node_addr();
+        let find_options = FindOptions::builder().projection(doc! { "_id": 1 }).build();
+        let mut cursor = group_service.dao.collection.find(doc! {}).with_options(find_options).await?;
+        let mut missing = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let Ok(oid) = doc?.get_object_id("_id") else {
+                continue;
+            };
+            let group_id = oid.to_hex();
+            if view.after.locate(&group_id) != Some(shard_addr) {
+                continue;
+            }
+            if !view.before.locate(&group_id).is_some_and(|from| sources.contains(from)) {
+                continue;
+            }
+            if !self.current.load().shard_map.contains_key(&group_id) {
+                missing.push(group_id);
+            }
+        }
+        for batch in missing.chunks(RELOAD_BATCH_GROUPS) {
+            self.reload_groups(batch).await?;
+        }
+        Ok(missing.len())
+    }
+
+    /// 加载从 `before` 切换到 `after` 时迁入本节点的区间内的群组（整组替换），返回加载的群组数
+    ///
+    /// 用于本地快照恢复：快照之后环发生过变化时，迁入的群组不在快照中，对账也只覆盖有更新的群组。
+    pub(crate) async fn load_gained_groups(&self, before: &HashRing, after: &HashRing) -> anyhow::Result<usize> {
+        let shard_addr = self.get_node_addr();
+        let gained: Vec<HashRange> = before
+            .diff(after)
+            .into_iter()
+            .filter(|m| m.to.as_deref() == Some(shard_addr))
+            .map(|m| m.range)
+            .collect();
+        if gained.is_empty() {
+            return Ok(0);
+        }
+        let group_service = GroupService::get();
+        let find_options = FindOptions::builder().projection(doc! { "_id": 1 }).build();
+        let mut cursor = group_service.dao.collection.find(doc! {}).with_options(find_options).await?;
+        let mut moved = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let Ok(oid) = doc?.get_object_id("_id") else {
+                continue;
+            };
+            let group_id = oid.to_hex();
+            let hash = HashRing::hash(&group_id);
+            if gained.iter().any(|range| range.contains(hash)) {
+                moved.push(group_id);
+            }
+        }
+        for batch in moved.chunks(RELOAD_BATCH_GROUPS) {
+            self.reload_groups(batch).await?;
+        }
+        Ok(moved.len())
+    }
+
+    /// 从数据库整组重新加载一批群组（一次查询）
+    async fn reload_groups(&self, group_ids: &[String]) -> anyhow::Result<()> {
+        let group_member_service = GroupMemberService::get();
+        let entities = group_member_service.dao.query(doc! { "group_id": { "$in": group_ids.to_vec() } }).await?;
+        let mut groups: HashMap<&str, Vec<MemberRef>> = group_ids.iter().map(|group_id| (group_id.as_str(), Vec::new())).collect();
+        for entity in entities {
+            if let Some(members) = groups.get_mut(entity.group_id.as_str()) {
+                members.push(MemberRef::from(entity));
+            }
+        }
+        let current = self.current.load();
+        for (group_id, members) in groups {
+            current.shard_map.clear(group_id);
+            current.shard_map.insert_many(group_id, members).map_err(|e| anyhow!("reload group {} error: {:?}", group_id, e))?;
+        }
+        Ok(())
+    }
+
+    /// 按一致性哈希环从数据库加载归属本节点的群组，返回加载的群组数
+    ///
+    /// 加载不写变更日志，调用方加载完成后写一次快照。
+    pub(crate) async fn load_owned_groups(&self, ring: &HashRing) -> anyhow::Result<usize> {
+        let group_service = GroupService::get();
+        let group_member_service = GroupMemberService::get();
+        let collection = &group_service.dao.collection;
+        let page_size = 100;
+        let mut page = 0;
+        let mut loaded = 0;
+        let shard_addr = self.get_node_addr();
+
+        loop {
+            let skip = page * page_size;
+
+            let find_options = FindOptions::builder()
+                .projection(doc! { "_id": 1 }) // 只取 _id
+                .limit(page_size as i64)
+                .skip(skip as u64)
+                .build();
+
+            let mut cursor = collection.find(doc! {}).with_options(find_options).await?;
+
+            let mut has_result = false;
+
+            while let Some(doc) = cursor.next().await {
+                has_result = true;
+
+                // 获取 group_id
+                let group_id = match doc {
+                    Ok(d) => match d.get_object_id("_id") {
+                        Ok(oid) => oid.to_hex(),
+                        Err(_) => continue,
+                    },
+                    Err(_) => continue,
+                };
+                if ring.locate(&group_id) != Some(shard_addr) {
+                    continue;
+                }
+                // 查询该群的所有成员
+                let members: Vec<GroupMemberEntity> =
+                    group_member_service.get_all_members_by_group_id(&group_id).await?;
+
+                // 将每个成员添加到该群组分片中
+                let mut member_refs = Vec::with_capacity(members.len());
+                for member in members {
+                    GroupRoleType::try_from(member.role)?;
+                    member_refs.push(MemberRef::from(member));
+                }
+                self.shard_data(&group_id)
+                    .shard_map
+                    .insert_many(&group_id, member_refs)
+                    .map_err(|e| anyhow!("load group {} error: {:?}", group_id, e))?;
+                loaded += 1;
+            }
+            if !has_result {
+                break;
+            }
+            page += 1;
+        }
+
+        Ok(loaded)
+    }
+}
+
@@ -0,0 +1,203 @@
+use crate::db::hash_shard_map::HashShardMap;
+use crate::service::rebalance_plan::RebalancePlan;
+use crate::service::shard_manager::{MemData, ShardManager};
+use anyhow::{anyhow, Result};
+use biz_core::protocol::arb::arb_client::arb_client_service_client::ArbClientServiceClient;
+use biz_core::protocol::arb::arb_client::{SyncGroupChunk, SyncStreamResp};
+use biz_core::protocol::arb::arb_models::MemberRef;
+use std::hash::Hasher;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tonic::transport::Channel;
+use twox_hash::XxHash64;
+
+/// 每块最多携带的成员数（在线成员同样按此分块，排在成员块之后）
+const SYNC_CHUNK_MEMBERS: usize = 2_000;
+/// 每次流式调用移交的群组数，调用成功后推进续传位置
+const SYNC_BATCH_GROUPS: usize = 256;
+/// 发送队列容量（块），接收方处理不过来时发送方在此等待
+const SYNC_CHANNEL_CAPACITY: usize = 16;
+/// 单个群组校验失败的最大重传次数
+const MAX_GROUP_RETRIES: u32 = 3;
+
+/// 群组校验和：成员（含各字段）与在线成员逐个取哈希后累加，与顺序无关，接收方可按块增量计算
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GroupChecksum(u64);
+
+impl GroupChecksum {
+    pub fn add_member(&mut self, member: &MemberRef) {
+        let mut hasher = XxHash64::with_seed(0);
+        hasher.write(member.id.as_bytes());
+        hasher.write_u8(0xff);
+        hasher.write_i32(member.role);
+        hasher.write_u64(member.mute_until);
+        hasher.write(member.alias.as_bytes());
+        hasher.write_u8(0xff);
+        hasher.write_u64(member.join_time);
+        self.0 = self.0.wrapping_add(hasher.finish());
+    }
+
+    pub fn add_online(&mut self, user_id: &str) {
+        let mut hasher = XxHash64::with_seed(1);
+        hasher.write(user_id.as_bytes());
+        self.0 = self.0.wrapping_add(hasher.finish());
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+/// 整组校验和（成员与在线成员）
+pub fn group_checksum(members: &[MemberRef], on_line_ids: &[String]) -> u64 {
+    let mut checksum = GroupChecksum::default();
+    members.iter().for_each(|member| checksum.add_member(member));
+    on_line_ids.iter().for_each(|user_id| checksum.add_online(user_id));
+    checksum.value()
+}
+
+/// 将群组切分为若干块：先发成员块（空群组也发一块），再发在线成员块，
+/// 接收方收到在线成员时成员均已写入；最后一块携带整组校验和与成员数
+pub fn group_chunks(shard_map: &HashShardMap, group_id: &str, epoch: u64, source_addr: &str) -> Vec<SyncGroupChunk> {
+    let members = shard_map.get_member_by_key(group_id);
+    let on_line_ids = shard_map.get_online_ids(group_id);
+    let checksum = group_checksum(&members, &on_line_ids);
+    let member_total = members.len() as u32;
+    let chunk = |members: Vec<MemberRef>, on_line_ids: Vec<String>| SyncGroupChunk {
+        epoch,
+        source_addr: source_addr.to_string(),
+        group_id: group_id.to_string(),
+        members,
+        on_line_ids,
+        last: false,
+        checksum,
+        member_total,
+    };
+    let mut chunks: Vec<SyncGroupChunk> = members.chunks(SYNC_CHUNK_MEMBERS).map(|part| chunk(part.to_vec(), Vec::new())).collect();
+    if chunks.is_empty() {
+        chunks.push(chunk(Vec::new(), Vec::new()));
+    }
+    chunks.extend(on_line_ids.chunks(SYNC_CHUNK_MEMBERS).map(|part| chunk(Vec::new(), part.to_vec())));
+    if let Some(last) = chunks.last_mut() {
+        last.last = true;
+    }
+    chunks
+}
+
+/// 以流式调用把 `groups` 移交给 `target`
+///
+/// 群组按 ID 有序发送，每次调用后把接收方返回的续传位置（其之前的群组均已写入）记下；
+/// 中断后再次调用时跳过续传位置之前（含）的群组，这些群组视为已移交完成。发送队列有界，接收方写入慢时发送方随之等待。
+/// 校验失败的群组立即单独重传，超过重传次数则整体失败（由调用方回滚）。
+/// 发送中的群组暂停写入，接收方写入后本节点不再受理（见 `ShardManager::owner_of`）。
+pub async fn stream_groups(
+    client: &mut ArbClientServiceClient<Channel>,
+    plan: &RebalancePlan,
+    target: &str,
+    data: Arc<MemData>,
+    mut groups: Vec<String>,
+) -> Result<usize> {
+    let shard_manager = ShardManager::get();
+    groups.sort();
+    if let Some(token) = plan.resume_token(target).await? {
+        let (done, rest): (Vec<String>, Vec<String>) = groups.into_iter().partition(|group_id| group_id.as_str() <= token.as_str());
+        // 之前的尝试已写入目标节点，本节点不再受理，否则之后的写入会在提交时丢失
+        for group_id in &done {
+            shard_manager.finish_hand_over(group_id);
+        }
+        groups = rest;
+        log::info!("⏩ 纪元 {} 向节点 {} 续传，跳过 {} 及之前的群组", plan.epoch, target, token);
+    }
+
+    let mut sent = 0;
+    for batch in groups.chunks(SYNC_BATCH_GROUPS) {
+        let mut pending = batch.to_vec();
+        let mut attempt = 0;
+        shard_manager.begin_hand_over(&pending);
+        while !pending.is_empty() {
+            if attempt > MAX_GROUP_RETRIES {
+                shard_manager.abort_hand_over(&pending);
+                return Err(anyhow!("groups {:?} failed checksum after {} retries", pending, MAX_GROUP_RETRIES));
+            }
+            let response = match send_batch(client, plan, data.clone(), pending.clone()).await {
+                Ok(response) => response,
+                Err(e) => {
+                    shard_manager.abort_hand_over(&pending);
+                    return Err(e);
+                }
+            };
+            sent += response.applied as usize;
+            if !response.resume_token.is_empty() {
+                plan.save_resume_token(target, &response.resume_token).await?;
+            }
+            if !response.failed_group_ids.is_empty() {
+                log::warn!("⚠️ 纪元 {} 向节点 {} 移交校验失败 {} 个群组，重传", plan.epoch, target, response.failed_group_ids.len());
+            }
+            for group_id in pending.iter().filter(|group_id| !response.failed_group_ids.contains(group_id)) {
+                shard_manager.finish_hand_over(group_id);
+            }
+            pending = response.failed_group_ids;
+            attempt += 1;
+        }
+    }
+    Ok(sent)
+}
+
+async fn send_batch(
+    client: &mut ArbClientServiceClient<Channel>,
+    plan: &RebalancePlan,
+    data: Arc<MemData>,
+    groups: Vec<String>,
+) -> Result<SyncStreamResp> {
+    let (tx, rx) = mpsc::channel::<SyncGroupChunk>(SYNC_CHANNEL_CAPACITY);
+    let epoch = plan.epoch;
+    let source_addr = plan.node_addr.clone();
+    let producer = tokio::spawn(async move {
+        for group_id in groups {
+            for chunk in group_chunks(&data.shard_map, &group_id, epoch, &source_addr) {
+                if tx.send(chunk).await.is_err() {
+                    // 接收方已结束本次调用
+                    return;
+                }
+            }
+        }
+    });
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) });
+    let result = client.sync_data_stream(stream).await;
+    producer.abort();
+    Ok(result?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::group_checksum;
+    use biz_core::protocol::arb::arb_models::MemberRef;
+
+    fn member(id: &str, role: i32) -> MemberRef {
+        MemberRef {
+            id: id.to_string(),
+            role,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_checksum_ignores_order_but_not_content() {
+        let a = vec![member("u1", 0), member("u2", 1), member("u3", 2)];
+        let b = vec![member("u3", 2), member("u1", 0), member("u2", 1)];
+        let online = vec!["u1".to_string(), "u3".to_string()];
+        let online_reordered = vec!["u3".to_string(), "u1".to_string()];
+        assert_eq!(group_checksum(&a, &online), group_checksum(&b, &online_reordered));
+
+        let role_changed = vec![member("u1", 0), member("u2", 2), member("u3", 2)];
+        assert_ne!(group_checksum(&a, &online), group_checksum(&role_changed, &online));
+        assert_ne!(group_checksum(&a, &online), group_checksum(&a[..2], &online));
+
+        let mut muted = a.clone();
+        muted[0].mute_until = 1_700_000_000_000;
+        assert_ne!(group_checksum(&a, &online), group_checksum(&muted, &online));
+
+        // 在线状态不同
+        assert_ne!(group_checksum(&a, &online), group_checksum(&a, &online[..1]));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::kafka::kafka_consumer::{get_consumer, get_pending_acks, PendingMeta};
+use crate::socket::socket_manager::{get_socket_manager, ConnState, ConnectionId, ConnectionInfo, ConnectionMeta};
+use anyhow::Result;
+use biz_core::protocol::common::ByteMessageType;
+use biz_core::service::inbox_service::{InboxEntry, InboxService};
+use common::util::date_util::now;
+use common::UserId;
+use once_cell::sync::OnceCell;
+use prost::bytes::Bytes;
+use prost::Message;
+use rdkafka::consumer::{CommitMode, Consumer};
+use rdkafka::message::{Message as KafkaMessageTrait, OwnedMessage};
+use rdkafka::{Offset, TopicPartitionList};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use tokio::time::{self, Duration};
+
+type MessageId = u64;
+
+/// 首次重传等待（毫秒），之后按 2 的幂次退避
+const RETRY_BASE_MS: u64 = 1_000;
+/// 最大重传次数，超过后转交收件箱，重连时补发
+const MAX_RETRY_ATTEMPTS: u32 = 5;
+/// 转交收件箱失败后的最长重试间隔（毫秒）
+const HANDOVER_MAX_BACKOFF_MS: u64 = 30_000;
+
+/// 单个连接上等待 ACK 的推送
+pub struct PendingPush {
+    pub bytes: Bytes,
+    /// 推送目标用户与其收件箱条目，放弃推送时据此转交收件箱
+    pub uid: UserId,
+    pub entry: InboxEntry,
+    pub attempts: u32,
+    pub next_retry_at: u64,
+}
+
+/// 分区消费进度：offset -> 未完成引用数
+///
+/// 消费登记时持有 1 个引用（处理中），每个待 ACK 的消息再持有 1 个引用，
+/// 引用归零的 offset 才算完成；只提交连续完成的最小水位，保证至少一次投递。
+#[derive(Default)]
+struct PartitionProgress {
+    inflight: BTreeMap<i64, usize>,
+    max_seen: i64,
+    committed: i64,
+}
+
+impl PartitionProgress {
+    /// 当前可提交的 offset（下一条待消费位置）
+    fn committable(&self) -> i64 {
+        match self.inflight.keys().next() {
+            Some(first) => *first,
+            None => self.max_seen + 1,
+        }
+    }
+}
+
+static PROGRESS: OnceCell<Mutex<HashMap<(String, i32), PartitionProgress>>> = OnceCell::new();
+
+fn progress() -> &'static Mutex<HashMap<(String, i32), PartitionProgress>> {
+    PROGRESS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 消费前登记 offset
+pub fn begin_offset(topic: &str, partition: i32, offset: i64) {
+    let mut map = progress().lock().unwrap();
+    let entry = map.entry((topic.to_string(), partition)).or_insert_with(|| PartitionProgress {
+        committed: offset,
+        ..Default::default()
+    });
+    *entry.inflight.entry(offset).or_insert(0) += 1;
+    entry.max_seen = entry.max_seen.max(offset);
+}
+
+/// 释放 offset 的一个引用，归零后尝试推进提交水位
+pub fn release_offset(topic: &str, partition: i32, offset: i64) {
+    let commit_to = {
+        let mut map = progress().lock().unwrap();
+        let Some(entry) = map.get_mut(&(topic.to_string(), partition)) else {
+            return;
+        };
+        if let Some(count) = entry.inflight.get_mut(&offset) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                entry.inflight.remove(&offset);
+            }
+        }
+        let committable = entry.committable();
+        if committable > entry.committed {
+            entry.committed = committable;
+            Some(committable)
+        } else {
+            None
+        }
+    };
+
+    if let Some(next_offset) = commit_to {
+        if let Err(e) = commit(topic, partition, next_offset) {
+            log::warn!("⚠️ Kafka offset 提交失败 {}-{}@{}: {:?}", topic, partition, next_offset, e);
+        }
+    }
+}
+
+fn commit(topic: &str, partition: i32, next_offset: i64) -> Result<()> {
+    let Some(consumer) = get_consumer() else {
+        return Ok(());
+    };
+    let mut tpl = TopicPartitionList::new();
+    tpl.add_partition_offset(topic, partition, Offset::Offset(next_offset))?;
+    consumer.commit(&tpl, CommitMode::Async)?;
+    log::debug!("✅ Kafka offset 已提交 {}-{}@{}", topic, partition, next_offset);
+    Ok(())
+}
+
+/// 向用户在线连接推送并逐连接追踪 ACK，返回推送的连接数
+///
+/// `entry` 为消息在用户收件箱中的条目，`device_filter` 按连接元信息逐设备过滤；先登记待确认再写入连接，避免客户端 ACK 早于登记；无在线连接时不追踪，
+/// 由收件箱在重连时补发；迁移中或关闭中的连接不再推送新消息，同样由收件箱补发。
+pub fn push_tracked<M: Message>(
+    source: &OwnedMessage,
+    entry: InboxEntry,
+    user_id: &str,
+    msg_type: &ByteMessageType,
+    msg: &M,
+    device_filter: impl Fn(&ConnectionMeta) -> bool,
+) -> Result<usize> {
+    let manager = get_socket_manager();
+    let targets: Vec<(ConnectionId, ConnectionInfo)> = manager
+        .get_connections_with_id_by_user(user_id)
+        .into_iter()
+        .filter(|(_, conn)| conn.meta.state == ConnState::Authenticated && device_filter(&conn.meta))
+        .collect();
+    if targets.is_empty() {
+        return Ok(0);
+    }
+
+    let mut buf = Vec::with_capacity(128);
+    buf.push(*msg_type as u8);
+    msg.encode(&mut buf)?;
+    let bytes = Bytes::from(buf);
+    let message_id = entry.message_id;
+
+    let pending_acks = get_pending_acks();
+    {
+        let mut meta = pending_acks.entry(message_id).or_insert_with(|| {
+            begin_offset(source.topic(), source.partition(), source.offset());
+            PendingMeta {
+                topic: source.topic().to_string(),
+                partition: source.partition(),
+                offset: source.offset(),
+                waiting: HashSet::new(),
+            }
+        });
+        for (conn_id, _) in &targets {
+            meta.waiting.insert(conn_id.clone());
+        }
+    }
+
+    let next_retry_at = now() as u64 + RETRY_BASE_MS;
+    for (_, conn) in &targets {
+        conn.unacked.insert(
+            message_id,
+            PendingPush {
+                bytes: bytes.clone(),
+                uid: user_id.to_string(),
+                entry: entry.clone(),
+                attempts: 0,
+                next_retry_at,
+            },
+        );
+        conn.sync_gate.deliver(&conn.sender, bytes.clone());
+    }
+    Ok(targets.len())
+}
+
+/// 连接对某条消息完成确认（ACK，或放弃推送且已转交收件箱）
+pub fn settle(conn_id: &ConnectionId, message_id: MessageId) {
+    let pending_acks = get_pending_acks();
+    let done = match pending_acks.get_mut(&message_id) {
+        Some(mut meta) => {
+            meta.waiting.remove(conn_id);
+            meta.waiting.is_empty()
+        }
+        None => false,
+    };
+    if done {
+        if let Some((_, meta)) = pending_acks.remove_if(&message_id, |_, m| m.waiting.is_empty()) {
+            release_offset(&meta.topic, meta.partition, meta.offset);
+        }
+    }
+}
+
+/// 处理客户端 ACK，返回该连接上是否存在对应的待确认推送
+pub fn acknowledge(conn_id: &ConnectionId, conn: &ConnectionInfo, message_id: MessageId) -> bool {
+    if conn.unacked.remove(&message_id).is_none() {
+        return false;
+    }
+    settle(conn_id, message_id);
+    true
+}
+
+/// 连接关闭时释放其全部待确认推送，转交收件箱后再结算
+pub fn release_connection(conn_id: &ConnectionId, conn: &ConnectionInfo) {
+    let message_ids: Vec<MessageId> = conn.unacked.iter().map(|e| *e.key()).collect();
+    let pushes: Vec<PendingPush> = message_ids.into_iter().filter_map(|id| conn.unacked.remove(&id)).map(|(_, push)| push).collect();
+    hand_over(conn_id.clone(), pushes);
+}
+
+/// 把放弃推送的消息转交收件箱：确认收件箱中存在条目后才结算，写入成功前消息保持待确认、offset 不提交
+///
+/// 收件箱写入幂等；同一用户其他设备已 ACK 移除的条目会被重新放回，重连时重复补发由客户端按 message_id 去重。
+fn hand_over(conn_id: ConnectionId, pushes: Vec<PendingPush>) {
+    if pushes.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut backoff = RETRY_BASE_MS;
+        let mut remaining = pushes;
+        loop {
+            let mut failed = Vec::new();
+            for push in remaining {
+                match InboxService::get().push(&push.uid, &push.entry).await {
+                    Ok(()) => settle(&conn_id, push.entry.message_id),
+                    Err(e) => {
+                        log::warn!("⚠️ 消息转交收件箱失败，稍后重试: {} uid={}: {:?}", push.entry.message_id, push.uid, e);
+                        failed.push(push);
+                    }
+                }
+            }
+            if failed.is_empty() {
+                return;
+            }
+            remaining = failed;
+            time::sleep(Duration::from_millis(backoff)).await;
+            backoff = (backoff * 2).min(HANDOVER_MAX_BACKOFF_MS);
+        }
+    });
+}
+
+/// 启动重传任务：按指数退避重发未确认消息，超过次数后转交收件箱
+pub fn start_retransmit_task() {
+    tokio::spawn(async {
+        let manager = get_socket_manager();
+        let mut interval = time::interval(Duration::from_millis(500));
+        loop {
+            interval.tick().await;
+            let now_ms = now() as u64;
+
+            for (conn_id, conn) in manager.all_connections() {
+                let mut expired = Vec::new();
+                for mut entry in conn.unacked.iter_mut() {
+                    let message_id = *entry.key();
+                    let push = entry.value_mut();
+                    if push.next_retry_at > now_ms {
+                        continue;
+                    }
+                    if push.attempts >= MAX_RETRY_ATTEMPTS {
+                        expired.push(message_id);
+                        continue;
+                    }
+                    push.attempts += 1;
+                    push.next_retry_at = now_ms + (RETRY_BASE_MS << push.attempts);
+                    log::debug!("🔁 重传消息 {} -> {:?} 第{}次", message_id, conn_id, push.attempts);
+                    conn.sync_gate.deliver(&conn.sender, push.bytes.clone());
+                }
+                let expired: Vec<PendingPush> = expired
+                    .into_iter()
+                    .filter_map(|message_id| conn.unacked.remove(&message_id))
+                    .map(|(message_id, push)| {
+                        log::warn!("⚠️ 消息重传超限放弃，转交收件箱等待重连补发: {} -> {:?}", message_id, conn_id);
+                        push
+                    })
+                    .collect();
+                hand_over(conn_id, expired);
+            }
+        }
+    });
+}
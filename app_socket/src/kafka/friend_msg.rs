@@ -1,59 +1,21 @@
-use crate::kafka::kafka_consumer::{get_pending_acks, PendingMeta};
 use crate::socket::socket_manager::SocketManager;
 use anyhow::Result;
-use biz_core::kafka_util::kafka_producer::KafkaInstanceService;
 use biz_core::protocol::common::ByteMessageType;
 use biz_core::protocol::msg::friend::FriendEventMsg;
 use bytes::Buf;
-use common::config::AppConfig;
-use common::util::common_utils::build_snow_id;
 use prost::Message;
-use rdkafka::message::{Message as KafkaMessageTrait, OwnedMessage};
+use rdkafka::message::OwnedMessage;
 use std::sync::Arc;
 
+/// 好友事件投递给接收方在线连接（事件不进收件箱，离线用户上线后通过好友列表拉取）
 pub async fn friend_msg_to_socket(
     mut body: impl Buf,
-    msg: &OwnedMessage,
+    _msg: &OwnedMessage,
     socket_manager: &Arc<SocketManager>,
 ) -> Result<()> {
-    let mut message = FriendEventMsg::decode(&mut body)?;
-    let message_id = message.message_id.clone();
-    message.message_id = build_snow_id();
-    get_pending_acks().insert(
-        message_id,
-        PendingMeta {
-            topic: msg.topic().to_string(),
-            partition: msg.partition(),
-            offset: msg.offset(),
-        },
-    );
-    let friend_event = FriendEventMsg {
-        message_id: build_snow_id(),
-        from_uid: "".to_string(),
-        to_uid: "".to_string(),
-        event_type: 0,
-        message: "".to_string(),
-        status: 0,
-        created_at: 0,
-        updated_at: 0,
-        source_type: 0,
-        from_a_name: "".to_string(),
-        to_a_name: "".to_string(),
-        from_remark: None,
-        to_remark: None,
-    };
-
-    // ---------- 6. Kafka 消息通知 ----------
-    let kafka_service = KafkaInstanceService::get();
-    let app_config = AppConfig::get();
-    let topic = &app_config.get_kafka().topic_single;
-    kafka_service
-        .send_proto(
-            &ByteMessageType::FriendEventMsgType,
-            &friend_event,
-            &friend_event.message_id,
-            topic,
-        )
-        .await?;
+    let message = FriendEventMsg::decode(&mut body)?;
+    socket_manager
+        .send_to_user_proto(&message.to_uid, &ByteMessageType::FriendEventMsgType, &message, None)
+        .map_err(|e| anyhow::anyhow!("好友事件推送失败: {:?}", e))?;
     Ok(())
 }
@@ -0,0 +1,59 @@
+use crate::kafka::ack_tracker;
+use crate::socket::socket_manager::{ConnectionMeta, SocketManager};
+use anyhow::Result;
+use biz_core::entitys::group_msg_entity::GroupMsgDelivery;
+use biz_core::protocol::common::ByteMessageType;
+use biz_core::protocol::msg::group::{CreateGroupMsg, DestroyGroupMsg};
+use biz_core::service::inbox_service::InboxEntry;
+use bytes::Buf;
+use prost::Message;
+use rdkafka::message::OwnedMessage;
+use std::sync::Arc;
+
+/// 群聊消息扇出给记录中本节点的接收者（生产者已按在线登记筛选、排除发送者），
+/// 只推送到登记过的设备，逐连接追踪 ACK
+pub async fn group_msg_to_socket(mut body: impl Buf, msg: &OwnedMessage, socket_manager: &Arc<SocketManager>) -> Result<()> {
+    let delivery = GroupMsgDelivery::decode(&mut body)?;
+    let Some(message) = delivery.message else {
+        log::warn!("⚠️ 群消息投递记录缺少消息体，已忽略");
+        return Ok(());
+    };
+
+    let mut pushed = 0;
+    for recipient in &delivery.recipients {
+        if !socket_manager.user_index.contains_key(&recipient.uid) {
+            continue;
+        }
+        let on_device = |meta: &ConnectionMeta| meta.device_type.is_some_and(|device| recipient.device_types.contains(&(device as i32)));
+        pushed += ack_tracker::push_tracked(msg, InboxEntry::group(message.message_id), &recipient.uid, &ByteMessageType::GroupMsgType, &message, on_device)?;
+    }
+    log::debug!("👥 群消息扇出 group_id={} 连接数={}", message.to, pushed);
+    Ok(())
+}
+
+/// 建群通知：推送给创建者与初始成员
+pub async fn group_create_to_socket(mut body: impl Buf, socket_manager: &Arc<SocketManager>) -> Result<()> {
+    let message = CreateGroupMsg::decode(&mut body)?;
+    let mut uids = message.uids.clone();
+    if !uids.contains(&message.creator_id) {
+        uids.push(message.creator_id.clone());
+    }
+    for uid in &uids {
+        if let Err(e) = socket_manager.send_to_user_proto(uid, &ByteMessageType::GroupCreateMsgType, &message, None) {
+            log::warn!("⚠️ 建群通知推送失败 uid={} group_id={}: {:?}", uid, message.group_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// 解散通知：成员记录已随解散删除，按消息携带的成员列表推送
+pub async fn group_dismiss_to_socket(mut body: impl Buf, socket_manager: &Arc<SocketManager>) -> Result<()> {
+    let message = DestroyGroupMsg::decode(&mut body)?;
+    for uid in message.uids.iter().filter(|uid| socket_manager.user_index.contains_key(*uid)) {
+        // 单个成员推送失败不影响其余成员
+        if let Err(e) = socket_manager.send_to_user_proto(uid, &ByteMessageType::GroupDismissMsgType, &message, None) {
+            log::warn!("⚠️ 解散通知推送失败 uid={} group_id={}: {:?}", uid, message.group_id, e);
+        }
+    }
+    Ok(())
+}
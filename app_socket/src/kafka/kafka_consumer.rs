@@ -4,12 +4,19 @@ use log::{debug, warn};
 use once_cell::sync::OnceCell;
 use rdkafka::message::{Message as KafkaMessageTrait, OwnedMessage};
 use serde::Deserialize;
+use std::collections::HashSet;
 use std::sync::Arc;
 
+use crate::kafka::ack_tracker;
 use crate::kafka::friend_msg::friend_msg_to_socket;
-use crate::socket::socket_manager::SocketManager;
+use crate::kafka::group_msg::{group_create_to_socket, group_dismiss_to_socket, group_msg_to_socket};
+use crate::kafka::status_msg::offline_status_to_socket;
+use crate::kafka::user_msg::user_msg_to_socket;
+use crate::socket::socket_manager::{local_node_addr, ConnectionId, SocketManager};
+use biz_core::kafka_util::kafka_producer::KafkaInstanceService;
 use biz_core::manager::user_manager::{UserManager, UserManagerOpt};
 use biz_core::protocol::common::ByteMessageType;
+use biz_core::service::presence_service::node_topic;
 use common::config::KafkaConfig;
 use common::util::date_util::now;
 use rdkafka::config::ClientConfig;
@@ -30,9 +37,11 @@ pub struct PendingMeta {
     pub topic: String,
     pub partition: i32,
     pub offset: i64,
+    /// 尚未 ACK 的目标连接，清空后该消息视为投递完成
+    pub waiting: HashSet<ConnectionId>,
 }
 
-/// 全局未确认消息映射（msg_id -> 元信息）
+/// 全局未确认消息映射（msg_id -> 元信息），由 ack_tracker 维护
 static PENDING_ACKS: OnceCell<Arc<DashMap<MessageId, PendingMeta>>> = OnceCell::new();
 
 pub fn get_pending_acks() -> Arc<DashMap<MessageId, PendingMeta>> {
@@ -49,14 +58,26 @@ pub fn get_consumer() -> Option<Arc<StreamConsumer>> {
 
 /// 启动 Kafka 消费循环
 pub async fn start_consumer(kafka_cfg: &KafkaConfig, socket_manager: Arc<SocketManager>) -> Result<()> {
+    // 聊天消息由生产者按在线登记投递到本节点专属 topic；公共 topic 只承载好友事件、下线通知等控制消息，
+    // 每个 socket 节点独立消费组，各节点都能收到
+    let node_addr = local_node_addr();
+    let single_topic = node_topic(&kafka_cfg.topic_single, &node_addr);
+    let group_topic = node_topic(&kafka_cfg.topic_group, &node_addr);
+    KafkaInstanceService::create_topics_or_exit(&kafka_cfg.brokers, &vec![(single_topic.clone(), 10, 1), (group_topic.clone(), 10, 1)]).await?;
     let consumer: StreamConsumer = ClientConfig::new()
-        .set("group.id", "im-dispatch-group")
+        .set("group.id", format!("im-dispatch-group-{}", node_addr))
         .set("bootstrap.servers", kafka_cfg.brokers.clone())
         .set("enable.auto.commit", "false") // 手动提交 offset
         .create()?;
 
-    consumer.subscribe(&[&kafka_cfg.topic_single.clone(), &kafka_cfg.topic_group.clone()])?;
-    log::info!("✅ Kafka 消费者已启动，订阅主题：{}, {}", &kafka_cfg.topic_single, &kafka_cfg.topic_group);
+    consumer.subscribe(&[&kafka_cfg.topic_single, &kafka_cfg.topic_group, &single_topic, &group_topic])?;
+    log::info!(
+        "✅ Kafka 消费者已启动，订阅主题：{}, {}, {}, {}",
+        &kafka_cfg.topic_single,
+        &kafka_cfg.topic_group,
+        &single_topic,
+        &group_topic
+    );
 
     let arc_consumer = Arc::new(consumer);
     if CONSUMER.set(arc_consumer.clone()).is_err() {
@@ -67,9 +88,12 @@ pub async fn start_consumer(kafka_cfg: &KafkaConfig, socket_manager: Arc<SocketM
         match arc_consumer.recv().await {
             Ok(msg) => {
                 let owned = msg.detach();
+                // 处理期间持有 offset，推送出去的消息全部 ACK 后才会提交
+                ack_tracker::begin_offset(owned.topic(), owned.partition(), owned.offset());
                 if let Err(e) = handle_kafka_message(&owned, &socket_manager).await {
                     log::error!("❌ Kafka 消息处理失败: {:?}", e);
                 }
+                ack_tracker::release_offset(owned.topic(), owned.partition(), owned.offset());
             }
             Err(e) => {
                 log::error!("❌ Kafka 消费错误: {:?}", e);
@@ -95,18 +119,28 @@ pub async fn handle_kafka_message(msg: &OwnedMessage, socket_manager: &Arc<Socke
 
         // 10~19 用户在线状态
         ByteMessageType::OnlineStatusMsgType => {}
-        ByteMessageType::OfflineStatusMsgType => {}
+        ByteMessageType::OfflineStatusMsgType => {
+            offline_status_to_socket(body, socket_manager).await?;
+        }
 
         // 20~29 聊天消息
-        ByteMessageType::UserMsgType => {}
-        ByteMessageType::GroupMsgType => {}
+        ByteMessageType::UserMsgType => {
+            user_msg_to_socket(body, msg).await?;
+        }
+        ByteMessageType::GroupMsgType => {
+            group_msg_to_socket(body, msg, socket_manager).await?;
+        }
 
         // 30~39 好友 & 群组事件
         ByteMessageType::FriendEventMsgType => {
             friend_msg_to_socket(body, msg, socket_manager).await?;
         }
-        ByteMessageType::GroupCreateMsgType => {}
-        ByteMessageType::GroupDismissMsgType => {}
+        ByteMessageType::GroupCreateMsgType => {
+            group_create_to_socket(body, socket_manager).await?;
+        }
+        ByteMessageType::GroupDismissMsgType => {
+            group_dismiss_to_socket(body, socket_manager).await?;
+        }
         ByteMessageType::HeartbeatMsgType => {
             log::debug!("心跳消息: {}", now());
         }
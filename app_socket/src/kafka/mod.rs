@@ -1,2 +1,6 @@
+pub mod ack_tracker;
 mod friend_msg;
+mod group_msg;
 pub mod kafka_consumer;
+mod status_msg;
+mod user_msg;
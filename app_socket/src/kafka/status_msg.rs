@@ -0,0 +1,30 @@
+use crate::socket::socket_manager::{ConnectionId, SocketManager};
+use anyhow::Result;
+use biz_core::protocol::common::ByteMessageType;
+use biz_core::protocol::msg::auth::OfflineStatueMsg;
+use bytes::Buf;
+use prost::Message;
+use std::sync::Arc;
+
+/// 下线通知：`client_id` 指定连接时为踢下线，仅由持有该连接的节点处理，
+/// 推送下线原因后关闭连接；未指定连接的为在线状态广播，这里不处理。
+pub async fn offline_status_to_socket(mut body: impl Buf, socket_manager: &Arc<SocketManager>) -> Result<()> {
+    let message = OfflineStatueMsg::decode(&mut body)?;
+    if message.client_id.is_empty() {
+        return Ok(());
+    }
+    let conn_id = ConnectionId(message.client_id.clone());
+    let Some(conn) = socket_manager.get_by_id(&conn_id) else {
+        return Ok(());
+    };
+    if conn.meta.uid.as_deref() != Some(message.uid.as_str()) {
+        log::warn!("⚠️ 踢下线目标与连接用户不一致，忽略: {:?} uid={}", conn_id, message.uid);
+        return Ok(());
+    }
+    if let Err(e) = socket_manager.send_to_connection_proto(&None, &conn_id, &ByteMessageType::OfflineStatusMsgType, &message) {
+        log::warn!("⚠️ 下线通知发送失败: {:?}", e);
+    }
+    socket_manager.close(&conn_id);
+    log::warn!("👢 连接已被踢下线: {:?} uid={} reason={}", conn_id, message.uid, message.reason);
+    Ok(())
+}
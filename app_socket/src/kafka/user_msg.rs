@@ -0,0 +1,18 @@
+use crate::kafka::ack_tracker;
+use anyhow::Result;
+use biz_core::entitys::user_msg_entity::UserMsgEntity;
+use biz_core::protocol::common::ByteMessageType;
+use biz_core::service::inbox_service::InboxEntry;
+use bytes::Buf;
+use prost::Message;
+use rdkafka::message::OwnedMessage;
+
+/// 单聊消息投递给接收方所有在线设备，逐连接追踪 ACK
+pub async fn user_msg_to_socket(mut body: impl Buf, msg: &OwnedMessage) -> Result<()> {
+    let message = UserMsgEntity::decode(&mut body)?;
+    let pushed = ack_tracker::push_tracked(msg, InboxEntry::single(message.message_id), &message.to, &ByteMessageType::UserMsgType, &message, |_| true)?;
+    if pushed == 0 {
+        log::debug!("📭 接收方不在本节点在线，等待重连补发: {}", message.to);
+    }
+    Ok(())
+}
@@ -1,6 +1,8 @@
 use app_socket::scheduler;
 use app_socket::service::rpc::arb_client_service_impl::ArbClientServiceImpl;
-use app_socket::socket::socket_server::start_server;
+use app_socket::socket::socket_server::{shutdown, start_server, start_ws_server};
+use app_socket::socket::socket_tls::SocketTls;
+use biz_core::kafka_util::kafka_producer::KafkaInstanceService;
 use common::config::AppConfig;
 use log::warn;
 use tokio::net::TcpListener;
@@ -8,18 +10,36 @@ use tokio::net::TcpListener;
 async fn main() -> anyhow::Result<()> {
     AppConfig::init(&"./app_socket/socket-config.toml".to_string()).await;
     let config = AppConfig::get();
-    //初始化 kafka
-    // KafkaService::init(&config.get_kafka()).await;
+    //初始化 kafka（登出、踢下线等通知需要生产者）
+    KafkaInstanceService::init_instance(&config.get_kafka()).await?;
     //初始化业务
     biz_core::init_service().await;
     //启动任务
     scheduler::configure();
     biz_core::manager::init();
     //arb server 与消费
-    ArbClientServiceImpl::start().await;
+    ArbClientServiceImpl::init().await?;
     //socket-web-server
-    let bind_cfg = &config.socket.clone().unwrap().node_addr;
+    let socket_cfg = config.socket.clone().unwrap();
+    SocketTls::init(socket_cfg.tls.clone())?;
+    if let Some(ws_addr) = &socket_cfg.ws_addr {
+        warn!("socket-ws-server bind: {}", ws_addr);
+        let ws_listener = TcpListener::bind(ws_addr).await?;
+        tokio::spawn(async move {
+            if let Err(e) = start_ws_server(ws_listener).await {
+                log::error!("❌ WebSocket 服务异常退出: {:?}", e);
+            }
+        });
+    }
+    let bind_cfg = &socket_cfg.node_addr;
     warn!("socket-web-server bind: {}", bind_cfg.clone());
     let listener = TcpListener::bind(bind_cfg).await?;
-    start_server(listener, &config.get_kafka().clone()).await
+    tokio::select! {
+        result = start_server(listener, &config.get_kafka().clone()) => result,
+        _ = tokio::signal::ctrl_c() => {
+            warn!("socket server shutting down");
+            shutdown().await;
+            Ok(())
+        }
+    }
 }
@@ -4,15 +4,20 @@ use common::util::date_util::now;
 use log::info;
 
 use biz_core::service::rpc_server_client_service::ArbServerRpcServiceClientService;
+use biz_core::service::shard_client_service::ShardClientService;
+use crate::service::rpc::msg_node_client::MsgNodeClient;
+use crate::socket::socket_manager::{local_node_addr, socket_ring, SocketManager};
+use biz_core::protocol::arb::arb_server::arb_server_rpc_service_client::ArbServerRpcServiceClient;
+use tonic::transport::Channel;
 
 use once_cell::sync::OnceCell;
 use std::str::FromStr;
 use std::sync::Arc;
-use tonic::{transport, Request, Response, Status};
+use tonic::{transport, Request, Response, Status, Streaming};
 use biz_core::kafka_util::node_util::NodeUtil;
 use biz_core::protocol::arb::arb_client::arb_client_service_server::{ArbClientService, ArbClientServiceServer};
-use biz_core::protocol::arb::arb_client::UpdateVersionReq;
-use biz_core::protocol::arb::arb_models::{NodeType, QueryNodeReq, RegRequest, SyncListGroup};
+use biz_core::protocol::arb::arb_client::{SyncGroupChunk, SyncStreamResp, UpdateVersionReq};
+use biz_core::protocol::arb::arb_models::{BaseRequest, NodeInfo, NodeType, QueryNodeReq, RegRequest, SyncListGroup};
 use biz_core::protocol::arb::arb_models::NodeType::{MsgGateway, SocketNode};
 use biz_core::protocol::common::CommonResp;
 
@@ -23,7 +28,6 @@ impl ArbClientServiceImpl {
     pub async fn start() {
         // 读取配置文件
         let rpc_server_service = ArbServerRpcServiceClientService::get();
-        let mut client = rpc_server_service.client.lock().await;
 
         let client_addr = AppConfig::get().get_shard().clone().client_addr.unwrap();
         let client_services = Self {};
@@ -32,9 +36,12 @@ impl ArbClientServiceImpl {
             node_type: NodeType::SocketNode as i32,
             node_addr: client_addr.clone(),
             kafka_addr: None,
+            socket_addr: Some(local_node_addr()),
+            ws_addr: AppConfig::get().socket.clone().and_then(|s| s.ws_addr),
         };
 
-        client.register_node(request).await.expect("reg socket gateway error");
+        rpc_server_service.register(request).await.expect("reg socket node error");
+        let mut client = rpc_server_service.client.lock().await;
 
         let response = client
             .list_all_nodes(QueryNodeReq {
@@ -44,7 +51,9 @@ impl ArbClientServiceImpl {
             .expect("list_all_nodes.error");
         let node_util = NodeUtil::get();
 
-        node_util.await.push_list(SocketNode, response.into_inner().nodes);
+        node_util.await.push_list(MsgGateway, response.into_inner().nodes);
+        Self::pull_msg_nodes(&mut client).await.expect("list msg nodes error");
+        Self::pull_socket_nodes(&mut client).await.expect("list socket nodes error");
 
         tokio::spawn(async move {
             // 启动 gRPC 服务
@@ -60,6 +69,60 @@ impl ArbClientServiceImpl {
         log::warn!("ArbGroupServiceServer started");
     }
 
+    /// 拉取单聊 / 群聊消息节点列表（客户端发消息时转发到这些节点）
+    async fn pull_msg_nodes(client: &mut ArbServerRpcServiceClient<Channel>) -> Result<(), Status> {
+        let node_util = NodeUtil::get().await;
+        for node_type in [NodeType::MsgFriend, NodeType::MesGroup] {
+            let response = client
+                .list_all_nodes(QueryNodeReq {
+                    node_type: node_type as i32,
+                })
+                .await?;
+            node_util.push_list(node_type, response.into_inner().nodes);
+        }
+        Ok(())
+    }
+
+    /// 拉取 socket 节点列表，返回最新列表、仲裁下发的虚拟节点数及接入环节点是否与本地缓存不同
+    async fn pull_socket_nodes(client: &mut ArbServerRpcServiceClient<Channel>) -> Result<(Vec<NodeInfo>, u32, bool), Status> {
+        let response = client
+            .list_all_nodes(QueryNodeReq {
+                node_type: SocketNode as i32,
+            })
+            .await?;
+        let response = response.into_inner();
+        let (nodes, virtual_nodes) = (response.nodes, response.virtual_nodes);
+        let node_util = NodeUtil::get().await;
+        let cached: Vec<String> =
+            node_util.node_address_list.get(&SocketNode).map(|list| socket_ring(&list, virtual_nodes).nodes().cloned().collect()).unwrap_or_default();
+        let changed = !socket_ring(&nodes, virtual_nodes).nodes().eq(cached.iter());
+        node_util.push_list(SocketNode, nodes.clone());
+        Ok((nodes, virtual_nodes, changed))
+    }
+
+    /// 查询仲裁服务当前的 socket 节点列表及虚拟节点数（本节点下线后用于计算连接迁移目标）
+    pub async fn list_socket_nodes() -> anyhow::Result<(Vec<NodeInfo>, u32)> {
+        let rpc_server_service = ArbServerRpcServiceClientService::get();
+        let mut client = rpc_server_service.client.lock().await;
+        let (nodes, virtual_nodes, _) = Self::pull_socket_nodes(&mut client).await?;
+        Ok((nodes, virtual_nodes))
+    }
+
+    /// 通知仲裁服务本节点优雅下线
+    pub async fn graceful_leave() -> anyhow::Result<()> {
+        let rpc_server_service = ArbServerRpcServiceClientService::get();
+        let mut client = rpc_server_service.client.lock().await;
+        let request = BaseRequest {
+            node_addr: AppConfig::get().get_shard().client_addr.unwrap_or_default(),
+            node_type: NodeType::SocketNode as i32,
+        };
+        let response = client.graceful_leave(request).await?.into_inner();
+        if !response.success {
+            log::warn!("⚠️ 节点下线通知未生效: {}", response.message);
+        }
+        Ok(())
+    }
+
     /// 获取单例
     pub fn get() -> Arc<Self> {
         INSTANCE.get().expect("INSTANCE is not initialized").clone()
@@ -67,6 +130,8 @@ impl ArbClientServiceImpl {
 
     pub async fn init() -> anyhow::Result<()> {
         NodeUtil::init().await;
+        MsgNodeClient::init();
+        ShardClientService::init();
         ArbServerRpcServiceClientService::init().await?;
         ArbClientServiceImpl::start().await;
         Ok(())
@@ -96,6 +161,10 @@ impl ArbClientService for ArbClientServiceImpl {
         }))
     }
 
+    async fn sync_data_stream(&self, _request: Request<Streaming<SyncGroupChunk>>) -> Result<Response<SyncStreamResp>, Status> {
+        Err(Status::unimplemented("sync_data_stream is only served by group shard nodes"))
+    }
+
     async fn flush_nodes(&self, _: Request<()>) -> Result<Response<CommonResp>, Status> {
         let rpc_server_service = ArbServerRpcServiceClientService::get();
         let mut client = rpc_server_service.client.lock().await;
@@ -106,6 +175,26 @@ impl ArbClientService for ArbClientServiceImpl {
             .await?;
         let node_util = NodeUtil::get();
         node_util.await.push_list(MsgGateway, response.into_inner().nodes);
+        Self::pull_msg_nodes(&mut client).await?;
+        MsgNodeClient::get().reset();
+        // 发群消息前按分片判断发言权限，分片节点变化时更新路由表
+        let response = client
+            .list_all_nodes(QueryNodeReq {
+                node_type: NodeType::GroupNode as i32,
+            })
+            .await?
+            .into_inner();
+        ShardClientService::get().update_routing(response.nodes, response.shard_epoch, response.virtual_nodes);
+        let (socket_nodes, virtual_nodes, changed) = Self::pull_socket_nodes(&mut client).await?;
+        if changed {
+            // socket 节点增减：迁移不再归属本节点的连接
+            tokio::spawn(async move {
+                match SocketManager::dispatch_mislocated_connections(socket_nodes, virtual_nodes).await {
+                    Ok(count) => log::warn!("🔀 socket 节点变更，迁移连接 {} 个", count),
+                    Err(e) => log::error!("❌ 连接迁移失败: {:?}", e),
+                }
+            });
+        }
         Ok(Response::new(CommonResp {
             success: true,
             message: String::new(),
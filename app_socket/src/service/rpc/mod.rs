@@ -1 +1,2 @@
 pub mod arb_client_service_impl;
+pub mod msg_node_client;
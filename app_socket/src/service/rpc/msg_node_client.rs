@@ -0,0 +1,70 @@
+use anyhow::anyhow;
+use biz_core::kafka_util::node_util::NodeUtil;
+use biz_core::protocol::arb::arb_models::{NodeInfo, NodeType};
+use biz_core::protocol::msg::friend_msg_server::friend_rpc_service_client::FriendRpcServiceClient;
+use biz_core::protocol::msg::group_msg_server::group_rpc_service_client::GroupRpcServiceClient;
+use common::util::common_utils::hash_index;
+use dashmap::DashMap;
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+use tonic::transport::Channel;
+
+/// 消息节点路由客户端：单聊按发送者 uid、群聊按 group_id 选择 msg_friend / msg_group 节点，
+/// 同一会话固定落在同一节点，并复用连接
+#[derive(Debug)]
+pub struct MsgNodeClient {
+    /// node_addr -> Channel
+    channels: DashMap<String, Channel>,
+}
+
+impl MsgNodeClient {
+    fn new() -> Self {
+        Self {
+            channels: DashMap::new(),
+        }
+    }
+
+    /// 单聊消息节点客户端
+    pub async fn friend_client(&self, uid: &str) -> anyhow::Result<FriendRpcServiceClient<Channel>> {
+        let channel = self.channel_for(NodeType::MsgFriend, uid).await?;
+        Ok(FriendRpcServiceClient::new(channel))
+    }
+
+    /// 群聊消息节点客户端
+    pub async fn group_client(&self, group_id: &str) -> anyhow::Result<GroupRpcServiceClient<Channel>> {
+        let channel = self.channel_for(NodeType::MesGroup, group_id).await?;
+        Ok(GroupRpcServiceClient::new(channel))
+    }
+
+    async fn channel_for(&self, node_type: NodeType, key: &str) -> anyhow::Result<Channel> {
+        let mut nodes: Vec<NodeInfo> = NodeUtil::get().await.node_address_list.get(&node_type).map(|list| list.clone()).unwrap_or_default();
+        if nodes.is_empty() {
+            return Err(anyhow!("msg.node.empty: {:?}", node_type));
+        }
+        nodes.sort_by(|a, b| a.node_addr.cmp(&b.node_addr));
+        let index = hash_index(key, nodes.len() as i32).unsigned_abs() as usize % nodes.len();
+        let node_addr = &nodes[index].node_addr;
+
+        if let Some(channel) = self.channels.get(node_addr) {
+            return Ok(channel.clone());
+        }
+        let channel = Channel::from_shared(format!("http://{}", node_addr))?.connect().await?;
+        self.channels.insert(node_addr.clone(), channel.clone());
+        Ok(channel)
+    }
+
+    /// 节点列表变更后清空连接缓存
+    pub fn reset(&self) {
+        self.channels.clear();
+    }
+
+    pub fn init() {
+        INSTANCE.set(Arc::new(Self::new())).expect("INSTANCE already initialized");
+    }
+
+    pub fn get() -> Arc<Self> {
+        INSTANCE.get().expect("INSTANCE is not initialized").clone()
+    }
+}
+
+static INSTANCE: OnceCell<Arc<MsgNodeClient>> = OnceCell::new();
@@ -0,0 +1,35 @@
+use crate::kafka::ack_tracker;
+use crate::socket::socket_manager::{ConnectionId, SocketManager};
+use anyhow::Result;
+use biz_core::protocol::common::ByteMessageType;
+use biz_core::protocol::msg::status::AckMsg;
+use biz_core::service::inbox_service::{InboxEntry, InboxService};
+use biz_core::service::offline_message_service::OfflineMessageService;
+
+/// 处理客户端 ACK
+///
+/// `ack_type` 为被确认消息的类型：先结束连接上的重传追踪（全部设备确认后提交 offset），
+/// 再从用户收件箱移除，单聊消息同时回写送达状态。
+pub async fn handle_ack(conn_id: &ConnectionId, ack: &AckMsg) -> Result<()> {
+    let Some(conn) = SocketManager::get().get_by_id(conn_id) else {
+        log::warn!("找不到连接: {:?}", conn_id);
+        return Ok(());
+    };
+    let tracked = ack_tracker::acknowledge(conn_id, &conn, ack.message_id);
+    log::debug!("✅ 收到 ACK message_id={} tracked={}", ack.message_id, tracked);
+
+    let Some(uid) = conn.meta.uid.as_ref() else {
+        return Ok(());
+    };
+    match ByteMessageType::try_from(ack.ack_type) {
+        Ok(ByteMessageType::UserMsgType) => {
+            InboxService::get().remove(uid, &InboxEntry::single(ack.message_id)).await?;
+            OfflineMessageService::get().mark_delivered(ack.message_id).await?;
+        }
+        Ok(ByteMessageType::GroupMsgType) => {
+            InboxService::get().remove(uid, &InboxEntry::group(ack.message_id)).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
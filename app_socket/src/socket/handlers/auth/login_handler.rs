@@ -1,11 +1,18 @@
-use crate::socket::socket_manager::{ConnectionId, SocketManager};
+use crate::socket::socket_manager::{presence_entry, ConnectionId, SocketManager};
+use anyhow::{anyhow, Result};
 use biz_core::manager::user_manager::{UserManager, UserManagerOpt};
 use biz_core::manager::user_manager_auth::{UserManagerAuth, UserManagerAuthOpt};
 use biz_core::protocol::common::ByteMessageType;
 use biz_core::protocol::msg::auth::{AuthType, DeviceType, LoginRespMsg};
-use biz_core::protocol::msg::status::AckMsg;
+use biz_core::service::presence_service::PresenceService;
+use biz_core::service::session_service::{SessionService, KICK_REASON_LOGIN_ELSEWHERE};
 use log::warn;
 
+/// 处理登录请求
+///
+/// `AuthType::Token` 时 `auth_content` 为此前签发的 token，用于断线重连免密登录；
+/// 登录成功后按设备类型策略登记会话（可能挤掉旧会话或拒绝本次登录），
+/// 再绑定连接的 uid 与设备并建立用户索引，连接进入已认证状态。
 pub async fn handle_login(
     conn_id: &ConnectionId,
     message_id: &u64,
@@ -15,28 +22,26 @@ pub async fn handle_login(
     device_type: &DeviceType,
 ) {
     let socket_manager = SocketManager::get();
-    let ack_msg = AckMsg {
-        message_id: 0,
-        ack_type: 0,
-        success: false,
-        error_code: 0,
-    };
-    let result = socket_manager.send_to_connection_proto(&Option::None, conn_id, &ByteMessageType::AckMsgType, &ack_msg);
-    match result {
-        Ok(_) => {
-            log::info!("✅ 登录成功，发送登录成功消息给客户端");
-        }
-        Err(e) => {
-            log::error!("❌ 登录失败，发送登录成功消息给客户端失败");
-        }
-    }
-
-    let conn = socket_manager.get_by_id(conn_id);
     let user_manager_auth = UserManagerAuth::get();
-    match user_manager_auth.login(message_id, auth_type, auth_content, password, device_type).await {
+    let login = match user_manager_auth.login(message_id, auth_type, auth_content, password, device_type).await {
+        Ok((token, client)) => admit_session(conn_id, &client.uid, auth_type, device_type, &token).await.map(|_| (token, client)),
+        Err(e) => Err(e),
+    };
+    let msg = match login {
         Ok((token, client)) => {
-            warn!("Login success: {}", auth_content);
-            let msg = LoginRespMsg {
+            if !socket_manager.authenticate(conn_id, &client.uid, *device_type, &token) {
+                warn!("连接已关闭，放弃登录绑定: {:?} uid={}", conn_id, client.uid);
+                let entry = presence_entry(conn_id, *device_type);
+                if let Err(e) = SessionService::get().unregister(&client.uid, device_type, &token, &entry).await {
+                    warn!("会话注销失败: {:?}", e);
+                }
+                if let Err(e) = PresenceService::get().unregister(&client.uid, &entry).await {
+                    warn!("在线登记注销失败: {:?}", e);
+                }
+                return;
+            }
+            log::info!("✅ 登录成功 uid={} auth_type={:?}", client.uid, auth_type);
+            LoginRespMsg {
                 message_id: *message_id,
                 token,
                 expires_at: 0,
@@ -45,12 +50,11 @@ pub async fn handle_login(
                 uid: client.uid.clone(),
                 nickname: client.name,
                 avatar: client.avatar,
-            };
-            socket_manager.send_to_connection_proto(&Some(*message_id), conn_id, &ByteMessageType::LoginRespMsgType, &msg).unwrap();
+            }
         }
         Err(e) => {
-            warn!("Login failed: {}", e);
-            let msg = LoginRespMsg {
+            warn!("Login failed: {} auth_type={:?}", e, auth_type);
+            LoginRespMsg {
                 message_id: *message_id,
                 token: "".to_string(),
                 expires_at: 0,
@@ -59,8 +63,49 @@ pub async fn handle_login(
                 nickname: "".to_string(),
                 success: false,
                 avatar: "".to_string(),
-            };
-            socket_manager.send_to_connection_proto(&Some(*message_id), conn_id, &ByteMessageType::LoginRespMsgType, &msg).unwrap();
+            }
+        }
+    };
+    if let Err(e) = socket_manager.send_to_connection_proto(&Some(*message_id), conn_id, &ByteMessageType::LoginRespMsgType, &msg) {
+        log::error!("❌ 登录响应发送失败: {:?}", e);
+    }
+}
+
+/// 登记连接的在线状态，再按设备类型登录策略登记会话，并踢掉被挤下线的旧会话
+///
+/// 携带客户端证书的连接只能登录证书签发给的 uid，不匹配时拒绝；
+/// 在线登记先于会话登记，其他节点的登录据此判断本会话存活；
+/// 先登录优先策略下已有存活会话时拒绝登录，撤销在线登记并吊销本次新签发的 token（token 恢复登录时保留原 token）。
+async fn admit_session(conn_id: &ConnectionId, uid: &str, auth_type: &AuthType, device_type: &DeviceType, token: &str) -> Result<()> {
+    let user_manager = UserManager::get();
+    let session_service = SessionService::get();
+    let presence_service = PresenceService::get();
+    let client_cert = SocketManager::get().get_by_id(conn_id).and_then(|conn| conn.meta.client_cert.clone());
+    if client_cert.is_some_and(|cert| !cert.matches(uid)) {
+        warn!("客户端证书与登录用户不匹配: {:?} uid={}", conn_id, uid);
+        if auth_type != &AuthType::Token {
+            user_manager.delete_token(token).await?;
+        }
+        return Err(anyhow!("login.cert.mismatch"));
+    }
+    let entry = presence_entry(conn_id, *device_type);
+    presence_service.register(uid, &entry).await?;
+    let evicted = match session_service.admit(uid, device_type, token, &entry).await {
+        Ok(evicted) => evicted,
+        Err(e) => {
+            if let Err(e) = presence_service.unregister(uid, &entry).await {
+                warn!("在线登记注销失败 uid={}: {:?}", uid, e);
+            }
+            if auth_type != &AuthType::Token {
+                user_manager.delete_token(token).await?;
+            }
+            return Err(e);
+        }
+    };
+    for (evicted_token, evicted_entry) in evicted {
+        if let Err(e) = session_service.kick(uid, device_type, &evicted_token, &evicted_entry, KICK_REASON_LOGIN_ELSEWHERE).await {
+            log::error!("❌ 踢下线失败 uid={} conn={}: {:?}", uid, evicted_entry.conn_id, e);
         }
     }
+    Ok(())
 }
@@ -1,7 +1,13 @@
 use common::util::date_util::now;
 use std::sync::atomic::Ordering;
 use tokio::time::{self, Duration, Interval};
-use crate::socket::socket_manager::get_socket_manager;
+use crate::socket::socket_manager::{get_socket_manager, presence_entry, ConnState};
+use biz_core::service::presence_service::PresenceService;
+
+/// 连接心跳超时（毫秒）
+const HEARTBEAT_TIMEOUT_MS: u64 = 60_000;
+/// 在线登记续期间隔，需明显小于 `PRESENCE_TTL_MS`
+const PRESENCE_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
 
 /// 启动全局统一心跳检测任务
 pub fn start_global_heartbeat_checker() {
@@ -13,14 +19,39 @@ pub fn start_global_heartbeat_checker() {
 
             let now_ts = now() as u64;
 
-            // 遍历当前所有连接
-            for entry in manager.connections.iter() {
-                let conn_id = entry.key();
-                let last_heartbeat = entry.value().last_heartbeat.load(Ordering::Relaxed);
-                if now_ts.saturating_sub(last_heartbeat) > 60_000 {
-                    log::warn!("⏱️ 心跳超时: {:?}", conn_id);
-                    manager.remove(conn_id);
-                }
+            // 先收集超时连接再移除，避免遍历时持有分片锁
+            let expired: Vec<_> = manager
+                .connections
+                .iter()
+                .filter(|entry| now_ts.saturating_sub(entry.value().last_heartbeat.load(Ordering::Relaxed)) > HEARTBEAT_TIMEOUT_MS)
+                .map(|entry| entry.key().clone())
+                .collect();
+            for conn_id in expired {
+                log::warn!("⏱️ 心跳超时: {:?}", conn_id);
+                manager.remove(&conn_id);
+            }
+        }
+    });
+}
+
+/// 启动在线登记续期任务：心跳未超时的已认证连接批量续期，停止心跳的连接随 TTL 自然过期
+pub fn start_presence_refresher() {
+    tokio::spawn(async {
+        let manager = get_socket_manager();
+        let mut interval = time::interval(PRESENCE_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let now_ts = now() as u64;
+            let entries: Vec<_> = manager
+                .all_connections()
+                .into_iter()
+                .filter(|(_, conn)| conn.meta.state == ConnState::Authenticated)
+                .filter(|(_, conn)| now_ts.saturating_sub(conn.last_heartbeat.load(Ordering::Relaxed)) <= HEARTBEAT_TIMEOUT_MS)
+                .filter_map(|(id, conn)| Some((conn.meta.uid.clone()?, presence_entry(&id, conn.meta.device_type?))))
+                .collect();
+            if let Err(e) = PresenceService::get().refresh(&entries).await {
+                log::warn!("⚠️ 在线登记续期失败: {:?}", e);
             }
         }
     });
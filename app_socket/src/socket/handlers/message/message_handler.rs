@@ -1 +1,205 @@
+use crate::service::rpc::msg_node_client::MsgNodeClient;
+use crate::socket::socket_manager::{ConnectionId, SocketManager};
+use anyhow::{anyhow, Result};
+use biz_core::entitys::group_msg_entity::GroupMsgEntity;
+use biz_core::entitys::user_msg_entity::UserMsgEntity;
+use biz_core::manager::user_manager::{UserManager, UserManagerOpt};
+use biz_core::protocol::common::ByteMessageType;
+use biz_core::protocol::msg::friend_msg_server::SendMessageRespMsg;
+use biz_core::protocol::msg::group_msg_server::GroupMessageReq;
+use biz_core::protocol::msg::status::AckMsg;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
 
+/// 连接未登录
+const ERR_UNAUTHORIZED: i32 = 401;
+/// 无权发送（冒用发送者、非好友、被拉黑、非群成员或被禁言）
+const ERR_FORBIDDEN: i32 = 403;
+/// 连接的转发队列已满，客户端稍后重发
+const ERR_BUSY: i32 = 429;
+/// 服务端处理失败
+const ERR_INTERNAL: i32 = 500;
+
+/// 每个连接排队等待转发的聊天消息上限
+const FORWARD_QUEUE_SIZE: usize = 64;
+
+/// 客户端发来的聊天消息
+pub enum ChatMessage {
+    User(UserMsgEntity),
+    Group(GroupMsgEntity),
+}
+
+impl ChatMessage {
+    fn client_message_id(&self) -> u64 {
+        match self {
+            ChatMessage::User(msg) => msg.message_id,
+            ChatMessage::Group(msg) => msg.message_id,
+        }
+    }
+
+    fn ack_type(&self) -> ByteMessageType {
+        match self {
+            ChatMessage::User(_) => ByteMessageType::UserMsgType,
+            ChatMessage::Group(_) => ByteMessageType::GroupMsgType,
+        }
+    }
+}
+
+/// 连接的聊天消息转发队列
+///
+/// 读循环只负责入队，由每个连接独立的任务按接收顺序逐条转交 msg 节点，转发 RPC 不阻塞心跳与 ACK；
+/// 队列有界，积压满时直接回执 `ERR_BUSY`。发送端全部释放后任务处理完剩余消息即退出。
+pub struct ChatForwarder {
+    tx: mpsc::Sender<ChatMessage>,
+}
+
+impl ChatForwarder {
+    pub fn spawn(conn_id: ConnectionId) -> Self {
+        let (tx, mut rx) = mpsc::channel(FORWARD_QUEUE_SIZE);
+        tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                let result = match msg {
+                    ChatMessage::User(msg) => handle_user_message(&conn_id, msg).await,
+                    ChatMessage::Group(msg) => handle_group_message(&conn_id, msg).await,
+                };
+                if let Err(e) = result {
+                    log::error!("❌ 聊天消息处理失败 {:?}: {:?}", conn_id, e);
+                }
+            }
+        });
+        Self { tx }
+    }
+
+    /// 入队待转发的消息，队列已满时回执失败
+    pub fn submit(&self, conn_id: &ConnectionId, msg: ChatMessage) -> Result<()> {
+        match self.tx.try_send(msg) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(msg)) => {
+                log::warn!("⚠️ 转发队列已满，拒绝消息: {:?}", conn_id);
+                reply_error(conn_id, msg.client_message_id(), msg.ack_type(), ERR_BUSY)
+            }
+            Err(TrySendError::Closed(_)) => Err(anyhow!("转发队列已关闭")),
+        }
+    }
+}
+
+/// 客户端发送单聊消息
+///
+/// 校验发送者与连接身份一致、双方好友关系及黑名单后转交 msg_friend 节点，消息 ID 与会话序号由其在接收后分配；
+/// 结果以 `AckMsg` 回执，`message_id` 为客户端消息 ID，`server_message_id` 为服务端分配的 ID。
+async fn handle_user_message(conn_id: &ConnectionId, msg: UserMsgEntity) -> Result<()> {
+    let client_message_id = msg.message_id;
+    let Some(uid) = authorized_uid(conn_id, &msg.from) else {
+        return reply_error(conn_id, client_message_id, ByteMessageType::UserMsgType, ERR_UNAUTHORIZED);
+    };
+    if !msg.from.is_empty() && msg.from != uid {
+        log::warn!("⚠️ 发送者与连接身份不一致 uid={} from={}", uid, msg.from);
+        return reply_error(conn_id, client_message_id, ByteMessageType::UserMsgType, ERR_FORBIDDEN);
+    }
+
+    let user_manager = UserManager::get();
+    if !user_manager.is_friend(&uid, &msg.to).await? || user_manager.is_blocked(&msg.to, &uid).await? {
+        return reply_error(conn_id, client_message_id, ByteMessageType::UserMsgType, ERR_FORBIDDEN);
+    }
+
+    let req = SendMessageRespMsg {
+        message_id: 0,
+        from_uid: uid.clone(),
+        to_uid: msg.to.clone(),
+        contents: msg.content,
+    };
+    let result = match MsgNodeClient::get().friend_client(&uid).await {
+        Ok(mut client) => client.send_message(req).await.map(|r| r.into_inner()).map_err(|e| anyhow!(e)),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(resp) if resp.success => reply(conn_id, client_message_id, ByteMessageType::UserMsgType, resp.message_id, resp.seq),
+        Ok(resp) => {
+            log::warn!("⚠️ 单聊消息被拒绝 uid={} to={}: {}", uid, msg.to, resp.message);
+            reply_error(conn_id, client_message_id, ByteMessageType::UserMsgType, ERR_FORBIDDEN)
+        }
+        Err(e) => {
+            log::error!("❌ 单聊消息转发失败 uid={} to={}: {:?}", uid, msg.to, e);
+            reply_error(conn_id, client_message_id, ByteMessageType::UserMsgType, ERR_INTERNAL)
+        }
+    }
+}
+
+/// 客户端发送群聊消息
+///
+/// 转交 msg_group 节点，成员资格与禁言由其校验，消息 ID 与群内序号在接收后分配。
+async fn handle_group_message(conn_id: &ConnectionId, msg: GroupMsgEntity) -> Result<()> {
+    let client_message_id = msg.message_id;
+    let Some(uid) = authorized_uid(conn_id, &msg.from) else {
+        return reply_error(conn_id, client_message_id, ByteMessageType::GroupMsgType, ERR_UNAUTHORIZED);
+    };
+    if !msg.from.is_empty() && msg.from != uid {
+        log::warn!("⚠️ 发送者与连接身份不一致 uid={} from={}", uid, msg.from);
+        return reply_error(conn_id, client_message_id, ByteMessageType::GroupMsgType, ERR_FORBIDDEN);
+    }
+
+    let req = GroupMessageReq {
+        message_id: 0,
+        from_uid: uid.clone(),
+        group_id: msg.to.clone(),
+        contents: msg.content,
+    };
+    let result = match MsgNodeClient::get().group_client(&msg.to).await {
+        Ok(mut client) => client.send_group_message(req).await.map(|r| r.into_inner()).map_err(|e| anyhow!(e)),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(resp) if resp.success => reply(conn_id, client_message_id, ByteMessageType::GroupMsgType, resp.message_id, resp.seq),
+        Ok(resp) => {
+            log::warn!("⚠️ 群消息被拒绝 uid={} group_id={}: {}", uid, msg.to, resp.message);
+            reply_error(conn_id, client_message_id, ByteMessageType::GroupMsgType, ERR_FORBIDDEN)
+        }
+        Err(e) => {
+            log::error!("❌ 群消息转发失败 uid={} group_id={}: {:?}", uid, msg.to, e);
+            reply_error(conn_id, client_message_id, ByteMessageType::GroupMsgType, ERR_INTERNAL)
+        }
+    }
+}
+
+/// 连接已登录时返回其 uid
+fn authorized_uid(conn_id: &ConnectionId, from: &str) -> Option<String> {
+    let uid = SocketManager::get().get_by_id(conn_id).and_then(|conn| conn.meta.uid.clone());
+    if uid.is_none() {
+        log::warn!("⚠️ 未登录连接发送消息: {:?} from={}", conn_id, from);
+    }
+    uid
+}
+
+fn reply(conn_id: &ConnectionId, message_id: u64, ack_type: ByteMessageType, server_message_id: u64, seq: i64) -> Result<()> {
+    send_ack(
+        conn_id,
+        AckMsg {
+            message_id,
+            ack_type: ack_type as i32,
+            success: true,
+            error_code: 0,
+            server_message_id,
+            seq,
+        },
+    )
+}
+
+fn reply_error(conn_id: &ConnectionId, message_id: u64, ack_type: ByteMessageType, error_code: i32) -> Result<()> {
+    send_ack(
+        conn_id,
+        AckMsg {
+            message_id,
+            ack_type: ack_type as i32,
+            success: false,
+            error_code,
+            server_message_id: 0,
+            seq: 0,
+        },
+    )
+}
+
+fn send_ack(conn_id: &ConnectionId, ack: AckMsg) -> Result<()> {
+    SocketManager::get()
+        .send_to_connection_proto(&None, conn_id, &ByteMessageType::AckMsgType, &ack)
+        .map_err(|e| anyhow!("发送回执失败: {:?}", e))
+}
@@ -1,2 +1,2 @@
-mod message_handler;
+pub mod message_handler;
 mod read_receipt_handler;
@@ -1,6 +1,8 @@
+pub mod ack_handler;
 pub mod auth;
 mod group_attribute_change_handler;
 mod group_member_change_handler;
 pub mod heartbeat_handler;
 pub mod message;
+pub mod offline_sync_handler;
 mod typing_notice_handler;
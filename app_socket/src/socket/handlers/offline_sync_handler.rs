@@ -0,0 +1,162 @@
+use crate::socket::socket_manager::{ConnectionId, SocketManager};
+use anyhow::{anyhow, Result};
+use biz_core::entitys::group_msg_entity::GroupMsgEntity;
+use biz_core::entitys::user_msg_entity::UserMsgEntity;
+use biz_core::protocol::common::{ByteMessageType, ChatTargetType};
+use biz_core::protocol::msg::status::{AckMsg, ReConnectMsg};
+use biz_core::service::inbox_service::{InboxEntry, InboxService};
+use biz_core::service::offline_message_service::OfflineMessageService;
+use std::collections::HashMap;
+
+/// 每页补发条数
+const SYNC_PAGE_SIZE: usize = 100;
+
+/// 客户端上报的各会话已连续收到的最大序号（`ReConnectMsg.received`）
+///
+/// 消息 ID 由各消息节点分别生成，与投递、确认顺序无关，不能作为续传游标；
+/// 会话内序号连续无空洞，客户端只上报无缺口的前缀，缺口之后的消息都会补发。
+#[derive(Debug, Default)]
+struct ReceivedSeqs(HashMap<(ChatTargetType, String), i64>);
+
+impl ReceivedSeqs {
+    fn from_msg(msg: &ReConnectMsg) -> Self {
+        let mut seqs = HashMap::new();
+        for item in &msg.received {
+            let Ok(target_type) = ChatTargetType::try_from(item.target_type) else {
+                continue;
+            };
+            let seq = seqs.entry((target_type, item.target_id.clone())).or_insert(item.seq);
+            *seq = (*seq).max(item.seq);
+        }
+        Self(seqs)
+    }
+
+    /// 客户端是否已收到该会话中序号为 `seq` 的消息
+    fn received(&self, target_type: ChatTargetType, target_id: &str, seq: i64) -> bool {
+        self.0.get(&(target_type, target_id.to_string())).is_some_and(|received| seq <= *received)
+    }
+}
+
+/// 处理客户端重连：按收件箱顺序分页补发未确认消息，跳过客户端按会话序号确认已收到的，完成后切换为实时投递
+///
+/// 补发结束时回复 `AckMsg{ack_type: ReConnectMsgType}` 作为分界，
+/// 之后才会冲刷补发期间缓存的实时消息；两者可能重复，客户端按 message_id 去重。
+pub async fn handle_reconnect(conn_id: &ConnectionId, msg: &ReConnectMsg) -> Result<()> {
+    let manager = SocketManager::get();
+    let conn = manager.get_by_id(conn_id).ok_or_else(|| anyhow!("连接不存在: {:?}", conn_id))?;
+    let Some(uid) = conn.meta.uid.clone() else {
+        log::warn!("⚠️ 未登录连接发起重连同步: {:?}", conn_id);
+        return reply(conn_id, msg.message_id, false);
+    };
+    if !conn.sync_gate.begin() {
+        log::debug!("🔁 连接已在补发中，忽略重复重连请求: {:?}", conn_id);
+        return Ok(());
+    }
+
+    let result = sync_inbox(&uid, conn_id, &ReceivedSeqs::from_msg(msg)).await;
+    match &result {
+        Ok(total) => log::info!("📬 离线消息补发完成 uid={} 条数={}", uid, total),
+        Err(e) => log::error!("❌ 离线消息补发失败 uid={}: {:?}", uid, e),
+    }
+    let replied = reply(conn_id, msg.message_id, result.is_ok());
+    conn.sync_gate.finish(&conn.sender);
+    replied?;
+    result.map(|_| ())
+}
+
+/// 按游标逐页读取收件箱并推送原文，返回补发条数；客户端已收到的移出收件箱，不再补发
+async fn sync_inbox(uid: &str, conn_id: &ConnectionId, received: &ReceivedSeqs) -> Result<usize> {
+    let inbox = InboxService::get();
+    let store = OfflineMessageService::get();
+    let manager = SocketManager::get();
+
+    let mut cursor: Option<InboxEntry> = None;
+    let mut total = 0;
+    loop {
+        let page = inbox.page(uid, cursor.as_ref(), SYNC_PAGE_SIZE).await?;
+        if page.is_empty() {
+            break;
+        }
+
+        let user_ids: Vec<u64> = page.iter().filter(|e| e.target_type != ChatTargetType::Group).map(|e| e.message_id).collect();
+        let group_ids: Vec<u64> = page.iter().filter(|e| e.target_type == ChatTargetType::Group).map(|e| e.message_id).collect();
+        let user_msgs: HashMap<u64, UserMsgEntity> = store.find_user_messages(&user_ids).await?.into_iter().map(|m| (m.message_id, m)).collect();
+        let group_msgs: HashMap<u64, GroupMsgEntity> = store.find_group_messages(&group_ids).await?.into_iter().map(|m| (m.message_id, m)).collect();
+
+        for entry in &page {
+            let already = match entry.target_type {
+                ChatTargetType::Group => group_msgs.get(&entry.message_id).is_some_and(|m| received.received(ChatTargetType::Group, &m.to, m.seq)),
+                _ => user_msgs.get(&entry.message_id).is_some_and(|m| {
+                    let peer = if m.from == uid { &m.to } else { &m.from };
+                    received.received(ChatTargetType::Single, peer, m.seq)
+                }),
+            };
+            if already {
+                inbox.remove(uid, entry).await?;
+                continue;
+            }
+            let sent = match entry.target_type {
+                ChatTargetType::Group => group_msgs
+                    .get(&entry.message_id)
+                    .map(|m| manager.send_to_connection_proto(&None, conn_id, &ByteMessageType::GroupMsgType, m)),
+                _ => user_msgs
+                    .get(&entry.message_id)
+                    .map(|m| manager.send_to_connection_proto(&None, conn_id, &ByteMessageType::UserMsgType, m)),
+            };
+            match sent {
+                Some(result) => {
+                    result.map_err(|e| anyhow!("补发写入连接失败: {:?}", e))?;
+                    total += 1;
+                }
+                None => log::warn!("⚠️ 收件箱消息原文不存在，跳过: {}", entry.message_id),
+            }
+        }
+
+        if page.len() < SYNC_PAGE_SIZE {
+            break;
+        }
+        cursor = page.last().cloned();
+    }
+    Ok(total)
+}
+
+fn reply(conn_id: &ConnectionId, message_id: u64, success: bool) -> Result<()> {
+    let ack = AckMsg {
+        message_id,
+        ack_type: ByteMessageType::ReConnectMsgType as i32,
+        success,
+        error_code: 0,
+        server_message_id: 0,
+        seq: 0,
+    };
+    SocketManager::get()
+        .send_to_connection_proto(&None, conn_id, &ByteMessageType::AckMsgType, &ack)
+        .map_err(|e| anyhow!("重连应答发送失败: {:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReceivedSeqs;
+    use biz_core::protocol::common::ChatTargetType;
+    use biz_core::protocol::msg::status::{ConversationSeq, ReConnectMsg};
+
+    #[test]
+    fn test_unacked_seq_before_acked_is_resent() {
+        // 群 g1：客户端收到 1..=4 与 6，5 未确认，只能上报无缺口的 4
+        let msg = ReConnectMsg {
+            received: vec![ConversationSeq {
+                target_type: ChatTargetType::Group as i32,
+                target_id: "g1".to_string(),
+                seq: 4,
+            }],
+            ..Default::default()
+        };
+        let received = ReceivedSeqs::from_msg(&msg);
+        assert!(received.received(ChatTargetType::Group, "g1", 4));
+        assert!(!received.received(ChatTargetType::Group, "g1", 5));
+        assert!(!received.received(ChatTargetType::Group, "g1", 6));
+        // 其他会话不受影响
+        assert!(!received.received(ChatTargetType::Group, "g2", 1));
+        assert!(!received.received(ChatTargetType::Single, "g1", 1));
+    }
+}
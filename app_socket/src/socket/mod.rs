@@ -3,3 +3,5 @@ pub mod socket_connection;
 pub mod socket_error;
 pub mod socket_manager;
 pub mod socket_server;
+pub mod socket_tls;
+pub mod ws_connection;
@@ -1,53 +1,87 @@
+use crate::socket::handlers::ack_handler::handle_ack;
 use crate::socket::handlers::auth::login_handler::handle_login;
 use crate::socket::handlers::auth::logout_handler::handle_logout;
-use crate::socket::handlers::heartbeat_handler::start_global_heartbeat_checker;
-use crate::socket::socket_manager::{get_socket_manager, ConnectionId, ConnectionInfo, ConnectionMeta, SocketManager};
+use crate::socket::handlers::message::message_handler::{ChatForwarder, ChatMessage};
+use crate::socket::handlers::offline_sync_handler::handle_reconnect;
+use crate::socket::socket_manager::{get_socket_manager, ConnectionId, ConnState, ConnectionInfo, ConnectionMeta, SocketManager, SyncGate, Transport};
+use crate::socket::socket_tls::ClientCertIdentity;
 use anyhow::{anyhow, Result};
+use biz_core::entitys::group_msg_entity::GroupMsgEntity;
+use biz_core::entitys::user_msg_entity::UserMsgEntity;
 use biz_core::protocol::common::ByteMessageType;
 use biz_core::protocol::msg::auth::{DeviceType, LoginReqMsg, LogoutReqMsg, OfflineStatueMsg, OnlineStatusMsg, SendVerificationCodeReqMsg};
 use biz_core::protocol::msg::friend::FriendEventMsg;
 use biz_core::protocol::msg::group::{CreateGroupMsg, DestroyGroupMsg};
-use biz_core::protocol::msg::status::{AckMsg, HeartbeatMsg};
+use biz_core::protocol::msg::status::{AckMsg, HeartbeatMsg, ReConnectMsg};
 use biz_core::protocol::msg::system::SystemNotificationMsg;
 use biz_core::protocol::msg::user::UserFlushMsg;
-use bytes::Buf;
+use bytes::{Buf, Bytes, BytesMut};
 use common::errors::AppError;
+use dashmap::DashMap;
 use common::util::common_utils::build_uuid;
 use common::util::date_util::now;
-use futures::{SinkExt, StreamExt};
+use futures::{Sink, SinkExt, Stream, StreamExt};
 use prost::Message;
+use std::fmt::Debug;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::net::TcpStream;
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, Notify};
 use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
 
-/// 客户端连接处理入口
-pub async fn handle_connection(stream: TcpStream) -> Result<()> {
-    let conn_id = build_uuid();
-    let (read_half, write_half) = stream.into_split();
-    let mut reader = FramedRead::new(read_half, LengthDelimitedCodec::new());
-    let mut writer = FramedWrite::new(write_half, LengthDelimitedCodec::new());
+/// 连接关闭时等待发送队列写完的最长时间
+const WRITE_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 客户端连接处理入口（TCP / TLS，长度前缀分帧）
+pub async fn handle_connection<S>(stream: S, client_cert: Option<ClientCertIdentity>) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (read_half, write_half) = tokio::io::split(stream);
+    let reader = FramedRead::new(read_half, LengthDelimitedCodec::new()).map(|frame| frame.map(BytesMut::freeze).map_err(anyhow::Error::from));
+    let writer = FramedWrite::new(write_half, LengthDelimitedCodec::new());
+    serve_connection(reader, writer, Transport::Tcp, client_cert).await
+}
 
+/// 传输层无关的连接处理：注册连接、启动写任务并进入读循环
+///
+/// 每个帧为 `ByteMessageType` 单字节前缀 + protobuf 消息体，TCP 与 WebSocket 共用；
+/// `transport` 记录接入方式，迁移时据此下发对应协议的接入地址；`client_cert` 为 TLS 握手校验过的客户端证书身份。
+pub(crate) async fn serve_connection<R, W>(mut reader: R, mut writer: W, transport: Transport, client_cert: Option<ClientCertIdentity>) -> Result<()>
+where
+    R: Stream<Item = Result<Bytes>> + Unpin,
+    W: Sink<Bytes> + Unpin + Send + 'static,
+    W::Error: Debug,
+{
+    let conn_id = build_uuid();
     let (tx, mut rx) = mpsc::unbounded_channel();
     let last_heartbeat = Arc::new(AtomicU64::new(now() as u64));
     let conn_key = ConnectionId(conn_id.clone());
+    let close_signal = Arc::new(Notify::new());
 
     let connection = ConnectionInfo {
         meta: ConnectionMeta {
             uid: None,
             device_type: None,
+            token: None,
+            state: ConnState::Unauthenticated,
+            transport,
+            client_cert,
         },
-        sender: tx.clone(),
+        sender: tx,
         last_heartbeat: last_heartbeat.clone(),
         focus_target: None,
+        sync_gate: Arc::new(SyncGate::default()),
+        unacked: Arc::new(DashMap::new()),
+        close_signal: close_signal.clone(),
     };
 
     let manager = get_socket_manager();
     manager.insert(conn_key.clone(), connection);
 
     // 启动写任务
-    let write_task = tokio::spawn(async move {
+    let mut write_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
             if let Err(e) = writer.send(msg).await {
                 log::warn!("❌ 写入客户端失败: {:?}", e);
@@ -56,23 +90,32 @@ pub async fn handle_connection(stream: TcpStream) -> Result<()> {
         }
     });
 
-    // 启动读取任务
-    let result = read_loop(&mut reader, &conn_key, last_heartbeat.clone()).await;
+    // 聊天消息经有界队列由独立任务转发，连接结束时随发送端释放
+    let forwarder = ChatForwarder::spawn(conn_key.clone());
+
+    // 启动读取任务（服务端主动关闭时提前结束）
+    let result = tokio::select! {
+        result = read_loop(&mut reader, &conn_key, &forwarder, last_heartbeat.clone()) => result,
+        _ = close_signal.notified() => {
+            log::info!("🔌 服务端关闭连接: {:?}", conn_key);
+            Ok(())
+        }
+    };
 
-    // 清理资源
+    // 清理资源：移除连接后发送队列关闭，等待写任务发完剩余消息（如下线通知）
+    manager.mark_closing(&conn_key);
     manager.remove(&conn_key);
-    // 启动全局统一心跳检测任务
-    start_global_heartbeat_checker();
-    write_task.abort();
+    if tokio::time::timeout(WRITE_DRAIN_TIMEOUT, &mut write_task).await.is_err() {
+        write_task.abort();
+    }
     result
 }
 
 /// 读取客户端数据 & 处理消息
-async fn read_loop(
-    reader: &mut FramedRead<tokio::net::tcp::OwnedReadHalf, LengthDelimitedCodec>,
-    conn_id: &ConnectionId,
-    last_heartbeat: Arc<AtomicU64>,
-) -> Result<()> {
+async fn read_loop<R>(reader: &mut R, conn_id: &ConnectionId, forwarder: &ChatForwarder, last_heartbeat: Arc<AtomicU64>) -> Result<()>
+where
+    R: Stream<Item = Result<Bytes>> + Unpin,
+{
     while let Some(frame) = reader.next().await {
         let mut bytes = frame?;
 
@@ -84,6 +127,23 @@ async fn read_loop(
         let message_type = ByteMessageType::try_from(type_code as i32).unwrap_or(ByteMessageType::UnknownByteMessageType);
 
         let socket_manager = SocketManager::get();
+        match socket_manager.state_of(conn_id) {
+            Some(state) if state.allows(&message_type) => {}
+            Some(ConnState::Unauthenticated) => {
+                log::warn!("⚠️ 未认证连接发送受限消息: {:?} type={:?}", conn_id, message_type);
+                reject_unauthenticated(&socket_manager, conn_id, message_type);
+                continue;
+            }
+            Some(_) => {
+                log::debug!("连接迁移或关闭中，丢弃消息: {:?} type={:?}", conn_id, message_type);
+                continue;
+            }
+            None => {
+                // 心跳超时或迁移时已被移除，结束读循环以断开连接
+                log::info!("🔌 连接已被移除，结束读取: {:?}", conn_id);
+                break;
+            }
+        }
         match message_type {
             ByteMessageType::LoginReqMsgType => {
                 log::info!("🛂 收到w登录请求");
@@ -98,15 +158,17 @@ async fn read_loop(
                     if let Err(e) = socket_manager.send_to_connection(conn_id, cache.clone()) {
                         log::error!("❌ 发送缓存响应失败：{}", "login");
                     }
-                    return Ok(());
+                    continue;
                 }
                 handle_login(conn_id, &message_id, &login.auth_type(), &login.auth_content, &login.password, &device_type).await;
             }
             ByteMessageType::LogoutReqMsgType => {
                 let logout_req = LogoutReqMsg::decode(bytes)?;
-                log::info!("🛂 收到w登录请求");
+                log::info!("🛂 收到登出请求");
                 if let Some(conn) = get_socket_manager().get_by_id(conn_id) {
                     if let (Some(uid), Some(device_type)) = (&conn.meta.uid, &conn.meta.device_type) {
+                        // 登出后连接进入关闭流程，不再处理后续请求（登出响应仍经 Kafka 回推），由客户端断开或心跳超时回收
+                        socket_manager.mark_closing(conn_id);
                         let message_id = logout_req.message_id;
                         handle_logout(&message_id, uid, device_type).await?;
                     } else {
@@ -137,14 +199,16 @@ async fn read_loop(
                 let msg = OfflineStatueMsg::decode(bytes)?;
                 log::debug!("🔴 用户下线");
             }
-            // ByteMessageType::UserMsgType => {
-            //     let msg = UserMsgEntity::decode(bytes)?;
-            //     log::debug!("📨 普通消息处理");
-            // }
-            // ByteMessageType::GroupMsgType => {
-            //     let msg = GroupMsgEntity::decode(bytes)?;
-            //     log::debug!("👥 群聊消息处理");
-            // }
+            ByteMessageType::UserMsgType => {
+                let msg = UserMsgEntity::decode(bytes)?;
+                log::debug!("📨 普通消息处理");
+                forwarder.submit(conn_id, ChatMessage::User(msg))?;
+            }
+            ByteMessageType::GroupMsgType => {
+                let msg = GroupMsgEntity::decode(bytes)?;
+                log::debug!("👥 群聊消息处理");
+                forwarder.submit(conn_id, ChatMessage::Group(msg))?;
+            }
             ByteMessageType::FriendEventMsgType => {
                 let msg = FriendEventMsg::decode(bytes)?;
                 log::debug!("👥 好友事件处理");
@@ -162,9 +226,23 @@ async fn read_loop(
                 last_heartbeat.store(now() as u64, Ordering::Relaxed);
                 log::debug!("🫀 收到客户端心跳");
             }
+            ByteMessageType::ReConnectMsgType => {
+                let msg = ReConnectMsg::decode(bytes)?;
+                log::info!("🔁 收到重连同步请求");
+                // 补发在独立任务中进行，读循环继续处理心跳与 ACK
+                let conn_id = conn_id.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_reconnect(&conn_id, &msg).await {
+                        log::error!("❌ 重连同步失败: {:?}", e);
+                    }
+                });
+            }
             ByteMessageType::AckMsgType => {
                 let msg = AckMsg::decode(bytes)?;
                 log::debug!("ACK 消息处理");
+                if let Err(e) = handle_ack(conn_id, &msg).await {
+                    log::warn!("⚠️ ACK 处理失败 message_id={}: {:?}", msg.message_id, e);
+                }
             }
             _ => {
                 log::warn!("⚠️ 未知消息类型: {type_code}");
@@ -174,3 +252,18 @@ async fn read_loop(
 
     Ok(())
 }
+
+/// 未认证连接发送了需要登录的消息：回复失败回执（401），连接保持以便继续登录
+fn reject_unauthenticated(socket_manager: &SocketManager, conn_id: &ConnectionId, message_type: ByteMessageType) {
+    let ack = AckMsg {
+        message_id: 0,
+        ack_type: message_type as i32,
+        success: false,
+        error_code: 401,
+        server_message_id: 0,
+        seq: 0,
+    };
+    if let Err(e) = socket_manager.send_to_connection_proto(&None, conn_id, &ByteMessageType::AckMsgType, &ack) {
+        log::warn!("⚠️ 未认证回执发送失败: {:?}", e);
+    }
+}
@@ -1,23 +1,36 @@
 use std::collections::HashSet;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::kafka::ack_tracker::{self, PendingPush};
 use crate::socket::socket_error::SendError;
+use crate::socket::socket_tls::ClientCertIdentity;
 use anyhow::Result;
 use biz_core::protocol::common::{ByteMessageType, ChatTargetType};
 use biz_core::protocol::msg::auth::DeviceType;
+use biz_core::protocol::msg::status::ReConnectMsg;
+use biz_core::service::presence_service::{PresenceEntry, PresenceService};
+use biz_core::service::session_service::SessionService;
 use common::config::AppConfig;
-use common::util::common_utils::hash_index;
+use common::util::common_utils::build_snow_id;
+use common::util::hash_ring::HashRing;
 use common::UserId;
 use dashmap::DashMap;
 use log::{info, warn};
 use once_cell::sync::OnceCell;
 use prost::bytes::Bytes;
 use prost::Message;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Notify};
+use tokio::time::{self, Duration, Instant};
 use biz_core::protocol::arb::arb_models::NodeInfo;
 
+/// 迁移宽限期：等待客户端确认已推送消息的最长时间
+pub const MIGRATION_GRACE: Duration = Duration::from_secs(15);
+/// 迁移通知发出后的最短等待，保证通知先于关闭送达
+const MIGRATION_MIN_WAIT: Duration = Duration::from_secs(1);
+const MIGRATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 static MESSAGE_CACHE: OnceCell<Arc<DashMap<MessageId, (CachedResponse, u64)>>> = OnceCell::new();
 
 type MessageId = u64;
@@ -31,11 +44,109 @@ pub enum FocusTarget {
     User(Arc<str>),
     Group(Arc<str>),
 }
+/// 连接认证状态：未认证 → 已认证 →（迁移中）→ 关闭中
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnState {
+    /// 仅允许登录、心跳与验证码请求
+    Unauthenticated,
+    /// 登录或 token 恢复成功，已建立用户索引
+    Authenticated,
+    /// 已通知客户端迁移到其他节点，宽限期内仅处理 ACK 与心跳
+    Draining,
+    /// 登出或断开中，不再处理任何请求
+    Closing,
+}
+
+impl ConnState {
+    /// 当前状态下允许处理的消息类型
+    pub fn allows(&self, message_type: &ByteMessageType) -> bool {
+        match self {
+            ConnState::Authenticated => true,
+            ConnState::Unauthenticated => matches!(
+                message_type,
+                ByteMessageType::LoginReqMsgType | ByteMessageType::HeartbeatMsgType | ByteMessageType::SendVerificationCodeReqMsgType
+            ),
+            ConnState::Draining => matches!(message_type, ByteMessageType::AckMsgType | ByteMessageType::HeartbeatMsgType),
+            ConnState::Closing => false,
+        }
+    }
+}
+
+/// 客户端接入方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// TCP / TLS 长连接
+    Tcp,
+    /// WebSocket（Web 端）
+    Ws,
+}
+
+impl Transport {
+    /// 目标节点上同一接入方式的地址，节点未开放该接入方式时返回 None
+    pub fn addr_of<'a>(&self, node: &'a NodeInfo) -> Option<&'a str> {
+        let addr = match self {
+            Transport::Tcp => node.socket_addr.as_deref(),
+            Transport::Ws => node.ws_addr.as_deref(),
+        };
+        addr.filter(|addr| !addr.is_empty())
+    }
+}
+
 /// 连接元信息（用户、设备、客户端等）
 #[derive(Clone)]
 pub struct ConnectionMeta {
     pub uid: Option<UserId>,
     pub device_type: Option<DeviceType>,
+    /// 登录会话 token，用于多端登录会话登记与注销
+    pub token: Option<String>,
+    pub state: ConnState,
+    pub transport: Transport,
+    /// TLS 客户端证书身份（内部机器人），登录时 uid 须与之匹配
+    pub client_cert: Option<ClientCertIdentity>,
+}
+
+/// 离线同步闸门
+///
+/// 重连补发期间，实时推送先进入缓冲区，补发结束后按到达顺序冲刷，
+/// 再切换为实时直投，避免新消息插队到离线消息之前。
+#[derive(Default)]
+pub struct SyncGate {
+    /// None：实时投递；Some：补发中，缓存实时消息
+    buffer: Mutex<Option<Vec<Bytes>>>,
+}
+
+impl SyncGate {
+    /// 进入补发状态，已在补发中返回 false
+    pub fn begin(&self) -> bool {
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.is_some() {
+            return false;
+        }
+        *buffer = Some(Vec::new());
+        true
+    }
+
+    /// 实时投递入口：补发中则缓存，否则直接写入连接
+    pub fn deliver(&self, sender: &mpsc::UnboundedSender<Bytes>, bytes: Bytes) -> bool {
+        let mut buffer = self.buffer.lock().unwrap();
+        match buffer.as_mut() {
+            Some(pending) => {
+                pending.push(bytes);
+                true
+            }
+            None => sender.send(bytes).is_ok(),
+        }
+    }
+
+    /// 补发结束：冲刷缓冲区并切换为实时投递
+    pub fn finish(&self, sender: &mpsc::UnboundedSender<Bytes>) {
+        let mut buffer = self.buffer.lock().unwrap();
+        if let Some(pending) = buffer.take() {
+            for bytes in pending {
+                let _ = sender.send(bytes);
+            }
+        }
+    }
 }
 
 /// 连接实体（包含心跳状态）
@@ -45,6 +156,11 @@ pub struct ConnectionInfo {
     pub sender: mpsc::UnboundedSender<Bytes>,
     pub last_heartbeat: Arc<AtomicU64>,
     pub focus_target: Option<FocusTarget>,
+    pub sync_gate: Arc<SyncGate>,
+    /// 已推送未确认的消息（message_id → 推送内容），用于重传与 offset 提交
+    pub unacked: Arc<DashMap<MessageId, PendingPush>>,
+    /// 服务端主动关闭连接（如被踢下线）的通知
+    pub close_signal: Arc<Notify>,
 }
 
 /// Socket连接管理器：用于统一管理所有在线连接、用户索引及群组关系
@@ -91,18 +207,80 @@ impl SocketManager {
     /// 移除连接
     pub fn remove(&self, id: &ConnectionId) {
         if let Some((_, conn)) = self.connections.remove(id) {
+            ack_tracker::release_connection(id, &conn);
             if let Some(user_id) = &conn.meta.uid {
-                if let Some(mut set) = self.user_index.get_mut(user_id) {
-                    set.remove(id);
-                    if set.is_empty() {
-                        drop(set);
-                        self.user_index.remove(user_id);
+                self.unindex_user(user_id, id);
+            }
+            if let (Some(uid), Some(device_type), Some(token)) = (conn.meta.uid.clone(), conn.meta.device_type, conn.meta.token.clone()) {
+                let conn_id = id.0.clone();
+                let entry = presence_entry(id, device_type);
+                tokio::spawn(async move {
+                    if let Err(e) = SessionService::get().unregister(&uid, &device_type, &token, &entry).await {
+                        warn!("⚠️ 会话注销失败 uid={} conn={}: {:?}", uid, conn_id, e);
                     }
-                }
+                    if let Err(e) = PresenceService::get().unregister(&uid, &entry).await {
+                        warn!("⚠️ 在线登记注销失败 uid={} conn={}: {:?}", uid, conn_id, e);
+                    }
+                });
             }
             info!("🔌 连接断开: {:?}", id.0);
         }
     }
+    /// 登录成功：绑定用户与设备并建立用户索引，关闭中的连接返回 false
+    ///
+    /// 同一连接重复登录其他账号时，先从原用户索引中移除。
+    pub fn authenticate(&self, id: &ConnectionId, uid: &UserId, device_type: DeviceType, token: &str) -> bool {
+        let previous = {
+            let Some(mut conn) = self.connections.get_mut(id) else {
+                return false;
+            };
+            if conn.meta.state == ConnState::Closing {
+                return false;
+            }
+            let previous = conn.meta.uid.replace(uid.clone());
+            conn.meta.device_type = Some(device_type);
+            conn.meta.token = Some(token.to_string());
+            conn.meta.state = ConnState::Authenticated;
+            previous
+        };
+        if let Some(previous) = previous.filter(|p| p != uid) {
+            self.unindex_user(&previous, id);
+        }
+        self.user_index.entry(uid.clone()).or_insert_with(HashSet::new).insert(id.clone());
+        info!("🔐 连接认证成功: {:?} uid={} device={:?}", id.0, uid, device_type);
+        true
+    }
+
+    /// 标记连接进入关闭流程，之后的请求全部丢弃
+    pub fn mark_closing(&self, id: &ConnectionId) {
+        if let Some(mut conn) = self.connections.get_mut(id) {
+            conn.meta.state = ConnState::Closing;
+        }
+    }
+
+    /// 服务端主动关闭连接：进入关闭流程并通知读循环退出，已写入发送队列的消息仍会发出
+    pub fn close(&self, id: &ConnectionId) {
+        if let Some(mut conn) = self.connections.get_mut(id) {
+            conn.meta.state = ConnState::Closing;
+            conn.close_signal.notify_one();
+        }
+    }
+
+    /// 连接当前认证状态，连接不存在返回 None
+    pub fn state_of(&self, id: &ConnectionId) -> Option<ConnState> {
+        self.connections.get(id).map(|conn| conn.meta.state)
+    }
+
+    fn unindex_user(&self, user_id: &str, id: &ConnectionId) {
+        if let Some(mut set) = self.user_index.get_mut(user_id) {
+            set.remove(id);
+            if set.is_empty() {
+                drop(set);
+                self.user_index.remove_if(user_id, |_, set| set.is_empty());
+            }
+        }
+    }
+
     /// 获取连接
     pub fn get_by_id(&self, conn_id: &ConnectionId) -> Option<Arc<ConnectionInfo>> {
         self.connections.get(conn_id).map(|v| Arc::new(v.clone()))
@@ -160,6 +338,14 @@ impl SocketManager {
         self.user_index.get(user_id).map(|set| set.iter().filter_map(|id| self.connections.get(id).map(|c| c.clone())).collect()).unwrap_or_default()
     }
 
+    /// 获取用户所有连接（含连接ID）
+    pub fn get_connections_with_id_by_user(&self, user_id: &str) -> Vec<(ConnectionId, ConnectionInfo)> {
+        self.user_index
+            .get(user_id)
+            .map(|set| set.iter().filter_map(|id| self.connections.get(id).map(|c| (id.clone(), c.clone()))).collect())
+            .unwrap_or_default()
+    }
+
     /// 向用户发送消息（支持设备类型过滤）
     pub fn send_to_user(&self, user_id: &str, bytes: Bytes, device_filter: Option<DeviceType>) -> Result<(), SendError> {
         let mut sent = false;
@@ -168,7 +354,7 @@ impl SocketManager {
             for conn_id in conn_ids.iter() {
                 if let Some(conn) = self.connections.get(conn_id) {
                     if device_filter.map_or(true, |d| conn.meta.device_type == Some(d)) {
-                        if conn.sender.send(bytes.clone()).is_ok() {
+                        if conn.sync_gate.deliver(&conn.sender, bytes.clone()) {
                             sent = true;
                         }
                     }
@@ -206,30 +392,84 @@ impl SocketManager {
     pub fn all_connections(&self) -> Vec<(ConnectionId, ConnectionInfo)> {
         self.connections.iter().map(|entry| (entry.key().clone(), entry.value().clone())).collect()
     }
-    /// 检查所有连接是否需要迁移，若不属于当前节点，则发送断开通知
-    pub async fn dispatch_mislocated_connections(socket_list: Vec<NodeInfo>) -> Result<()> {
-        let manager = get_socket_manager();
-        let connections = manager.all_connections(); // snapshot
-
-        let node_count = socket_list.len();
-        if node_count == 0 {
-            log::warn!("⚠️ socket_list 为空，跳过连接迁移检查");
-            return Ok(());
+    /// 开始迁移连接：进入迁移状态并下发携带新节点接入地址的 `ReConnectMsg`
+    ///
+    /// 下发与连接接入方式一致的地址（WebSocket 连接下发 `ws_addr`），目标节点未开放该接入方式时不迁移。
+    /// 宽限期内继续接收 ACK 与心跳，待已推送消息全部确认或宽限期结束后关闭连接；
+    /// 仍未确认的消息随连接关闭转交收件箱后才提交 offset，客户端在新节点重连同步时补发。
+    pub fn begin_migration(&self, id: &ConnectionId, target: &NodeInfo) -> bool {
+        let target_addr = {
+            let Some(mut conn) = self.connections.get_mut(id) else {
+                return false;
+            };
+            if conn.meta.state != ConnState::Authenticated {
+                return false;
+            }
+            let Some(addr) = conn.meta.transport.addr_of(target) else {
+                warn!("⚠️ 目标节点未开放 {:?} 接入，跳过迁移: {:?} → {}", conn.meta.transport, id.0, target.node_addr);
+                return false;
+            };
+            conn.meta.state = ConnState::Draining;
+            addr.to_string()
+        };
+        let msg = ReConnectMsg {
+            message_id: build_snow_id(),
+            socket_addr: target_addr.clone(),
+            received: Vec::new(),
+        };
+        if let Err(e) = self.send_to_connection_proto(&None, id, &ByteMessageType::ReConnectMsgType, &msg) {
+            warn!("⚠️ 迁移通知发送失败: {:?} {:?}", id, e);
+            self.close(id);
+            return false;
         }
-        let socket_list = sort_nodes(socket_list);
-        let socket_addr = AppConfig::get().get_shard().server_addr.unwrap_or_default();
-        for (conn_id, conn_info) in connections {
-            let idx = hash_index(&conn_id.0, node_count as i32);
+        info!("🚧 连接迁移中: {:?} → {}", id.0, target_addr);
 
-            match socket_list.get(idx as usize) {
-                Some(target_node) if target_node.node_addr != socket_addr => {
-                    log::info!("🚧 连接不属于本节点，迁移中: conn_id={:?}, 分配节点={}", conn_id.0, target_node.node_addr,);
-
-                    // 可使用 RECONNECT 消息结构替代裸字符串
-                    let _ = conn_info.sender.send(Bytes::from("RECONNECT"));
+        let id = id.clone();
+        tokio::spawn(async move {
+            let manager = get_socket_manager();
+            let deadline = Instant::now() + MIGRATION_GRACE;
+            let mut interval = time::interval_at(Instant::now() + MIGRATION_MIN_WAIT, MIGRATION_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(conn) = manager.get_by_id(&id) else {
+                    // 客户端已主动断开
+                    return;
+                };
+                if conn.unacked.is_empty() || Instant::now() >= deadline {
+                    break;
+                }
+            }
+            manager.close(&id);
+        });
+        true
+    }
 
-                    // 移除本地连接（客户端将自动重连）
-                    manager.remove(&conn_id);
+    /// socket 节点变更后迁移不再归属本节点的已认证连接，返回迁移的连接数
+    ///
+    /// 按 uid 在一致性哈希接入环上选择目标节点，同一用户的多端连接落在同一节点；
+    /// 节点增减只迁移相邻区间内的用户。
+    pub async fn dispatch_mislocated_connections(socket_list: Vec<NodeInfo>, virtual_nodes: u32) -> Result<usize> {
+        let manager = get_socket_manager();
+        let ring = socket_ring(&socket_list, virtual_nodes);
+        if ring.is_empty() {
+            log::warn!("⚠️ socket_list 为空，跳过连接迁移检查");
+            return Ok(0);
+        }
+        let local_addr = local_node_addr();
+        let mut migrated = 0;
+        for (conn_id, conn_info) in manager.all_connections() {
+            if conn_info.meta.state != ConnState::Authenticated {
+                continue;
+            }
+            let Some(uid) = conn_info.meta.uid.as_ref() else {
+                continue;
+            };
+            match ring.locate(uid) {
+                Some(target) if target != local_addr => {
+                    let node = socket_list.iter().find(|node| node.socket_addr.as_deref() == Some(target));
+                    if node.is_some_and(|node| manager.begin_migration(&conn_id, node)) {
+                        migrated += 1;
+                    }
                 }
                 _ => {
                     // 属于当前节点，无需处理
@@ -237,20 +477,33 @@ impl SocketManager {
             }
         }
 
-        log::info!("✅ 连接迁移检查完成，连接总数：{}", manager.connections.len());
-        return Ok(());
+        log::info!("✅ 连接迁移检查完成，迁移 {} 个，连接总数：{}", migrated, manager.connections.len());
+        Ok(migrated)
     }
 }
 
 static SOCKET_MANAGER: OnceCell<Arc<SocketManager>> = OnceCell::new();
 
+/// 本节点地址（在线路由表中的节点标识，与 Kafka 消费组一致）
+pub fn local_node_addr() -> String {
+    AppConfig::get().socket.clone().map(|s| s.node_addr).unwrap_or_default()
+}
+
+/// 本节点连接的在线登记
+pub fn presence_entry(id: &ConnectionId, device_type: DeviceType) -> PresenceEntry {
+    PresenceEntry {
+        node_addr: local_node_addr(),
+        conn_id: id.0.clone(),
+        device_type,
+    }
+}
+
 /// 获取全局 SocketManager 实例
 pub fn get_socket_manager() -> Arc<SocketManager> {
     SOCKET_MANAGER.get_or_init(|| Arc::new(SocketManager::new())).clone()
 }
 
-// 对节点列表进行排序 按照地址
-fn sort_nodes(mut nodes: Vec<NodeInfo>) -> Vec<NodeInfo> {
-    nodes.sort_by(|a, b| a.node_addr.cmp(&b.node_addr));
-    nodes
+/// socket 节点的客户端接入环：以 TCP 接入地址为节点标识（未上报接入地址的节点不参与），虚拟节点数取仲裁下发值
+pub fn socket_ring(nodes: &[NodeInfo], virtual_nodes: u32) -> HashRing {
+    HashRing::from_arb(virtual_nodes, nodes.iter().filter_map(|n| n.socket_addr.clone()).filter(|addr| !addr.is_empty()))
 }
@@ -1,12 +1,19 @@
+use crate::kafka::ack_tracker;
 use crate::kafka::kafka_consumer;
 use crate::kafka::kafka_consumer::start_consumer;
+use crate::service::rpc::arb_client_service_impl::ArbClientServiceImpl;
+use crate::socket::handlers::heartbeat_handler::{start_global_heartbeat_checker, start_presence_refresher};
 use crate::socket::socket_connection::handle_connection;
-use crate::socket::socket_manager::get_socket_manager;
+use crate::socket::socket_manager::{get_socket_manager, local_node_addr, SocketManager, MIGRATION_GRACE};
+use crate::socket::socket_tls::SocketTls;
+use crate::socket::ws_connection::handle_ws_connection;
+use biz_core::service::presence_service::PresenceService;
 use common::config::KafkaConfig;
 use std::sync::Arc;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{Duration, Instant};
 
-/// 启动 TCP 服务 + Kafka 消费任务
+/// 启动 TCP 服务 + Kafka 消费任务（WebSocket 监听另由 `start_ws_server` 启动）
 pub async fn start_server(listener: TcpListener, kafka_cfg: &KafkaConfig) -> anyhow::Result<()> {
     let socket_manager = get_socket_manager(); // ✅ 获取全局 SocketManager 单例
 
@@ -22,6 +29,19 @@ pub async fn start_server(listener: TcpListener, kafka_cfg: &KafkaConfig) -> any
         });
     }
 
+    // ✅ 启动未确认消息重传任务
+    ack_tracker::start_retransmit_task();
+    // ✅ 启动全局统一心跳检测任务（TCP 与 WebSocket 连接共用）
+    start_global_heartbeat_checker();
+
+    // ✅ 清理本节点上次运行遗留的在线登记，并启动续期任务
+    match PresenceService::get().purge_node(&local_node_addr()).await {
+        Ok(count) if count > 0 => log::warn!("🧹 已清理本节点遗留在线登记 {} 条", count),
+        Ok(_) => {}
+        Err(e) => log::error!("❌ 清理遗留在线登记失败: {:?}", e),
+    }
+    start_presence_refresher();
+
     log::warn!("✅ TCP 服务器已启动，开始监听连接...");
 
     loop {
@@ -30,7 +50,7 @@ pub async fn start_server(listener: TcpListener, kafka_cfg: &KafkaConfig) -> any
                 log::warn!("📡 新连接建立 [{}]", addr);
 
                 tokio::spawn(async move {
-                    if let Err(e) = handle_connection(stream).await {
+                    if let Err(e) = serve_tcp(stream).await {
                         log::error!("❌ 连接处理失败 [{}]: {:?}", addr, e);
                     } else {
                         log::info!("🔌 连接处理完成 [{}]", addr);
@@ -43,3 +63,78 @@ pub async fn start_server(listener: TcpListener, kafka_cfg: &KafkaConfig) -> any
         }
     }
 }
+
+/// 启动 WebSocket 服务（Web 端接入），连接与 TCP 共用 SocketManager 及全部处理逻辑
+pub async fn start_ws_server(listener: TcpListener) -> anyhow::Result<()> {
+    log::warn!("✅ WebSocket 服务器已启动，开始监听连接...");
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                log::warn!("🌐 新 WebSocket 连接建立 [{}]", addr);
+
+                tokio::spawn(async move {
+                    if let Err(e) = serve_ws(stream).await {
+                        log::error!("❌ WebSocket 连接处理失败 [{}]: {:?}", addr, e);
+                    } else {
+                        log::info!("🔌 WebSocket 连接处理完成 [{}]", addr);
+                    }
+                });
+            }
+            Err(e) => {
+                log::error!("❌ WebSocket 连接接收失败: {:?}", e);
+            }
+        }
+    }
+}
+
+/// 处理 TCP 连接，启用 TLS 时先完成握手
+async fn serve_tcp(stream: TcpStream) -> anyhow::Result<()> {
+    match SocketTls::get() {
+        Some(tls) => {
+            let (stream, client_cert) = tls.accept(stream).await?;
+            handle_connection(stream, client_cert).await
+        }
+        None => handle_connection(stream, None).await,
+    }
+}
+
+/// 处理 WebSocket 连接，启用 TLS 时即为 wss
+async fn serve_ws(stream: TcpStream) -> anyhow::Result<()> {
+    match SocketTls::get() {
+        Some(tls) => {
+            let (stream, client_cert) = tls.accept(stream).await?;
+            handle_ws_connection(stream, client_cert).await
+        }
+        None => handle_ws_connection(stream, None).await,
+    }
+}
+
+/// 节点优雅下线：通知仲裁服务，将本节点连接迁移到剩余节点并等待宽限期，最后清理在线登记
+pub async fn shutdown() {
+    if let Err(e) = ArbClientServiceImpl::graceful_leave().await {
+        log::error!("❌ 节点下线通知仲裁服务失败: {:?}", e);
+    }
+    match ArbClientServiceImpl::list_socket_nodes().await {
+        Ok((nodes, virtual_nodes)) => match SocketManager::dispatch_mislocated_connections(nodes, virtual_nodes).await {
+            Ok(count) => log::warn!("🔀 节点下线，迁移连接 {} 个", count),
+            Err(e) => log::error!("❌ 节点下线迁移连接失败: {:?}", e),
+        },
+        Err(e) => log::error!("❌ 节点下线获取 socket 节点列表失败: {:?}", e),
+    }
+
+    let manager = get_socket_manager();
+    let deadline = Instant::now() + MIGRATION_GRACE;
+    while !manager.connections.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    // 宽限期结束仍未断开（含无迁移目标）的连接直接关闭
+    for (conn_id, _) in manager.all_connections() {
+        manager.close(&conn_id);
+    }
+
+    match PresenceService::get().purge_node(&local_node_addr()).await {
+        Ok(count) => log::warn!("🧹 节点下线，已清理在线登记 {} 条", count),
+        Err(e) => log::error!("❌ 节点下线清理在线登记失败: {:?}", e),
+    }
+}
@@ -0,0 +1,318 @@
+use anyhow::{anyhow, Context, Result};
+use arc_swap::ArcSwap;
+use common::config::SocketTlsConfig;
+use once_cell::sync::OnceCell;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// 默认证书变更检查间隔（秒）
+const DEFAULT_RELOAD_INTERVAL_SECS: u64 = 30;
+/// TLS 握手超时
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 已校验的客户端证书身份：主题 CN 与 SAN 中的 DNS 名称
+///
+/// 握手时由 rustls 按 `client_ca_path` 校验证书链，这里只解析叶子证书取出身份，登录时据此绑定 uid。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientCertIdentity {
+    pub common_name: Option<String>,
+    pub dns_names: Vec<String>,
+}
+
+impl ClientCertIdentity {
+    /// 从 DER 编码的证书中解析身份，格式不符时返回 None
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let mut buf = der;
+        let (0x30, mut cert) = read_tlv(&mut buf)? else {
+            return None;
+        };
+        let (0x30, mut tbs) = read_tlv(&mut cert)? else {
+            return None;
+        };
+        let mut fields = Vec::new();
+        while !tbs.is_empty() {
+            fields.push(read_tlv(&mut tbs)?);
+        }
+        // [0] 版本号可省略；其后依次为序列号、签名算法、颁发者、有效期、主题
+        let start = usize::from(fields.first()?.0 == 0xa0);
+        let (0x30, subject) = *fields.get(start + 4)? else {
+            return None;
+        };
+        let dns_names = match fields.iter().find(|(tag, _)| *tag == 0xa3) {
+            Some((_, extensions)) => san_dns_names(extensions)?,
+            None => Vec::new(),
+        };
+        Some(Self {
+            common_name: common_name(subject)?,
+            dns_names,
+        })
+    }
+
+    /// 证书是否签发给 `uid`：CN 或任一 SAN DNS 名称与之相同
+    pub fn matches(&self, uid: &str) -> bool {
+        self.common_name.as_deref() == Some(uid) || self.dns_names.iter().any(|name| name == uid)
+    }
+}
+
+/// Socket 网关 TLS 终结：TCP 与 WebSocket 监听共用
+///
+/// 证书文件变更后重新加载并原子替换 `ServerConfig`，只影响之后的新握手，已建立的连接不受影响。
+pub struct SocketTls {
+    cfg: SocketTlsConfig,
+    server_config: ArcSwap<ServerConfig>,
+    /// 上次加载时各证书文件的最新修改时间
+    loaded_at: Mutex<Option<SystemTime>>,
+}
+
+impl SocketTls {
+    fn new(cfg: SocketTlsConfig) -> Result<Self> {
+        let loaded_at = latest_modified(&cfg);
+        let server_config = build_server_config(&cfg)?;
+        Ok(Self {
+            cfg,
+            server_config: ArcSwap::from_pointee(server_config),
+            loaded_at: Mutex::new(loaded_at),
+        })
+    }
+
+    /// 完成 TLS 握手，返回连接及客户端证书身份（未携带证书时为 None）
+    pub async fn accept(&self, stream: TcpStream) -> Result<(TlsStream<TcpStream>, Option<ClientCertIdentity>)> {
+        let acceptor = TlsAcceptor::from(self.server_config.load_full());
+        let tls_stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await.map_err(|_| anyhow!("TLS 握手超时"))??;
+        let identity = match tls_stream.get_ref().1.peer_certificates().and_then(|certs| certs.first()) {
+            Some(cert) => Some(ClientCertIdentity::from_der(cert).ok_or_else(|| anyhow!("无法解析客户端证书"))?),
+            None => None,
+        };
+        Ok((tls_stream, identity))
+    }
+
+    /// 证书文件有变更时重新加载，失败则继续使用旧证书
+    fn reload_if_changed(&self) {
+        let modified = latest_modified(&self.cfg);
+        {
+            let loaded_at = self.loaded_at.lock().unwrap();
+            if modified.is_none() || modified == *loaded_at {
+                return;
+            }
+        }
+        match build_server_config(&self.cfg) {
+            Ok(server_config) => {
+                self.server_config.store(Arc::new(server_config));
+                *self.loaded_at.lock().unwrap() = modified;
+                log::warn!("🔐 TLS 证书已重新加载: {}", self.cfg.cert_path);
+            }
+            Err(e) => log::error!("❌ TLS 证书重新加载失败，继续使用旧证书: {:?}", e),
+        }
+    }
+
+    fn start_reload_task(tls: Arc<Self>) {
+        let secs = tls.cfg.reload_interval_secs.unwrap_or(DEFAULT_RELOAD_INTERVAL_SECS).max(1);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(secs));
+            loop {
+                interval.tick().await;
+                tls.reload_if_changed();
+            }
+        });
+    }
+
+    /// 按配置初始化，未配置 TLS 时不启用
+    pub fn init(cfg: Option<SocketTlsConfig>) -> Result<()> {
+        let Some(cfg) = cfg else {
+            log::warn!("⚠️ Socket 未配置 TLS，使用明文连接");
+            return Ok(());
+        };
+        let tls = Arc::new(Self::new(cfg)?);
+        INSTANCE.set(tls.clone()).map_err(|_| anyhow!("INSTANCE already initialized"))?;
+        Self::start_reload_task(tls);
+        Ok(())
+    }
+
+    /// 获取 TLS 实例，未启用 TLS 时返回 None
+    pub fn get() -> Option<Arc<Self>> {
+        INSTANCE.get().cloned()
+    }
+}
+
+static INSTANCE: OnceCell<Arc<SocketTls>> = OnceCell::new();
+
+fn build_server_config(cfg: &SocketTlsConfig) -> Result<ServerConfig> {
+    let certs = load_certs(&cfg.cert_path)?;
+    let key = load_private_key(&cfg.key_path)?;
+    let builder = ServerConfig::builder();
+    let builder = match &cfg.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_path)? {
+                roots.add(cert).with_context(|| format!("无效的客户端 CA 证书: {}", ca_path))?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+            let verifier = if cfg.client_cert_required { verifier.build()? } else { verifier.allow_unauthenticated().build()? };
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    Ok(builder.with_single_cert(certs, key)?)
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("打开证书失败: {}", path))?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(anyhow!("证书文件为空: {}", path));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("打开私钥失败: {}", path))?);
+    rustls_pemfile::private_key(&mut reader)?.ok_or_else(|| anyhow!("私钥文件为空: {}", path))
+}
+
+/// 读取一个 DER TLV，返回 (标签, 内容)；只支持定长编码
+fn read_tlv<'a>(buf: &mut &'a [u8]) -> Option<(u8, &'a [u8])> {
+    let (&tag, rest) = buf.split_first()?;
+    let (&first, mut rest) = rest.split_first()?;
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let (bytes, tail) = rest.split_at_checked(count)?;
+        rest = tail;
+        bytes.iter().fold(0usize, |len, byte| (len << 8) | *byte as usize)
+    };
+    let (value, rest) = rest.split_at_checked(len)?;
+    *buf = rest;
+    Some((tag, value))
+}
+
+/// 主题中的 CN（OID 2.5.4.3）
+fn common_name(mut name: &[u8]) -> Option<Option<String>> {
+    while !name.is_empty() {
+        let (0x31, mut rdn) = read_tlv(&mut name)? else {
+            return None;
+        };
+        while !rdn.is_empty() {
+            let (0x30, mut attr) = read_tlv(&mut rdn)? else {
+                return None;
+            };
+            let (0x06, oid) = read_tlv(&mut attr)? else {
+                return None;
+            };
+            if oid == [0x55, 0x04, 0x03] {
+                let (_, value) = read_tlv(&mut attr)?;
+                return Some(Some(String::from_utf8(value.to_vec()).ok()?));
+            }
+        }
+    }
+    Some(None)
+}
+
+/// 扩展 `[3]` 中 SubjectAltName（OID 2.5.29.17）的 DNS 名称
+fn san_dns_names(mut extensions: &[u8]) -> Option<Vec<String>> {
+    let (0x30, mut list) = read_tlv(&mut extensions)? else {
+        return None;
+    };
+    while !list.is_empty() {
+        let (0x30, mut ext) = read_tlv(&mut list)? else {
+            return None;
+        };
+        let (0x06, oid) = read_tlv(&mut ext)? else {
+            return None;
+        };
+        if oid != [0x55, 0x1d, 0x11] {
+            continue;
+        }
+        // 可选的 critical 标记之后为 OCTET STRING 包装的 GeneralNames
+        let (mut tag, mut value) = read_tlv(&mut ext)?;
+        if tag == 0x01 {
+            (tag, value) = read_tlv(&mut ext)?;
+        }
+        if tag != 0x04 {
+            return None;
+        }
+        let (0x30, mut names) = read_tlv(&mut value)? else {
+            return None;
+        };
+        let mut dns_names = Vec::new();
+        while !names.is_empty() {
+            let (tag, name) = read_tlv(&mut names)?;
+            if tag == 0x82 {
+                dns_names.push(String::from_utf8(name.to_vec()).ok()?);
+            }
+        }
+        return Some(dns_names);
+    }
+    Some(Vec::new())
+}
+
+/// 证书、私钥与 CA 文件中最新的修改时间
+fn latest_modified(cfg: &SocketTlsConfig) -> Option<SystemTime> {
+    [Some(&cfg.cert_path), Some(&cfg.key_path), cfg.client_ca_path.as_ref()]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClientCertIdentity;
+
+    fn tlv(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let value = parts.concat();
+        let mut out = vec![tag];
+        if value.len() < 0x80 {
+            out.push(value.len() as u8);
+        } else {
+            out.extend_from_slice(&[0x82, (value.len() >> 8) as u8, value.len() as u8]);
+        }
+        out.extend_from_slice(&value);
+        out
+    }
+
+    fn name(cn: &str) -> Vec<u8> {
+        let attr = tlv(0x30, &[&tlv(0x06, &[&[0x55, 0x04, 0x03]]), &tlv(0x0c, &[cn.as_bytes()])]);
+        tlv(0x30, &[&tlv(0x31, &[&attr])])
+    }
+
+    #[test]
+    fn test_identity_from_cert() {
+        let general_names = tlv(0x30, &[&tlv(0x82, &[b"bot-1"]), &tlv(0x86, &[b"spiffe://im/bot-1"]), &tlv(0x82, &[b"bot-2"])]);
+        let san = tlv(0x30, &[&tlv(0x06, &[&[0x55, 0x1d, 0x11]]), &tlv(0x01, &[&[0xff]]), &tlv(0x04, &[&general_names])]);
+        let key_usage = tlv(0x30, &[&tlv(0x06, &[&[0x55, 0x1d, 0x0f]]), &tlv(0x04, &[&[0x03, 0x02, 0x05, 0xa0]])]);
+        let algorithm = tlv(0x30, &[&tlv(0x06, &[&[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02]])]);
+        let tbs = tlv(
+            0x30,
+            &[
+                &tlv(0xa0, &[&tlv(0x02, &[&[0x02]])]),
+                &tlv(0x02, &[&[0x01]]),
+                &algorithm,
+                &name("im-ca"),
+                &tlv(0x30, &[]),
+                &name("bot-1"),
+                &tlv(0x30, &[]),
+                &tlv(0xa3, &[&tlv(0x30, &[&key_usage, &san])]),
+            ],
+        );
+        let cert = tlv(0x30, &[&tbs, &algorithm, &tlv(0x03, &[&[0x00]])]);
+
+        let identity = ClientCertIdentity::from_der(&cert).unwrap();
+        assert_eq!(identity.common_name.as_deref(), Some("bot-1"));
+        assert_eq!(identity.dns_names, vec!["bot-1".to_string(), "bot-2".to_string()]);
+        assert!(identity.matches("bot-2"));
+        assert!(!identity.matches("im-ca"));
+        assert!(ClientCertIdentity::from_der(&cert[..cert.len() - 1]).is_none());
+    }
+}
@@ -0,0 +1,37 @@
+use crate::socket::socket_connection::serve_connection;
+use crate::socket::socket_manager::Transport;
+use crate::socket::socket_tls::ClientCertIdentity;
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{future, SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
+
+/// WebSocket 客户端连接处理入口
+///
+/// 每个二进制帧承载一条 `ByteMessageType` 前缀 + protobuf 消息，与 TCP 帧格式一致；
+/// Ping/Pong 由协议层自动应答，文本帧忽略，收到 Close 结束读循环。
+pub async fn handle_ws_connection<S>(stream: S, client_cert: Option<ClientCertIdentity>) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let ws_stream = tokio_tungstenite::accept_async(stream).await?;
+    let (sink, stream) = ws_stream.split();
+
+    let reader = stream
+        .take_while(|frame| future::ready(!matches!(frame, Ok(WsMessage::Close(_)))))
+        .filter_map(|frame| {
+            future::ready(match frame {
+                Ok(WsMessage::Binary(data)) => Some(Ok(data)),
+                Ok(WsMessage::Text(_)) => {
+                    log::warn!("⚠️ WebSocket 不支持文本帧，已忽略");
+                    None
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(anyhow::Error::from(e))),
+            })
+        });
+    let writer = sink.with(|bytes: Bytes| future::ready(Ok::<_, WsError>(WsMessage::Binary(bytes))));
+
+    serve_connection(Box::pin(reader), writer, Transport::Ws, client_cert).await
+}
@@ -1 +1,2 @@
 pub mod redis_const;
+pub mod shard_const;
@@ -0,0 +1,13 @@
+/// 调用方所见的分片纪元（请求元数据），写请求必须携带
+pub const SHARD_EPOCH_HEADER: &str = "x-shard-epoch";
+/// 群组当前归属节点（重定向时的响应元数据）
+pub const SHARD_OWNER_HEADER: &str = "x-shard-owner";
+
+/// 群组不归属被调用节点：FAILED_PRECONDITION，元数据携带归属节点与被调用节点的纪元
+pub const SHARD_MOVED: &str = "shard.moved";
+/// 被调用节点的纪元落后于调用方：UNAVAILABLE，稍后重试
+pub const SHARD_EPOCH_AHEAD: &str = "shard.epoch.ahead";
+/// 群组正在移交，暂停写入：UNAVAILABLE，稍后重试
+pub const SHARD_GROUP_MIGRATING: &str = "shard.group.migrating";
+/// 写请求未携带分片纪元，无法判断路由是否过期：INVALID_ARGUMENT，不重试
+pub const SHARD_EPOCH_MISSING: &str = "shard.epoch.missing";
@@ -1,3 +1,4 @@
+use crate::protocol::arb::arb_models::MemberRef;
 use common::index_trait::MongoIndexModelProvider;
 use mongo_macro::MongoIndexModelProvider as MongoDeriveMongoIndex;
 /// *
@@ -6,6 +7,7 @@ use mongo_macro::MongoIndexModelProvider as MongoDeriveMongoIndex;
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Debug)]
 #[mongo_index(name("idx_group_uid"),fields["group_id","uid"], unique)]
+#[mongo_index(name("idx_update_time"),fields["update_time"])]
 pub struct GroupMemberEntity {
     /// 成员记录ID（内部持久化用）
     pub id: ::prost::alloc::string::String,
@@ -19,6 +21,9 @@ pub struct GroupMemberEntity {
     pub role: i32,
     /// 是否禁言中（true=被禁言）
     pub is_muted: bool,
+    /// 禁言截止时间（毫秒），0 表示未设置截止时间（此时 `is_muted` 为永久禁言）
+    #[serde(default)]
+    pub mute_until: u64,
     /// 成员头像URL（前端展示用）
     pub avatar: ::prost::alloc::string::String,
     /// 加入时间
@@ -26,3 +31,27 @@ pub struct GroupMemberEntity {
     /// 更新时间
     pub update_time: u64,
 }
+
+impl GroupMemberEntity {
+    /// 禁言截止时间（毫秒）：永久禁言为 `u64::MAX`，未禁言为 0
+    pub fn muted_until(&self) -> u64 {
+        match (self.mute_until, self.is_muted) {
+            (0, true) => u64::MAX,
+            (0, false) => 0,
+            (until, _) => until,
+        }
+    }
+}
+
+/// 分片节点内存中的成员记录
+impl From<GroupMemberEntity> for MemberRef {
+    fn from(entity: GroupMemberEntity) -> Self {
+        Self {
+            mute_until: entity.muted_until(),
+            id: entity.uid,
+            role: entity.role,
+            alias: entity.alias,
+            join_time: entity.create_time,
+        }
+    }
+}
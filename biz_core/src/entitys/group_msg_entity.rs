@@ -0,0 +1,61 @@
+use crate::protocol::msg::auth::DeviceType;
+use crate::protocol::msg::message::Segment;
+
+/// ======================================
+/// 👥 群组消息结构（群聊消息）
+/// ======================================
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GroupMsgEntity {
+    /// 消息唯一 ID
+    #[prost(uint64, tag = "1")]
+    pub message_id: u64,
+    /// 发送者用户ID
+    #[prost(string, tag = "2")]
+    pub from: ::prost::alloc::string::String,
+    /// 群组ID
+    #[prost(string, tag = "3")]
+    pub to: ::prost::alloc::string::String,
+    /// 消息内容
+    #[prost(message, repeated, tag = "4")]
+    pub content: ::prost::alloc::vec::Vec<Segment>,
+    /// 群内消息序号
+    #[prost(int64, tag = "5")]
+    pub seq: i64,
+    /// 是否撤回
+    #[prost(bool, tag = "6")]
+    pub revoked: bool,
+    /// 是否系统消息
+    #[prost(bool, tag = "7")]
+    pub is_system: bool,
+    /// 是否同步到 MQ
+    #[prost(bool, tag = "8")]
+    pub sync_mq_status: bool,
+    /// 创建时间
+    #[prost(int64, tag = "99")]
+    pub create_time: i64,
+    /// 更新时间
+    #[prost(int64, tag = "100")]
+    pub update_time: i64,
+}
+
+/// 群消息在某个 socket 节点上的接收者及其在线设备
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GroupMsgRecipient {
+    /// 接收者用户ID
+    #[prost(string, tag = "1")]
+    pub uid: ::prost::alloc::string::String,
+    /// 在线登记中该节点上的设备类型
+    #[prost(enumeration = "DeviceType", repeated, tag = "2")]
+    pub device_types: ::prost::alloc::vec::Vec<i32>,
+}
+
+/// 群消息的 Kafka 投递记录：按 socket 节点拆分，携带该节点上的接收者，节点无需再查询群成员
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GroupMsgDelivery {
+    #[prost(message, optional, tag = "1")]
+    pub message: ::core::option::Option<GroupMsgEntity>,
+    #[prost(message, repeated, tag = "2")]
+    pub recipients: ::prost::alloc::vec::Vec<GroupMsgRecipient>,
+}
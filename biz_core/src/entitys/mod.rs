@@ -5,12 +5,14 @@ pub mod friend;
 pub mod group_entity;
 pub mod group_join_req_entity;
 pub mod group_member_entity;
+pub mod group_msg_entity;
 mod kafka_msg_entity;
 pub mod mail_entity;
 pub mod read_index;
 pub mod role_entity;
 pub mod tag_info_entity;
 pub mod user_entity;
+pub mod user_msg_entity;
 pub mod user_role_entity;
 
 use mongodb::bson::oid::ObjectId;
@@ -1,5 +1,8 @@
 use crate::protocol::common::ChatTargetType;
+use serde::{Deserialize, Serialize};
 
+/// 会话已读位置（按会话序号）
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReadIndexEntity {
     pub from: String,
     pub to: String, // 单聊为 user_id，群聊为 group_id
@@ -158,6 +158,17 @@ impl KafkaInstanceService {
         }
     }
 
+    /// 按 Kafka 配置初始化全局单例（单聊 / 群聊 topic）
+    pub async fn init_instance(kafka_cfg: &KafkaConfig) -> Result<()> {
+        let topic_list = vec![
+            TopicInfo::new(kafka_cfg.topic_single.clone(), 10, 1),
+            TopicInfo::new(kafka_cfg.topic_group.clone(), 10, 1),
+        ];
+        let instance = Self::new(&kafka_cfg.brokers, &topic_list).await?;
+        SERVICE.set(Arc::new(instance)).map_err(|_| anyhow!("KafkaService already initialized"))?;
+        Ok(())
+    }
+
     /// 获取单例
     pub fn get() -> Arc<Self> {
         SERVICE.get().expect("KafkaService is not initialized").clone()
@@ -68,6 +68,8 @@ pub trait UserManagerOpt: Send + Sync {
     async fn is_friend(&self, user_id: &UserId, friend_id: &UserId) -> Result<bool>;
     /// 获取用户的好友列表
     async fn get_friends(&self, user_id: &UserId) -> Result<Vec<UserId>>;
+    /// 检查 user_id 是否已拉黑 friend_id
+    async fn is_blocked(&self, user_id: &UserId, friend_id: &UserId) -> Result<bool>;
     /// 拉黑好友
     async fn friend_block(&self, user_id: &UserId, friend_id: &UserId) -> Result<()>;
     /// 拉黑好友-取消
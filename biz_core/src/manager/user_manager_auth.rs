@@ -71,6 +71,8 @@ pub trait UserManagerAuthOpt: Send + Sync {
         password: &str,
         device_type: &DeviceType,
     ) -> anyhow::Result<(String, ClientEntity)>;
+    /// 使用已签发的 token 恢复登录（断线重连免密），返回原 token 与用户信息
+    async fn login_by_token(&self, token: &str, device_type: &DeviceType) -> anyhow::Result<(String, ClientEntity)>;
 
     async fn logout(&self, message_id: &u64, user_id: &UserId, device_type: &DeviceType) -> anyhow::Result<()>;
     /// 注册新用户
@@ -64,6 +64,9 @@ impl UserManagerAuthOpt for UserManagerAuth {
         password: &str,
         device_type: &DeviceType,
     ) -> anyhow::Result<(String, ClientEntity)> {
+        if auth_type == &AuthType::Token {
+            return self.login_by_token(auth_content, device_type).await;
+        }
         let client_service = ClientService::get();
         let mut client: Option<ClientEntity> = Option::None;
         if auth_type == &AuthType::Phone {
@@ -89,6 +92,20 @@ impl UserManagerAuthOpt for UserManagerAuth {
         Ok((token, entity))
     }
 
+    async fn login_by_token(&self, token: &str, device_type: &DeviceType) -> anyhow::Result<(String, ClientEntity)> {
+        let user_manager = UserManager::get();
+        if token.is_empty() || !user_manager.verify_token(token).await? {
+            return Err(anyhow!("token.invalid"));
+        }
+        let dto = user_manager.get_client_token(token).await?;
+        // token 与签发时的设备绑定，不允许跨设备复用
+        if dto.device_type != *device_type as u8 {
+            return Err(anyhow!("token.device.mismatch"));
+        }
+        let client = user_manager.get_user_info(&dto.uid).await?.ok_or_else(|| anyhow!("token.user.not.found"))?;
+        Ok((token.to_string(), client))
+    }
+
     async fn logout(
         &self,
         message_id: &u64,
@@ -1,5 +1,6 @@
 use crate::service::client_service::ClientService;
 use crate::service::friend_service::UserFriendService;
+use crate::service::session_service::SessionService;
 use crate::manager::user_manager::{UserManager, UserManagerOpt, USER_ONLINE_TTL_SECS};
 
 use crate::entitys::client_entity::ClientEntity;
@@ -11,7 +12,7 @@ use crate::protocol::msg::friend::{
 };
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
-use common::config::AppConfig;
+use common::config::{AppConfig, DeviceLoginPolicy};
 use common::errors::AppError;
 use common::repository_util::Repository;
 use common::util::common_utils::{build_snow_id, build_uuid};
@@ -181,9 +182,11 @@ impl UserManagerOpt for UserManager {
 
     /// 构建 Token：写入主数据、索引、集合（反向查找用）
     async fn build_token(&self, uid: &UserId, device_type: &DeviceType) -> Result<String> {
-        // 清除原有设备类型的 token
-        if let Some(existing_token) = self.get_token_by_uid_device(uid, device_type).await? {
-            self.delete_token(&existing_token).await?;
+        // 清除原有设备类型的 token（多开或先登录优先时保留，由会话登记决定去留）
+        if SessionService::policy(device_type) == DeviceLoginPolicy::NewestWins {
+            if let Some(existing_token) = self.get_token_by_uid_device(uid, device_type).await? {
+                self.delete_token(&existing_token).await?;
+            }
         }
         let token_key = build_uuid();
         let token_data_key = format!("token:{}", token_key);
@@ -218,7 +221,11 @@ impl UserManagerOpt for UserManager {
                 let index_key = format!("token:index:{}:{}", dto.uid, dto.device_type);
                 let token_set_key = format!("token:uid:{}", dto.uid);
 
-                let _: () = conn.del(index_key).await?;
+                // 单设备索引可能已指向该设备更新的 token，仅在仍指向自身时删除
+                let indexed: Option<String> = conn.get(&index_key).await?;
+                if indexed.as_deref() == Some(token) {
+                    let _: () = conn.del(index_key).await?;
+                }
                 let _: () = conn.srem(token_set_key, token).await?;
             }
         }
@@ -332,6 +339,20 @@ impl UserManagerOpt for UserManager {
         Ok(mongo_friends)
     }
 
+    async fn is_blocked(&self, user_id: &UserId, friend_id: &UserId) -> Result<bool> {
+        // 1. Redis 黑名单集合
+        let redis_key = format!("block:user:{}", user_id);
+        let mut conn = self.pool.get().await?;
+        let exists: bool = conn.sismember(&redis_key, friend_id).await.context("Redis SISMEMBER 查询失败")?;
+        if exists {
+            return Ok(true);
+        }
+
+        // 2. MongoDB 兜底
+        let friend = UserFriendService::get().get_friend_detail(user_id, friend_id).await?;
+        Ok(friend.map(|f| f.is_blocked).unwrap_or(false))
+    }
+
     async fn friend_block(&self, user_id: &UserId, friend_id: &UserId) -> Result<()> {
         let friend_service = UserFriendService::get();
 
@@ -6,7 +6,7 @@ pub struct UpdateVersionReq {
     /// 所属节点地址
     #[prost(string, tag = "1")]
     pub node_addr: ::prost::alloc::string::String,
-    /// 当前版本号（用于 CAS 检查）
+    /// 群分片归属纪元（CAS：只接受比本地更大的纪元）
     #[prost(uint64, tag = "2")]
     pub version: u64,
     /// 当前状态
@@ -17,6 +17,54 @@ pub struct UpdateVersionReq {
     pub last_update_time: u64,
     #[prost(int32, tag = "6")]
     pub total: i32,
+    /// 一致性哈希环每个节点的虚拟节点数（以仲裁配置为准）
+    #[prost(uint32, tag = "7")]
+    pub virtual_nodes: u32,
+}
+/// 分片迁移：群组数据分块（同一群组的分块连续发送）
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SyncGroupChunk {
+    /// 再均衡纪元
+    #[prost(uint64, tag = "1")]
+    pub epoch: u64,
+    /// 源节点地址
+    #[prost(string, tag = "2")]
+    pub source_addr: ::prost::alloc::string::String,
+    /// 群组ID
+    #[prost(string, tag = "3")]
+    pub group_id: ::prost::alloc::string::String,
+    /// 本块成员
+    #[prost(message, repeated, tag = "4")]
+    pub members: ::prost::alloc::vec::Vec<super::arb_models::MemberRef>,
+    /// 本块在线成员
+    #[prost(string, repeated, tag = "5")]
+    pub on_line_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// 是否为该群组最后一块
+    #[prost(bool, tag = "6")]
+    pub last: bool,
+    /// 整组成员及在线成员校验和（仅最后一块）
+    #[prost(uint64, tag = "7")]
+    pub checksum: u64,
+    /// 整组成员数（仅最后一块）
+    #[prost(uint32, tag = "8")]
+    pub member_total: u32,
+}
+/// 分片迁移：一次流式传输的结果
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SyncStreamResp {
+    /// 续传位置：此前（含）的群组均已校验通过并写入，续传从其后开始
+    #[prost(string, tag = "1")]
+    pub resume_token: ::prost::alloc::string::String,
+    /// 校验通过并写入的群组数
+    #[prost(uint32, tag = "2")]
+    pub applied: u32,
+    /// 校验失败或不完整的群组，需要重传
+    #[prost(string, repeated, tag = "3")]
+    pub failed_group_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// Generated client implementations.
 pub mod arb_client_service_client {
@@ -193,6 +241,33 @@ pub mod arb_client_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        /// 流式同步数据（分片迁移批量移交）
+        pub async fn sync_data_stream(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::SyncGroupChunk>,
+        ) -> std::result::Result<tonic::Response<super::SyncStreamResp>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/protocol.arb_client.ArbClientService/syncDataStream",
+            );
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "protocol.arb_client.ArbClientService",
+                        "syncDataStream",
+                    ),
+                );
+            self.inner.client_streaming(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -232,6 +307,11 @@ pub mod arb_client_service_server {
             tonic::Response<super::super::super::common::CommonResp>,
             tonic::Status,
         >;
+        /// 流式同步数据（分片迁移批量移交）
+        async fn sync_data_stream(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::SyncGroupChunk>>,
+        ) -> std::result::Result<tonic::Response<super::SyncStreamResp>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct ArbClientServiceServer<T> {
@@ -443,6 +523,54 @@ pub mod arb_client_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/protocol.arb_client.ArbClientService/syncDataStream" => {
+                    #[allow(non_camel_case_types)]
+                    struct syncDataStreamSvc<T: ArbClientService>(pub Arc<T>);
+                    impl<
+                        T: ArbClientService,
+                    > tonic::server::ClientStreamingService<super::SyncGroupChunk>
+                    for syncDataStreamSvc<T> {
+                        type Response = super::SyncStreamResp;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                tonic::Streaming<super::SyncGroupChunk>,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ArbClientService>::sync_data_stream(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = syncDataStreamSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.client_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         let mut response = http::Response::new(
@@ -31,6 +31,12 @@ pub struct RegRequest {
     /// Kafka 地址（可选，用于消息队列）
     #[prost(string, optional, tag = "3")]
     pub kafka_addr: ::core::option::Option<::prost::alloc::string::String>,
+    /// 客户端接入地址（仅 socket 节点，用于连接迁移）
+    #[prost(string, optional, tag = "4")]
+    pub socket_addr: ::core::option::Option<::prost::alloc::string::String>,
+    /// WebSocket 接入地址（仅 socket 节点，Web 端连接迁移）
+    #[prost(string, optional, tag = "5")]
+    pub ws_addr: ::core::option::Option<::prost::alloc::string::String>,
 }
 /// =====================
 /// 通用结构体定义
@@ -72,6 +78,12 @@ pub struct NodeInfo {
     /// Kafka 地址（可选，用于消息传递）
     #[prost(string, optional, tag = "8")]
     pub kafka_addr: ::core::option::Option<::prost::alloc::string::String>,
+    /// 客户端接入地址（仅 socket 节点，用于连接迁移）
+    #[prost(string, optional, tag = "9")]
+    pub socket_addr: ::core::option::Option<::prost::alloc::string::String>,
+    /// WebSocket 接入地址（仅 socket 节点，Web 端连接迁移）
+    #[prost(string, optional, tag = "10")]
+    pub ws_addr: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -83,6 +95,15 @@ pub struct MemberRef {
     /// 成员角色
     #[prost(enumeration = "super::super::common::GroupRoleType", tag = "3")]
     pub role: i32,
+    /// 禁言截止时间（毫秒），0 表示未禁言
+    #[prost(uint64, tag = "4")]
+    pub mute_until: u64,
+    /// 群内别名
+    #[prost(string, tag = "5")]
+    pub alias: ::prost::alloc::string::String,
+    /// 加入时间（毫秒）
+    #[prost(uint64, tag = "6")]
+    pub join_time: u64,
 }
 #[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -123,6 +144,12 @@ pub struct ListShardNodesResponse {
 pub struct ListAllNodesResponse {
     #[prost(message, repeated, tag = "1")]
     pub nodes: ::prost::alloc::vec::Vec<NodeInfo>,
+    /// 群分片归属纪元（分片节点成员每变化一次加一）
+    #[prost(uint64, tag = "2")]
+    pub shard_epoch: u64,
+    /// 一致性哈希环每个节点的虚拟节点数（以仲裁配置为准，各方按此建环）
+    #[prost(uint32, tag = "3")]
+    pub virtual_nodes: u32,
 }
 /// =====================
 /// 枚举：分片节点状态定义
@@ -25,10 +25,17 @@ pub struct RemoveMemberReq {
 pub struct GetMemberPageReq {
     #[prost(string, tag = "1")]
     pub group_id: ::prost::alloc::string::String,
+    /// 跳过的成员数，仅在未携带 cursor 时生效（开销随 offset 增长，逐页翻页请用 cursor）
     #[prost(uint32, tag = "2")]
     pub offset: u32,
     #[prost(uint32, tag = "3")]
     pub limit: u32,
+    /// 上一页返回的 next_cursor，首页为空
+    #[prost(string, tag = "4")]
+    pub cursor: ::prost::alloc::string::String,
+    /// 与 cursor 所属的分页须一致
+    #[prost(enumeration = "MemberOrder", tag = "5")]
+    pub order: i32,
 }
 #[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -62,9 +69,56 @@ pub struct ChangeRoleReq {
 #[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MuteMemberReq {
+    #[prost(string, tag = "1")]
+    pub group_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub user_id: ::prost::alloc::string::String,
+    /// 禁言截止时间（毫秒），0 表示解除禁言
+    #[prost(uint64, tag = "3")]
+    pub mute_until: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SetAliasReq {
+    #[prost(string, tag = "1")]
+    pub group_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub user_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub alias: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetMembersByRoleReq {
+    #[prost(string, tag = "1")]
+    pub group_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "super::super::common::GroupRoleType", tag = "2")]
+    pub role: i32,
+    /// 只取在线成员
+    #[prost(bool, tag = "3")]
+    pub online_only: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CheckCanSpeakReq {
+    #[prost(string, tag = "1")]
+    pub group_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub user_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct MemberListResp {
     #[prost(message, repeated, tag = "1")]
     pub members: ::prost::alloc::vec::Vec<super::arb_models::MemberRef>,
+    /// 下一页游标（仅分页查询），为空表示没有更多
+    #[prost(string, tag = "2")]
+    pub next_cursor: ::prost::alloc::string::String,
 }
 #[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -88,6 +142,50 @@ pub struct GetGroupsResp {
     #[prost(string, repeated, tag = "1")]
     pub group_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CheckCanSpeakResp {
+    #[prost(bool, tag = "1")]
+    pub can_speak: bool,
+    /// 不能发言的原因：group.member.notfound / group.member.muted
+    #[prost(string, tag = "2")]
+    pub reason: ::prost::alloc::string::String,
+    /// 禁言截止时间（毫秒）
+    #[prost(uint64, tag = "3")]
+    pub mute_until: u64,
+}
+/// 成员分页排序方式
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MemberOrder {
+    /// 按用户 ID
+    ByUid = 0,
+    /// 按角色（群主、管理员、普通成员），同角色按入群时间
+    ByRole = 1,
+}
+impl MemberOrder {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::ByUid => "BY_UID",
+            Self::ByRole => "BY_ROLE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "BY_UID" => Some(Self::ByUid),
+            "BY_ROLE" => Some(Self::ByRole),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod shard_rpc_service_client {
     #![allow(
@@ -443,7 +541,7 @@ pub mod shard_rpc_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
-        /// 获取在线管理员（异步）
+        /// 获取在线管理员（含群主）
         pub async fn get_admin_member(
             &mut self,
             request: impl tonic::IntoRequest<super::super::super::common::IdReq>,
@@ -497,6 +595,114 @@ pub mod shard_rpc_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        /// 设置或解除成员禁言
+        pub async fn mute_member(
+            &mut self,
+            request: impl tonic::IntoRequest<super::MuteMemberReq>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/protocol.shard_service.ShardRpcService/MuteMember",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "protocol.shard_service.ShardRpcService",
+                        "MuteMember",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// 设置成员群内别名
+        pub async fn set_alias(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetAliasReq>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/protocol.shard_service.ShardRpcService/SetAlias",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "protocol.shard_service.ShardRpcService",
+                        "SetAlias",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// 成员能否在群内发言（仅查内存：是否成员、是否禁言中）
+        pub async fn check_can_speak(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CheckCanSpeakReq>,
+        ) -> std::result::Result<tonic::Response<super::CheckCanSpeakResp>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/protocol.shard_service.ShardRpcService/CheckCanSpeak",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "protocol.shard_service.ShardRpcService",
+                        "CheckCanSpeak",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// 按角色获取成员（按入群时间排序），如群主、管理员、在线管理员
+        pub async fn get_members_by_role(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetMembersByRoleReq>,
+        ) -> std::result::Result<tonic::Response<super::MemberListResp>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/protocol.shard_service.ShardRpcService/GetMembersByRole",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "protocol.shard_service.ShardRpcService",
+                        "GetMembersByRole",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -562,7 +768,7 @@ pub mod shard_rpc_service_server {
             &self,
             request: tonic::Request<super::ChangeRoleReq>,
         ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
-        /// 获取在线管理员（异步）
+        /// 获取在线管理员（含群主）
         async fn get_admin_member(
             &self,
             request: tonic::Request<super::super::super::common::IdReq>,
@@ -572,6 +778,26 @@ pub mod shard_rpc_service_server {
             &self,
             request: tonic::Request<super::super::super::common::IdReq>,
         ) -> std::result::Result<tonic::Response<super::GetGroupsResp>, tonic::Status>;
+        /// 设置或解除成员禁言
+        async fn mute_member(
+            &self,
+            request: tonic::Request<super::MuteMemberReq>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
+        /// 设置成员群内别名
+        async fn set_alias(
+            &self,
+            request: tonic::Request<super::SetAliasReq>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
+        /// 成员能否在群内发言（仅查内存：是否成员、是否禁言中）
+        async fn check_can_speak(
+            &self,
+            request: tonic::Request<super::CheckCanSpeakReq>,
+        ) -> std::result::Result<tonic::Response<super::CheckCanSpeakResp>, tonic::Status>;
+        /// 按角色获取成员（按入群时间排序），如群主、管理员、在线管理员
+        async fn get_members_by_role(
+            &self,
+            request: tonic::Request<super::GetMembersByRoleReq>,
+        ) -> std::result::Result<tonic::Response<super::MemberListResp>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct ShardRpcServiceServer<T> {
@@ -1193,6 +1419,186 @@ pub mod shard_rpc_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/protocol.shard_service.ShardRpcService/MuteMember" => {
+                    #[allow(non_camel_case_types)]
+                    struct MuteMemberSvc<T: ShardRpcService>(pub Arc<T>);
+                    impl<
+                        T: ShardRpcService,
+                    > tonic::server::UnaryService<super::MuteMemberReq>
+                    for MuteMemberSvc<T> {
+                        type Response = ();
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::MuteMemberReq>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ShardRpcService>::mute_member(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = MuteMemberSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/protocol.shard_service.ShardRpcService/SetAlias" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetAliasSvc<T: ShardRpcService>(pub Arc<T>);
+                    impl<
+                        T: ShardRpcService,
+                    > tonic::server::UnaryService<super::SetAliasReq>
+                    for SetAliasSvc<T> {
+                        type Response = ();
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetAliasReq>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ShardRpcService>::set_alias(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SetAliasSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/protocol.shard_service.ShardRpcService/CheckCanSpeak" => {
+                    #[allow(non_camel_case_types)]
+                    struct CheckCanSpeakSvc<T: ShardRpcService>(pub Arc<T>);
+                    impl<
+                        T: ShardRpcService,
+                    > tonic::server::UnaryService<super::CheckCanSpeakReq>
+                    for CheckCanSpeakSvc<T> {
+                        type Response = super::CheckCanSpeakResp;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CheckCanSpeakReq>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ShardRpcService>::check_can_speak(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CheckCanSpeakSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/protocol.shard_service.ShardRpcService/GetMembersByRole" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetMembersByRoleSvc<T: ShardRpcService>(pub Arc<T>);
+                    impl<
+                        T: ShardRpcService,
+                    > tonic::server::UnaryService<super::GetMembersByRoleReq>
+                    for GetMembersByRoleSvc<T> {
+                        type Response = super::MemberListResp;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetMembersByRoleReq>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ShardRpcService>::get_members_by_role(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetMembersByRoleSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         let mut response = http::Response::new(
@@ -13,6 +13,26 @@ pub struct CommonResp {
     pub message: ::prost::alloc::string::String,
 }
 /// *
+/// 聊天消息发送结果
+/// 消息节点校验通过后分配消息 ID 与序号，回传给 socket 节点用于发送回执
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SendMessageResp {
+    /// 是否成功
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+    /// 错误或提示信息
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+    /// 服务端分配的消息 ID
+    #[prost(uint64, tag = "3")]
+    pub message_id: u64,
+    /// 服务端分配的会话 / 群内序号
+    #[prost(int64, tag = "4")]
+    pub seq: i64,
+}
+/// *
 /// 群标签信息
 #[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
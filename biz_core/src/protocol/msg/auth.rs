@@ -220,6 +220,8 @@ pub enum AuthType {
     Unknown = 0,
     Email = 1,
     Phone = 2,
+    /// 使用已签发的 token 恢复登录（auth_content 为 token）
+    Token = 3,
 }
 impl AuthType {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -231,6 +233,7 @@ impl AuthType {
             Self::Unknown => "AUTH_TYPE_UNKNOWN",
             Self::Email => "AUTH_TYPE_EMAIL",
             Self::Phone => "AUTH_TYPE_PHONE",
+            Self::Token => "AUTH_TYPE_TOKEN",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -239,6 +242,7 @@ impl AuthType {
             "AUTH_TYPE_UNKNOWN" => Some(Self::Unknown),
             "AUTH_TYPE_EMAIL" => Some(Self::Email),
             "AUTH_TYPE_PHONE" => Some(Self::Phone),
+            "AUTH_TYPE_TOKEN" => Some(Self::Token),
             _ => None,
         }
     }
@@ -327,7 +327,7 @@ pub mod friend_rpc_service_client {
             &mut self,
             request: impl tonic::IntoRequest<super::SendMessageRespMsg>,
         ) -> std::result::Result<
-            tonic::Response<super::super::super::common::CommonResp>,
+            tonic::Response<super::super::super::common::SendMessageResp>,
             tonic::Status,
         > {
             self.inner
@@ -434,7 +434,7 @@ pub mod friend_rpc_service_server {
             &self,
             request: tonic::Request<super::SendMessageRespMsg>,
         ) -> std::result::Result<
-            tonic::Response<super::super::super::common::CommonResp>,
+            tonic::Response<super::super::super::common::SendMessageResp>,
             tonic::Status,
         >;
         /// 修改好友信息（备注名、别名）
@@ -713,7 +713,7 @@ pub mod friend_rpc_service_server {
                         T: FriendRpcService,
                     > tonic::server::UnaryService<super::SendMessageRespMsg>
                     for SendMessageSvc<T> {
-                        type Response = super::super::super::common::CommonResp;
+                        type Response = super::super::super::common::SendMessageResp;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
@@ -39,6 +39,9 @@ pub struct DestroyGroupMsg {
     /// 操作者 ID（必须为群主）
     #[prost(string, tag = "3")]
     pub operator_id: ::prost::alloc::string::String,
+    /// 解散时的成员列表（成员记录随解散删除，据此推送通知）
+    #[prost(string, repeated, tag = "4")]
+    pub uids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// *
 /// 修改群组信息（可用于名称、头像、公告等更新）
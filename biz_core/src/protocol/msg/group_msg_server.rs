@@ -462,7 +462,7 @@ pub mod group_rpc_service_client {
             &mut self,
             request: impl tonic::IntoRequest<super::GroupMessageReq>,
         ) -> std::result::Result<
-            tonic::Response<super::super::super::common::CommonResp>,
+            tonic::Response<super::super::super::common::SendMessageResp>,
             tonic::Status,
         > {
             self.inner
@@ -563,7 +563,7 @@ pub mod group_rpc_service_server {
             &self,
             request: tonic::Request<super::GroupMessageReq>,
         ) -> std::result::Result<
-            tonic::Response<super::super::super::common::CommonResp>,
+            tonic::Response<super::super::super::common::SendMessageResp>,
             tonic::Status,
         >;
     }
@@ -969,7 +969,7 @@ pub mod group_rpc_service_server {
                         T: GroupRpcService,
                     > tonic::server::UnaryService<super::GroupMessageReq>
                     for SendGroupMessageSvc<T> {
-                        type Response = super::super::super::common::CommonResp;
+                        type Response = super::super::super::common::SendMessageResp;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
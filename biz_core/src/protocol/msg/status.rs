@@ -68,6 +68,12 @@ pub struct AckMsg {
     /// 错误码
     #[prost(int32, tag = "5")]
     pub error_code: i32,
+    /// 服务端分配的消息 ID（聊天消息发送回执）
+    #[prost(uint64, tag = "6")]
+    pub server_message_id: u64,
+    /// 服务端分配的会话序号（聊天消息发送回执）
+    #[prost(int64, tag = "7")]
+    pub seq: i64,
 }
 /// =======================================
 /// 🔗 重连消息（ReConnectMsg）
@@ -82,4 +88,22 @@ pub struct ReConnectMsg {
     /// 重连的 Socket 地址
     #[prost(string, tag = "2")]
     pub socket_addr: ::prost::alloc::string::String,
+    /// 客户端发起：各会话已连续收到的最大序号，补发跳过不超过该序号的消息
+    #[prost(message, repeated, tag = "3")]
+    pub received: ::prost::alloc::vec::Vec<ConversationSeq>,
+}
+/// 会话序号游标：客户端在该会话中已连续收到（无缺口）的最大序号
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConversationSeq {
+    /// 会话类型
+    #[prost(enumeration = "super::super::common::ChatTargetType", tag = "1")]
+    pub target_type: i32,
+    /// 单聊为对方用户ID，群聊为群组ID
+    #[prost(string, tag = "2")]
+    pub target_id: ::prost::alloc::string::String,
+    /// 已连续收到的最大序号
+    #[prost(int64, tag = "3")]
+    pub seq: i64,
 }
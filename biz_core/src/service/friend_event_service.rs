@@ -87,7 +87,7 @@ impl FriendEventService {
 
             friend_event_coll
                 .update_one(
-                    doc! { "_id": event_id },
+                    doc! { "_id": ObjectId::parse_str(event_id)? },
                     doc! {
                             "$set": {
                                 "event_type": FriendEventType::FriendAccept as i32 ,
@@ -115,6 +115,40 @@ impl FriendEventService {
         Ok(())
     }
 
+    /// 查询待处理的好友申请，返回申请事件ID
+    pub async fn find_pending_request(&self, from_uid: &UserId, to_uid: &UserId) -> Result<Option<String>> {
+        let filter = doc! {
+            "from_uid": from_uid,
+            "to_uid": to_uid,
+            "event_type": FriendEventType::FriendRequest as i32,
+            "status": EventStatus::Pending as i32,
+        };
+        let doc = self.dao.collection.find_one(filter).await?;
+        Ok(doc.and_then(|d| d.get_object_id("_id").ok().map(|id| id.to_hex())))
+    }
+
+    /// 拒绝好友申请
+    pub async fn reject_friend(&self, event_id: &str, uid: &UserId, reason: &str) -> Result<()> {
+        let info = self.dao.find_by_id(event_id).await?;
+        let Some(event) = info else {
+            return Err(anyhow::anyhow!("申请记录不存在"));
+        };
+        if event.to_uid != uid.clone() {
+            warn!("事件错误: event_id:{} to_uid:{}", event_id, uid);
+            return Err(anyhow::anyhow!("事件错误，非当前用户的好友申请"));
+        }
+        let update = doc! {
+            "$set": {
+                "event_type": FriendEventType::FriendReject as i32,
+                "status": EventStatus::Done as i32,
+                "message": reason,
+                "updated_at": now() as i64,
+            }
+        };
+        self.dao.update(doc! { "_id": ObjectId::parse_str(event_id)? }, update).await?;
+        Ok(())
+    }
+
     /// 删除好友关系
     pub async fn remove_friend(&self, uid: &UserId, friend_id: &UserId) -> Result<()> {
         let database = self.db.clone();
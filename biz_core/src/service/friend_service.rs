@@ -86,6 +86,26 @@ impl UserFriendService {
         return Ok(result);
     }
 
+    /// 修改好友别名 / 备注（仅影响当前用户视角）
+    pub async fn change_friend_info(&self, uid: &UserId, friend_id: &UserId, nickname: Option<&str>, remark: Option<&str>) -> Result<()> {
+        let mut set = doc! {};
+        if let Some(nickname) = nickname {
+            set.insert("nickname", nickname);
+        }
+        if let Some(remark) = remark {
+            set.insert("remark", remark);
+        }
+        if set.is_empty() {
+            return Ok(());
+        }
+        let filter = doc! {
+            "uid": uid,
+            "friend_id": friend_id
+        };
+        self.dao.update(filter, doc! { "$set": set }).await?;
+        Ok(())
+    }
+
     /// 批量删除某用户相关记录（如注销）
     pub async fn delete_all_for_user(&self, uid: &UserId) -> Result<()> {
         let filter = doc! {
@@ -74,6 +74,7 @@ impl GroupService {
                 uid: group.owner_id.clone(),
                 role: GroupRoleType::Owner as i32,
                 is_muted: false,
+                mute_until: 0,
                 alias: user.user_name.clone(),
                 create_time: now,
                 update_time: now,
@@ -92,6 +93,7 @@ impl GroupService {
                     uid: user_id.clone(),
                     role: GroupRoleType::Member as i32,
                     is_muted: false,
+                    mute_until: 0,
                     alias: "".to_string(),
                     create_time: now,
                     update_time: now,
@@ -217,6 +219,33 @@ impl GroupService {
 
         Ok(())
     }
+    /// 更新群资料（名称 / 头像 / 简介），为空的字段保持不变
+    pub async fn update_group_profile(
+        &self,
+        group_id: &str,
+        name: Option<&str>,
+        avatar: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<(), AppError> {
+        let mut set = doc! { "update_time": now() };
+        if let Some(name) = name {
+            set.insert("name", name);
+        }
+        if let Some(avatar) = avatar {
+            set.insert("avatar", avatar);
+        }
+        if let Some(description) = description {
+            set.insert("description", description);
+        }
+        let object_id = mongodb::bson::oid::ObjectId::parse_str(group_id).map_err(|_| AppError::BizError("group.id.invalid".into()))?;
+        self.dao.update(doc! { "_id": object_id }, doc! { "$set": set }).await?;
+
+        // 资料变更后删除缓存，下次读取时回源
+        let mut conn = RedisPoolTools::get().get().await?;
+        conn.del::<_, ()>(format!("group:info:{}", group_id)).await?;
+        tracing::info!("✅ 群资料已更新: group_id={}", group_id);
+        Ok(())
+    }
     /// 转让群组（变更 creator_id）
 
     pub async fn transfer_ownership(&self, group_id: &str, new_owner_id: &UserId) -> Result<()> {
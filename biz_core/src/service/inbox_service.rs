@@ -0,0 +1,126 @@
+use crate::protocol::common::ChatTargetType;
+use anyhow::Result;
+use common::redis::redis_pool::RedisPoolTools;
+use common::RedisPool;
+use deadpool_redis::redis::cmd;
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+
+/// 收件箱保留时长（秒），超过未上线的用户由历史消息接口补齐
+const INBOX_TTL_SECS: i64 = 7 * 24 * 3600;
+
+/// 收件箱条目：一条尚未投递确认的消息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboxEntry {
+    pub message_id: u64,
+    pub target_type: ChatTargetType,
+}
+
+impl InboxEntry {
+    pub fn single(message_id: u64) -> Self {
+        Self { message_id, target_type: ChatTargetType::Single }
+    }
+
+    pub fn group(message_id: u64) -> Self {
+        Self { message_id, target_type: ChatTargetType::Group }
+    }
+
+    /// 成员编码：定长 20 位消息 ID + 类型，字典序即雪花 ID 的时间序
+    pub fn to_member(&self) -> String {
+        let kind = match self.target_type {
+            ChatTargetType::Group => "g",
+            _ => "u",
+        };
+        format!("{:020}:{}", self.message_id, kind)
+    }
+
+    pub fn from_member(member: &str) -> Option<Self> {
+        let (id, kind) = member.split_once(':')?;
+        let message_id = id.parse::<u64>().ok()?;
+        match kind {
+            "g" => Some(Self::group(message_id)),
+            "u" => Some(Self::single(message_id)),
+            _ => None,
+        }
+    }
+}
+
+/// 离线收件箱服务
+///
+/// 每个用户一个 Redis 有序集合（`inbox:user:{uid}`），所有成员 score 相同，
+/// 按成员字典序（即消息 ID 顺序）排列；消息在客户端 ACK 后移除，
+/// 重连时按游标分页拉取，游标为上一页最后一个成员，翻页期间的增删不会导致跳读。
+#[derive(Debug)]
+pub struct InboxService {
+    pub pool: Arc<RedisPool>,
+}
+
+impl InboxService {
+    pub fn new(pool: Arc<RedisPool>) -> Self {
+        Self { pool }
+    }
+
+    fn inbox_key(uid: &str) -> String {
+        format!("inbox:user:{}", uid)
+    }
+
+    /// 写入单个用户收件箱
+    pub async fn push(&self, uid: &str, entry: &InboxEntry) -> Result<()> {
+        self.push_many(&[uid.to_string()], entry).await
+    }
+
+    /// 同一条消息写入多个用户收件箱（群聊扇出）
+    pub async fn push_many(&self, uids: &[String], entry: &InboxEntry) -> Result<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.pool.get().await?;
+        let member = entry.to_member();
+        let mut pipe = deadpool_redis::redis::pipe();
+        for uid in uids {
+            let key = Self::inbox_key(uid);
+            pipe.cmd("ZADD").arg(&key).arg(0).arg(&member).ignore();
+            pipe.cmd("EXPIRE").arg(&key).arg(INBOX_TTL_SECS).ignore();
+        }
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    /// 移除已确认投递的消息
+    pub async fn remove(&self, uid: &str, entry: &InboxEntry) -> Result<bool> {
+        let mut conn = self.pool.get().await?;
+        let count: i64 = cmd("ZREM").arg(Self::inbox_key(uid)).arg(entry.to_member()).query_async(&mut conn).await?;
+        Ok(count > 0)
+    }
+
+    /// 按游标分页读取，`after` 为上一页最后一条（不含），None 表示从头开始
+    pub async fn page(&self, uid: &str, after: Option<&InboxEntry>, limit: usize) -> Result<Vec<InboxEntry>> {
+        let mut conn = self.pool.get().await?;
+        let min = match after {
+            Some(entry) => format!("({}", entry.to_member()),
+            None => "-".to_string(),
+        };
+        let members: Vec<String> =
+            cmd("ZRANGEBYLEX").arg(Self::inbox_key(uid)).arg(min).arg("+").arg("LIMIT").arg(0).arg(limit).query_async(&mut conn).await?;
+        Ok(members.iter().filter_map(|m| InboxEntry::from_member(m)).collect())
+    }
+
+    /// 未投递消息数量
+    pub async fn count(&self, uid: &str) -> Result<u64> {
+        let mut conn = self.pool.get().await?;
+        let count: u64 = cmd("ZCARD").arg(Self::inbox_key(uid)).query_async(&mut conn).await?;
+        Ok(count)
+    }
+
+    pub fn init() {
+        let instance = Self::new(RedisPoolTools::get().clone());
+        INSTANCE.set(Arc::new(instance)).expect("INSTANCE already initialized");
+    }
+
+    /// 获取单例
+    pub fn get() -> Arc<Self> {
+        INSTANCE.get().expect("INSTANCE is not initialized").clone()
+    }
+}
+
+static INSTANCE: OnceCell<Arc<InboxService>> = OnceCell::new();
@@ -1,12 +1,19 @@
 pub mod client_service;
 pub mod country_service;
-mod friend_event_service;
+pub mod friend_event_service;
 pub mod friend_service;
 pub mod group_member_service;
 pub mod group_service;
+pub mod inbox_service;
 pub mod mail_service;
+pub mod offline_message_service;
+pub mod presence_service;
+pub mod read_index_service;
 pub mod role_service;
 pub mod rpc_server_client_service;
+pub mod seq_service;
+pub mod session_service;
+pub mod shard_client_service;
 pub mod user_role_service;
 pub mod user_service;
 
@@ -22,4 +29,10 @@ pub async fn init_service() {
     user_service::UserService::init(db.clone()).await;
     friend_service::UserFriendService::init(db.clone()).await;
     FriendEventService::init(db.clone()).await;
+    seq_service::SeqService::init(db.clone()).await;
+    read_index_service::ReadIndexService::init(db.clone()).await;
+    offline_message_service::OfflineMessageService::init(db.clone()).await;
+    inbox_service::InboxService::init();
+    session_service::SessionService::init();
+    presence_service::PresenceService::init();
 }
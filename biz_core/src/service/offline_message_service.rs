@@ -0,0 +1,70 @@
+use crate::entitys::group_msg_entity::GroupMsgEntity;
+use crate::entitys::user_msg_entity::UserMsgEntity;
+use anyhow::Result;
+use common::repository_util::{BaseRepository, Repository};
+use common::util::date_util::now;
+use mongodb::bson::doc;
+use mongodb::Database;
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+
+/// 离线消息服务
+///
+/// 按消息 ID 批量读取单聊 / 群聊消息原文，供重连补发使用，并在 ACK 后回写送达状态；
+/// 消息由 msg_friend / msg_group 节点写入。
+#[derive(Debug)]
+pub struct OfflineMessageService {
+    pub user_msg_dao: BaseRepository<UserMsgEntity>,
+    pub group_msg_dao: BaseRepository<GroupMsgEntity>,
+}
+
+impl OfflineMessageService {
+    pub async fn new(db: Database) -> Self {
+        Self {
+            user_msg_dao: BaseRepository::new(db.clone(), "mq_user_message").await,
+            group_msg_dao: BaseRepository::new(db, "mq_group_message").await,
+        }
+    }
+
+    /// 批量读取单聊消息（不保证顺序，由调用方按收件箱顺序重排）
+    pub async fn find_user_messages(&self, message_ids: &[u64]) -> Result<Vec<UserMsgEntity>> {
+        if message_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let ids: Vec<i64> = message_ids.iter().map(|id| *id as i64).collect();
+        self.user_msg_dao.query(doc! { "messageId": { "$in": ids } }).await
+    }
+
+    /// 批量读取群聊消息
+    pub async fn find_group_messages(&self, message_ids: &[u64]) -> Result<Vec<GroupMsgEntity>> {
+        if message_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let ids: Vec<i64> = message_ids.iter().map(|id| *id as i64).collect();
+        self.group_msg_dao.query(doc! { "messageId": { "$in": ids } }).await
+    }
+
+    /// 标记单聊消息已送达（幂等）
+    pub async fn mark_delivered(&self, message_id: u64) -> Result<bool> {
+        let count = self
+            .user_msg_dao
+            .update(
+                doc! { "messageId": message_id as i64, "delivered": false },
+                doc! { "$set": { "delivered": true, "updatedTime": now() } },
+            )
+            .await?;
+        Ok(count > 0)
+    }
+
+    pub async fn init(db: Database) {
+        let instance = Self::new(db).await;
+        INSTANCE.set(Arc::new(instance)).expect("INSTANCE already initialized");
+    }
+
+    /// 获取单例
+    pub fn get() -> Arc<Self> {
+        INSTANCE.get().expect("INSTANCE is not initialized").clone()
+    }
+}
+
+static INSTANCE: OnceCell<Arc<OfflineMessageService>> = OnceCell::new();
@@ -0,0 +1,188 @@
+use crate::protocol::msg::auth::DeviceType;
+use anyhow::Result;
+use common::redis::redis_pool::RedisPoolTools;
+use common::util::date_util::now;
+use common::RedisPool;
+use deadpool_redis::redis::cmd;
+use once_cell::sync::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// 在线登记有效期（毫秒），socket 节点需在此时间内续期，否则视为离线
+pub const PRESENCE_TTL_MS: i64 = 90_000;
+
+/// socket 节点专属的投递 topic：`{base}.{node_addr}`，地址中 Kafka 不允许的字符替换为 `_`
+///
+/// 聊天消息按在线登记只投递到接收者所在节点的 topic，不在线的接收者由收件箱在重连时补发。
+pub fn node_topic(base: &str, node_addr: &str) -> String {
+    let node: String = node_addr.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+    format!("{}.{}", base, node)
+}
+
+/// 在线登记：用户的某个连接位于哪个 socket 节点
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PresenceEntry {
+    pub node_addr: String,
+    pub conn_id: String,
+    pub device_type: DeviceType,
+}
+
+impl PresenceEntry {
+    /// 成员编码：`{node_addr}|{conn_id}|{device_type}`
+    pub fn to_member(&self) -> String {
+        format!("{}|{}|{}", self.node_addr, self.conn_id, self.device_type as i32)
+    }
+
+    pub fn from_member(member: &str) -> Option<Self> {
+        let mut parts = member.splitn(3, '|');
+        let node_addr = parts.next()?.to_string();
+        let conn_id = parts.next()?.to_string();
+        let device_type = DeviceType::try_from(parts.next()?.parse::<i32>().ok()?).ok()?;
+        Some(Self { node_addr, conn_id, device_type })
+    }
+}
+
+/// 集群在线路由表
+///
+/// - `presence:user:{uid}`：有序集合，成员为 `PresenceEntry`，score 为过期时间（毫秒），
+///   读取时忽略并清理已过期成员，节点宕机未注销的连接到期自然失效；
+/// - `presence:node:{node_addr}`：哈希，conn_id → `{uid}|{device_type}`，节点下线时据此批量清理。
+#[derive(Debug)]
+pub struct PresenceService {
+    pub pool: Arc<RedisPool>,
+}
+
+impl PresenceService {
+    pub fn new(pool: Arc<RedisPool>) -> Self {
+        Self { pool }
+    }
+
+    pub(crate) fn user_key(uid: &str) -> String {
+        format!("presence:user:{}", uid)
+    }
+
+    fn node_key(node_addr: &str) -> String {
+        format!("presence:node:{}", node_addr)
+    }
+
+    /// 登记连接（登录成功后）
+    pub async fn register(&self, uid: &str, entry: &PresenceEntry) -> Result<()> {
+        self.refresh(&[(uid.to_string(), entry.clone())]).await
+    }
+
+    /// 批量续期，`entries` 为 (uid, 连接)；未登记的连接同时补登记
+    pub async fn refresh(&self, entries: &[(String, PresenceEntry)]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let expire_at = now() + PRESENCE_TTL_MS;
+        let key_ttl_secs = PRESENCE_TTL_MS / 1000 * 2;
+        let mut conn = self.pool.get().await?;
+        let mut pipe = deadpool_redis::redis::pipe();
+        for (uid, entry) in entries {
+            let user_key = Self::user_key(uid);
+            pipe.cmd("ZADD").arg(&user_key).arg(expire_at).arg(entry.to_member()).ignore();
+            pipe.cmd("EXPIRE").arg(&user_key).arg(key_ttl_secs).ignore();
+            let node_key = Self::node_key(&entry.node_addr);
+            pipe.cmd("HSET").arg(&node_key).arg(&entry.conn_id).arg(format!("{}|{}", uid, entry.device_type as i32)).ignore();
+            pipe.cmd("EXPIRE").arg(&node_key).arg(key_ttl_secs).ignore();
+        }
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    /// 注销连接（断开、心跳超时、被踢下线）
+    pub async fn unregister(&self, uid: &str, entry: &PresenceEntry) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        let mut pipe = deadpool_redis::redis::pipe();
+        pipe.cmd("ZREM").arg(Self::user_key(uid)).arg(entry.to_member()).ignore();
+        pipe.cmd("HDEL").arg(Self::node_key(&entry.node_addr)).arg(&entry.conn_id).ignore();
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    /// 查询用户当前所有在线连接
+    pub async fn locate(&self, uid: &str) -> Result<Vec<PresenceEntry>> {
+        let user_key = Self::user_key(uid);
+        let now_ms = now();
+        let mut conn = self.pool.get().await?;
+        let (members,): (Vec<String>,) = deadpool_redis::redis::pipe()
+            .cmd("ZREMRANGEBYSCORE")
+            .arg(&user_key)
+            .arg("-inf")
+            .arg(now_ms)
+            .ignore()
+            .cmd("ZRANGEBYSCORE")
+            .arg(&user_key)
+            .arg(format!("({}", now_ms))
+            .arg("+inf")
+            .query_async(&mut conn)
+            .await?;
+        Ok(members.iter().filter_map(|m| PresenceEntry::from_member(m)).collect())
+    }
+
+    /// 用户所在的 socket 节点，生产者据此定向投递而非全节点广播
+    pub async fn nodes_of(&self, uid: &str) -> Result<HashSet<String>> {
+        Ok(self.locate(uid).await?.into_iter().map(|e| e.node_addr).collect())
+    }
+
+    /// 批量定位：socket 节点 → 该节点上在线的 uid（已过期的登记忽略，留待 `locate` 清理）
+    pub async fn route(&self, uids: &[String]) -> Result<HashMap<String, Vec<String>>> {
+        let routes = self.route_devices(uids).await?;
+        Ok(routes.into_iter().map(|(node, users)| (node, users.into_keys().collect())).collect())
+    }
+
+    /// 批量定位到设备：socket 节点 → (uid → 该节点上在线的设备类型)
+    pub async fn route_devices(&self, uids: &[String]) -> Result<HashMap<String, HashMap<String, Vec<DeviceType>>>> {
+        let mut routes: HashMap<String, HashMap<String, Vec<DeviceType>>> = HashMap::new();
+        if uids.is_empty() {
+            return Ok(routes);
+        }
+        let min_score = format!("({}", now());
+        let mut conn = self.pool.get().await?;
+        let mut pipe = deadpool_redis::redis::pipe();
+        for uid in uids {
+            pipe.cmd("ZRANGEBYSCORE").arg(Self::user_key(uid)).arg(&min_score).arg("+inf");
+        }
+        let members: Vec<Vec<String>> = pipe.query_async(&mut conn).await?;
+        for (uid, members) in uids.iter().zip(members) {
+            for entry in members.iter().filter_map(|m| PresenceEntry::from_member(m)) {
+                let devices = routes.entry(entry.node_addr).or_default().entry(uid.clone()).or_default();
+                if !devices.contains(&entry.device_type) {
+                    devices.push(entry.device_type);
+                }
+            }
+        }
+        Ok(routes)
+    }
+
+    /// 清理节点上的全部登记（节点优雅下线或重启时），返回清理的连接数
+    pub async fn purge_node(&self, node_addr: &str) -> Result<usize> {
+        let node_key = Self::node_key(node_addr);
+        let mut conn = self.pool.get().await?;
+        let conns: HashMap<String, String> = cmd("HGETALL").arg(&node_key).query_async(&mut conn).await?;
+        let mut pipe = deadpool_redis::redis::pipe();
+        for (conn_id, value) in &conns {
+            let Some((uid, device)) = value.split_once('|') else {
+                continue;
+            };
+            let member = format!("{}|{}|{}", node_addr, conn_id, device);
+            pipe.cmd("ZREM").arg(Self::user_key(uid)).arg(member).ignore();
+        }
+        pipe.cmd("DEL").arg(&node_key).ignore();
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(conns.len())
+    }
+
+    pub fn init() {
+        let instance = Self::new(RedisPoolTools::get().clone());
+        INSTANCE.set(Arc::new(instance)).expect("INSTANCE already initialized");
+    }
+
+    /// 获取单例
+    pub fn get() -> Arc<Self> {
+        INSTANCE.get().expect("INSTANCE is not initialized").clone()
+    }
+}
+
+static INSTANCE: OnceCell<Arc<PresenceService>> = OnceCell::new();
@@ -0,0 +1,60 @@
+use crate::entitys::read_index::ReadIndexEntity;
+use crate::protocol::common::ChatTargetType;
+use anyhow::Result;
+use common::repository_util::{BaseRepository, Repository};
+use common::util::date_util::now;
+use mongodb::bson::doc;
+use mongodb::Database;
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+
+/// 会话已读位置服务
+#[derive(Debug)]
+pub struct ReadIndexService {
+    pub dao: BaseRepository<ReadIndexEntity>,
+}
+
+impl ReadIndexService {
+    pub async fn new(db: Database) -> Self {
+        Self {
+            dao: BaseRepository::new(db, "read_index").await,
+        }
+    }
+
+    /// 推进已读序号（只增不减）
+    pub async fn update_read_seq(&self, from: &str, to: &str, target_type: ChatTargetType, read_seq: i64) -> Result<()> {
+        let filter = doc! {
+            "from": from,
+            "to": to,
+            "target_type": bson::to_bson(&target_type)?,
+        };
+        let update = doc! {
+            "$max": { "read_seq": read_seq },
+            "$set": { "updated_at": now() },
+        };
+        self.dao.collection.update_one(filter, update).upsert(true).await?;
+        Ok(())
+    }
+
+    /// 查询已读序号，未读过返回 0
+    pub async fn get_read_seq(&self, from: &str, to: &str, target_type: ChatTargetType) -> Result<i64> {
+        let filter = doc! {
+            "from": from,
+            "to": to,
+            "target_type": bson::to_bson(&target_type)?,
+        };
+        Ok(self.dao.find_one(filter).await?.map(|entity| entity.read_seq).unwrap_or(0))
+    }
+
+    pub async fn init(db: Database) {
+        let instance = Self::new(db).await;
+        INSTANCE.set(Arc::new(instance)).expect("INSTANCE already initialized");
+    }
+
+    /// 获取单例
+    pub fn get() -> Arc<Self> {
+        INSTANCE.get().expect("INSTANCE is not initialized").clone()
+    }
+}
+
+static INSTANCE: OnceCell<Arc<ReadIndexService>> = OnceCell::new();
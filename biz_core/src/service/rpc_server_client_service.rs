@@ -1,9 +1,14 @@
 use common::config::AppConfig;
 use once_cell::sync::OnceCell;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
+use crate::protocol::arb::arb_models::{BaseRequest, NodeInfo, RegRequest};
 use crate::protocol::arb::arb_server::arb_server_rpc_service_client::ArbServerRpcServiceClient;
 
+/// 向仲裁服务上报心跳的间隔，需明显小于仲裁的失联判定时间
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 pub struct ArbServerRpcServiceClientService {
     pub client: Arc<Mutex<ArbServerRpcServiceClient<tonic::transport::Channel>>>,
@@ -18,6 +23,38 @@ impl ArbServerRpcServiceClientService {
     pub fn get() -> Arc<Self> {
         INSTANCE.get().unwrap().clone()
     }
+    /// 向仲裁注册本节点并启动心跳任务，各类节点统一由此接入（分片节点的注册随其状态机进行）
+    pub async fn register(&self, reg: RegRequest) -> anyhow::Result<NodeInfo> {
+        let node = self.client.lock().await.register_node(reg.clone()).await?.into_inner();
+        Self::start_heartbeat(reg);
+        Ok(node)
+    }
+    /// 启动心跳任务：仲裁不识别本节点（被判定失联后下线、或仲裁状态丢失）时按原注册信息重新注册
+    fn start_heartbeat(reg: RegRequest) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let service = Self::get();
+                let mut client = service.client.lock().await;
+                let request = BaseRequest {
+                    node_addr: reg.node_addr.clone(),
+                    node_type: reg.node_type,
+                };
+                match client.heartbeat(request).await {
+                    Ok(response) if response.get_ref().success => {}
+                    Ok(response) => {
+                        log::warn!("⚠️ 仲裁未识别本节点 {}（{}），重新注册", reg.node_addr, response.into_inner().message);
+                        if let Err(e) = client.register_node(reg.clone()).await {
+                            log::error!("❌ 重新注册失败: {:?}", e);
+                        }
+                    }
+                    Err(e) => log::warn!("⚠️ 心跳上报失败: {:?}", e),
+                }
+            }
+        });
+    }
     pub async fn init() -> anyhow::Result<()> {
         INSTANCE.set(Arc::new(Self::new().await)).expect("Failed to set instance");
         return Ok(());
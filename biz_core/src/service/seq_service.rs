@@ -0,0 +1,143 @@
+use anyhow::{anyhow, Result};
+use mongodb::bson::{doc, Document};
+use mongodb::error::TRANSIENT_TRANSACTION_ERROR;
+use mongodb::options::ReturnDocument;
+use mongodb::{ClientSession, Collection, Database};
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+
+/// 同一会话并发分配发生事务写冲突时的最大尝试次数
+const MAX_TXN_ATTEMPTS: usize = 5;
+
+/// 会话消息序号分配服务
+///
+/// 每个群 / 每对单聊用户维护一个单调递增序号，计数器持久化在 Mongo（`msg_seq`），
+/// 通过 `$inc` 原子自增，进程重启后从库中继续递增。
+/// 消息落库使用 `insert_with_seq`，序号与消息在同一事务内提交，会话序号连续无空洞。
+#[derive(Debug)]
+pub struct SeqService {
+    pub collection: Collection<Document>,
+}
+
+impl SeqService {
+    pub async fn new(db: Database) -> Self {
+        Self {
+            collection: db.collection::<Document>("msg_seq"),
+        }
+    }
+
+    /// 群聊序号 key
+    pub fn group_key(group_id: &str) -> String {
+        format!("group:{}", group_id)
+    }
+
+    /// 单聊序号 key（双方共用同一序列，与收发方向无关）
+    pub fn single_key(uid: &str, peer_uid: &str) -> String {
+        if uid <= peer_uid { format!("single:{}:{}", uid, peer_uid) } else { format!("single:{}:{}", peer_uid, uid) }
+    }
+
+    /// 在同一事务内分配下一个序号并写入消息 `doc`（填入 `seq` 字段），返回分配的序号
+    ///
+    /// 写入失败时事务回滚、计数器不递增，下一条消息拿到的仍是紧接着的序号；
+    /// 同一会话并发写入在计数器文档上冲突，冲突方按 `TransientTransactionError` 整体重试。要求 MongoDB 以副本集部署。
+    pub async fn insert_with_seq(&self, key: &str, collection: &Collection<Document>, doc: Document) -> Result<i64> {
+        let mut session = self.collection.client().start_session().await?;
+        let mut attempt = 1;
+        loop {
+            match self.transact(&mut session, key, collection, doc.clone()).await {
+                Ok(seq) => return Ok(seq),
+                Err(e) if attempt < MAX_TXN_ATTEMPTS && is_transient(&e) => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn transact(&self, session: &mut ClientSession, key: &str, collection: &Collection<Document>, mut doc: Document) -> Result<i64> {
+        session.start_transaction().await?;
+        let result = async {
+            let counter = self
+                .collection
+                .find_one_and_update(doc! { "_id": key }, doc! { "$inc": { "seq": 1i64 } })
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+                .session(&mut *session)
+                .await?
+                .ok_or_else(|| anyhow!("seq.alloc.failed: {}", key))?;
+            let seq = counter.get_i64("seq")?;
+            doc.insert("seq", seq);
+            collection.insert_one(doc).session(&mut *session).await?;
+            Ok::<i64, anyhow::Error>(seq)
+        }
+        .await;
+        match result {
+            Ok(seq) => {
+                session.commit_transaction().await?;
+                Ok(seq)
+            }
+            Err(e) => {
+                let _ = session.abort_transaction().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// 当前已分配的最大序号，未分配过返回 0
+    pub async fn current_seq(&self, key: &str) -> Result<i64> {
+        let doc = self.collection.find_one(doc! { "_id": key }).await?;
+        Ok(doc.and_then(|d| d.get_i64("seq").ok()).unwrap_or(0))
+    }
+
+    pub async fn init(db: Database) {
+        let instance = Self::new(db).await;
+        INSTANCE.set(Arc::new(instance)).expect("INSTANCE already initialized");
+    }
+
+    /// 获取单例
+    pub fn get() -> Arc<Self> {
+        INSTANCE.get().expect("INSTANCE is not initialized").clone()
+    }
+}
+
+static INSTANCE: OnceCell<Arc<SeqService>> = OnceCell::new();
+
+/// 事务写冲突等可整体重试的错误
+fn is_transient(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<mongodb::error::Error>().is_some_and(|e| e.contains_label(TRANSIENT_TRANSACTION_ERROR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeqService;
+    use futures_util::TryStreamExt;
+    use mongodb::bson::doc;
+    use mongodb::bson::oid::ObjectId;
+    use mongodb::Client;
+
+    #[tokio::test]
+    #[ignore = "需要以副本集部署的 MongoDB，地址取自 MONGO_URL"]
+    async fn test_failed_insert_leaves_no_gap() {
+        let url = std::env::var("MONGO_URL").unwrap_or_else(|_| "mongodb://127.0.0.1:27017/?replicaSet=rs0".to_string());
+        let db = Client::with_uri_str(&url).await.unwrap().database("seq_service_test");
+        let service = SeqService::new(db.clone()).await;
+        let messages = db.collection("messages");
+        let key = format!("single:{}", ObjectId::new().to_hex());
+
+        assert_eq!(service.insert_with_seq(&key, &messages, doc! { "_id": format!("{}:1", key) }).await.unwrap(), 1);
+        // 主键重复，写入失败，序号不被占用
+        assert!(service.insert_with_seq(&key, &messages, doc! { "_id": format!("{}:1", key) }).await.is_err());
+        assert_eq!(service.current_seq(&key).await.unwrap(), 1);
+        assert_eq!(service.insert_with_seq(&key, &messages, doc! { "_id": format!("{}:2", key) }).await.unwrap(), 2);
+
+        let seqs: Vec<i64> = messages
+            .find(doc! { "_id": { "$regex": format!("^{}:", key) } })
+            .await
+            .unwrap()
+            .try_collect::<Vec<mongodb::bson::Document>>()
+            .await
+            .unwrap()
+            .iter()
+            .map(|doc| doc.get_i64("seq").unwrap())
+            .collect();
+        assert_eq!(seqs, vec![1, 2]);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::kafka_util::kafka_producer::KafkaInstanceService;
+use crate::manager::user_manager::{UserManager, UserManagerOpt};
+use crate::protocol::common::ByteMessageType;
+use crate::protocol::msg::auth::{DeviceType, OfflineStatueMsg};
+use crate::service::presence_service::{node_topic, PresenceEntry, PresenceService};
+use anyhow::{anyhow, Result};
+use common::config::{AppConfig, DeviceLoginPolicy};
+use common::redis::redis_pool::RedisPoolTools;
+use common::util::common_utils::build_snow_id;
+use common::util::date_util::now;
+use common::RedisPool;
+use deadpool_redis::redis::Script;
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 会话登记保留时长（秒），仅用于回收长期无人登录的哈希；会话是否有效以持有连接的在线登记为准
+const SESSION_TTL_SECS: i64 = 24 * 3600;
+/// 被新登录挤下线
+pub const KICK_REASON_LOGIN_ELSEWHERE: &str = "login.elsewhere";
+
+/// 按策略登记会话：OldestWins 下已有其他会话则拒绝，NewestWins 下移除并返回其他会话
+///
+/// 其他会话的连接在线登记已过期（所在节点宕机、未正常注销）时直接清除，不再阻止登录。
+const ADMIT_SCRIPT: &str = r#"
+local existing = redis.call('HGETALL', KEYS[1])
+local evicted = {}
+for i = 1, #existing, 2 do
+    local alive = false
+    if existing[i] ~= ARGV[2] then
+        local expire_at = redis.call('ZSCORE', KEYS[2], existing[i + 1])
+        alive = expire_at and tonumber(expire_at) > tonumber(ARGV[5])
+        if not alive then
+            redis.call('HDEL', KEYS[1], existing[i])
+        end
+    end
+    if alive then
+        if ARGV[1] == 'oldest_wins' then
+            return redis.error_reply('device.already.online')
+        end
+        if ARGV[1] == 'newest_wins' then
+            table.insert(evicted, existing[i])
+            table.insert(evicted, existing[i + 1])
+            redis.call('HDEL', KEYS[1], existing[i])
+        end
+    end
+end
+redis.call('HSET', KEYS[1], ARGV[2], ARGV[3])
+redis.call('EXPIRE', KEYS[1], ARGV[4])
+return evicted
+"#;
+
+/// 仅当会话仍属于该连接时注销（同一 token 可能已在新连接上恢复登录）
+const UNREGISTER_SCRIPT: &str = r#"
+if redis.call('HGET', KEYS[1], ARGV[1]) == ARGV[2] then
+    return redis.call('HDEL', KEYS[1], ARGV[1])
+end
+return 0
+"#;
+
+/// 多端登录会话服务
+///
+/// 每个 (uid, 设备类型) 一个 Redis 哈希（`session:{uid}:{device_type}`），token → 连接的在线登记成员，
+/// 登录时按设备类型策略原子登记；被挤下线的会话吊销 token，并通过 Kafka 通知所在 socket 节点断开连接。
+/// 调用方需先登记新连接的在线状态，已有会话是否存活按其连接的在线登记判断。
+#[derive(Debug)]
+pub struct SessionService {
+    pub pool: Arc<RedisPool>,
+}
+
+impl SessionService {
+    pub fn new(pool: Arc<RedisPool>) -> Self {
+        Self { pool }
+    }
+
+    fn session_key(uid: &str, device_type: &DeviceType) -> String {
+        format!("session:{}:{}", uid, *device_type as i32)
+    }
+
+    /// 设备类型对应的登录策略（默认移动端、桌面端新登录挤掉旧会话，Web 允许多开）
+    pub fn policy(device_type: &DeviceType) -> DeviceLoginPolicy {
+        let cfg = AppConfig::get().get_login_policy();
+        match device_type {
+            DeviceType::Mobile => cfg.mobile.unwrap_or(DeviceLoginPolicy::NewestWins),
+            DeviceType::Desktop => cfg.desktop.unwrap_or(DeviceLoginPolicy::NewestWins),
+            DeviceType::Web => cfg.web.unwrap_or(DeviceLoginPolicy::AllowMany),
+            _ => DeviceLoginPolicy::AllowMany,
+        }
+    }
+
+    /// 登记会话，返回被挤下线的会话（token → 连接）；OldestWins 下已有存活会话时返回错误
+    pub async fn admit(&self, uid: &str, device_type: &DeviceType, token: &str, entry: &PresenceEntry) -> Result<HashMap<String, PresenceEntry>> {
+        let policy = match Self::policy(device_type) {
+            DeviceLoginPolicy::AllowMany => "allow_many",
+            DeviceLoginPolicy::NewestWins => "newest_wins",
+            DeviceLoginPolicy::OldestWins => "oldest_wins",
+        };
+        let mut conn = self.pool.get().await?;
+        let evicted: Vec<String> = Script::new(ADMIT_SCRIPT)
+            .key(Self::session_key(uid, device_type))
+            .key(PresenceService::user_key(uid))
+            .arg(policy)
+            .arg(token)
+            .arg(entry.to_member())
+            .arg(SESSION_TTL_SECS)
+            .arg(now())
+            .invoke_async(&mut conn)
+            .await
+            .map_err(|e| match e.detail() {
+                Some(detail) if detail.contains("device.already.online") => anyhow!("device.already.online"),
+                _ => anyhow!(e),
+            })?;
+        Ok(evicted
+            .chunks_exact(2)
+            .filter_map(|pair| PresenceEntry::from_member(&pair[1]).map(|entry| (pair[0].clone(), entry)))
+            .collect())
+    }
+
+    /// 连接断开时注销会话
+    pub async fn unregister(&self, uid: &str, device_type: &DeviceType, token: &str, entry: &PresenceEntry) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        let _: i64 = Script::new(UNREGISTER_SCRIPT)
+            .key(Self::session_key(uid, device_type))
+            .arg(token)
+            .arg(entry.to_member())
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// 踢下线：吊销 token，并向持有该连接的 socket 节点发送下线通知，由其推送给客户端后断开
+    pub async fn kick(&self, uid: &str, device_type: &DeviceType, token: &str, entry: &PresenceEntry, reason: &str) -> Result<()> {
+        UserManager::get().delete_token(token).await?;
+        let msg = OfflineStatueMsg {
+            message_id: build_snow_id(),
+            uid: uid.to_string(),
+            device_type: *device_type as i32,
+            client_id: entry.conn_id.clone(),
+            logout_time: now(),
+            reason: reason.to_string(),
+        };
+        let topic = node_topic(&AppConfig::get().get_kafka().topic_single, &entry.node_addr);
+        KafkaInstanceService::get()
+            .send_proto(&ByteMessageType::OfflineStatusMsgType, &msg, &msg.message_id, &topic)
+            .await?;
+        log::warn!("👢 会话被踢下线 uid={} device={:?} conn={} reason={}", uid, device_type, entry.conn_id, reason);
+        Ok(())
+    }
+
+    pub fn init() {
+        let pool = RedisPoolTools::get().clone();
+        INSTANCE.set(Arc::new(Self::new(pool))).expect("INSTANCE already initialized");
+    }
+
+    /// 获取单例
+    pub fn get() -> Arc<Self> {
+        INSTANCE.get().expect("INSTANCE is not initialized").clone()
+    }
+}
+
+static INSTANCE: OnceCell<Arc<SessionService>> = OnceCell::new();
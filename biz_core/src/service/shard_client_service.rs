@@ -0,0 +1,350 @@
+use crate::consts::shard_const::{SHARD_EPOCH_AHEAD, SHARD_EPOCH_HEADER, SHARD_GROUP_MIGRATING, SHARD_MOVED, SHARD_OWNER_HEADER};
+use crate::protocol::arb::arb_models::{NodeInfo, NodeType, QueryNodeReq};
+use crate::protocol::arb::shard_service::shard_rpc_service_client::ShardRpcServiceClient;
+use crate::protocol::arb::shard_service::{CheckCanSpeakReq, CheckCanSpeakResp};
+use crate::protocol::common::IdReq;
+use crate::service::rpc_server_client_service::ArbServerRpcServiceClientService;
+use common::util::hash_ring::{HashRing, DEFAULT_VIRTUAL_NODES};
+use dashmap::DashMap;
+use futures_util::future::join_all;
+use once_cell::sync::OnceCell;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tonic::metadata::MetadataValue;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Response, Status};
+
+/// 单次调用最多尝试次数（含重定向与重试）
+const MAX_ATTEMPTS: u32 = 3;
+/// 分片节点要求稍后重试时的退避基数，按尝试次数递增
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// 携带分片纪元的分片节点客户端
+pub type ShardRpcClient = ShardRpcServiceClient<InterceptedService<Channel, ShardEpochInterceptor>>;
+
+/// 每次调用在元数据中携带本节点所见的分片纪元，分片节点据此拒绝过期路由
+#[derive(Debug, Clone)]
+pub struct ShardEpochInterceptor {
+    epoch: Arc<AtomicU64>,
+}
+
+impl Interceptor for ShardEpochInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        request.metadata_mut().insert(SHARD_EPOCH_HEADER, MetadataValue::from(self.epoch.load(Ordering::Acquire)));
+        Ok(request)
+    }
+}
+
+/// 路由表：分片节点构成的一致性哈希环及其纪元
+#[derive(Debug)]
+struct ShardRouting {
+    ring: HashRing,
+    epoch: u64,
+}
+
+/// 分片节点的拒绝如何处理
+enum Retry {
+    /// 重定向到归属节点（附带其纪元）
+    Redirect(String, u64),
+    /// 退避后按路由表重试
+    Later,
+    /// 节点不可达：刷新路由表后重试（仅幂等调用）
+    Reroute,
+}
+
+/// 按用户查询群组的结果：部分分片节点查询失败时仍返回其余节点的结果
+#[derive(Debug, Default)]
+pub struct UserGroups {
+    pub group_ids: Vec<String>,
+    /// 查询失败的分片节点，其上的群组不在结果中
+    pub failed_nodes: Vec<String>,
+}
+
+/// 群分片客户端：维护仲裁下发的路由表（`list_all_nodes` 拉取、`flush_nodes` 推送时更新），
+/// 按 group_id 路由到归属节点并复用连接；遇到重定向或纪元不一致时自动重试，
+/// 按用户的查询（如 `GetUserGroups`）向全部分片节点并发查询后合并。
+#[derive(Debug)]
+pub struct ShardClientService {
+    routing: RwLock<Arc<ShardRouting>>,
+    /// node_addr -> Channel（惰性连接，多个客户端共用）
+    channels: DashMap<String, Channel>,
+    /// 路由表对应的分片纪元，由拦截器附加到每次调用
+    epoch: Arc<AtomicU64>,
+}
+
+impl ShardClientService {
+    fn new() -> Self {
+        Self {
+            routing: RwLock::new(Arc::new(ShardRouting {
+                ring: HashRing::new(DEFAULT_VIRTUAL_NODES),
+                epoch: 0,
+            })),
+            channels: DashMap::new(),
+            epoch: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// 当前路由表的分片纪元
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    /// 推进调用时携带的分片纪元（只接受更大的纪元），分片节点据此以自身受理的纪元转发请求
+    pub fn observe_epoch(&self, epoch: u64) {
+        self.epoch.fetch_max(epoch, Ordering::AcqRel);
+    }
+
+    /// 按仲裁返回的分片节点列表及虚拟节点数更新路由表，纪元更旧的（乱序到达的推送）忽略
+    pub fn update_routing(&self, nodes: Vec<NodeInfo>, epoch: u64, virtual_nodes: u32) {
+        let ring = HashRing::from_arb(virtual_nodes, nodes.into_iter().map(|node| node.node_addr));
+        let mut routing = self.routing.write().unwrap();
+        if epoch < routing.epoch {
+            return;
+        }
+        self.channels.retain(|node_addr, _| ring.contains_node(node_addr));
+        log::info!("🧭 分片路由表更新: {} 个节点, 纪元 {}", ring.len(), epoch);
+        *routing = Arc::new(ShardRouting { ring, epoch });
+        self.epoch.fetch_max(epoch, Ordering::AcqRel);
+    }
+
+    /// 从仲裁拉取分片节点列表并更新路由表
+    pub async fn refresh(&self) -> anyhow::Result<()> {
+        let response = {
+            let service = ArbServerRpcServiceClientService::get();
+            let mut client = service.client.lock().await;
+            client
+                .list_all_nodes(QueryNodeReq {
+                    node_type: NodeType::GroupNode as i32,
+                })
+                .await?
+                .into_inner()
+        };
+        self.update_routing(response.nodes, response.shard_epoch, response.virtual_nodes);
+        Ok(())
+    }
+
+    /// 当前路由表，尚未拉取过时先从仲裁拉取
+    async fn routing(&self) -> Result<Arc<ShardRouting>, Status> {
+        let routing = self.routing.read().unwrap().clone();
+        if !routing.ring.is_empty() {
+            return Ok(routing);
+        }
+        self.refresh().await.map_err(|e| Status::unavailable(format!("shard.routing.refresh: {}", e)))?;
+        Ok(self.routing.read().unwrap().clone())
+    }
+
+    /// group_id 的归属节点
+    pub async fn owner(&self, group_id: &str) -> Result<String, Status> {
+        let routing = self.routing().await?;
+        routing.ring.locate(group_id).map(str::to_string).ok_or_else(|| Status::unavailable("shard.node.empty"))
+    }
+
+    /// 到指定节点的连接（惰性连接，首次调用时建立）
+    pub fn channel(&self, node_addr: &str) -> Result<Channel, Status> {
+        if let Some(channel) = self.channels.get(node_addr) {
+            return Ok(channel.clone());
+        }
+        let channel = Channel::from_shared(format!("http://{}", node_addr)).map_err(|e| Status::invalid_argument(e.to_string()))?.connect_lazy();
+        self.channels.insert(node_addr.to_string(), channel.clone());
+        Ok(channel)
+    }
+
+    /// 指定节点的分片客户端
+    pub fn client(&self, node_addr: &str) -> Result<ShardRpcClient, Status> {
+        Ok(ShardRpcServiceClient::with_interceptor(
+            self.channel(node_addr)?,
+            ShardEpochInterceptor {
+                epoch: self.epoch.clone(),
+            },
+        ))
+    }
+
+    /// 调用 group_id 归属节点：按重定向直接改投归属节点，节点要求稍后重试时退避重试；
+    /// 只重试分片节点明确拒绝（未执行）的请求，其余错误直接返回
+    ///
+    /// `f` 可能被调用多次，每次传入当次目标节点的客户端。
+    pub async fn call<T, F, Fut>(&self, group_id: &str, f: F) -> Result<T, Status>
+    where
+        F: FnMut(ShardRpcClient) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        self.invoke(group_id, false, f).await
+    }
+
+    /// 同 `call`，用于幂等调用（查询）：节点不可达时另外刷新路由表后重试
+    pub async fn call_idempotent<T, F, Fut>(&self, group_id: &str, f: F) -> Result<T, Status>
+    where
+        F: FnMut(ShardRpcClient) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        self.invoke(group_id, true, f).await
+    }
+
+    async fn invoke<T, F, Fut>(&self, group_id: &str, idempotent: bool, mut f: F) -> Result<T, Status>
+    where
+        F: FnMut(ShardRpcClient) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        let mut redirect: Option<String> = None;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let node_addr = match redirect.take() {
+                Some(node_addr) => node_addr,
+                None => self.owner(group_id).await?,
+            };
+            let status = match f(self.client(&node_addr)?).await {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(status) => status,
+            };
+            let Some(retry) = Self::classify(&status, idempotent) else {
+                return Err(status);
+            };
+            if attempt >= MAX_ATTEMPTS {
+                log::warn!("⚠️ 分片调用 group_id={} 重试 {} 次仍失败: {}", group_id, attempt, status.message());
+                return Err(status);
+            }
+            match retry {
+                Retry::Redirect(owner, epoch) => {
+                    if epoch > self.epoch() {
+                        // 本节点路由表过期
+                        if let Err(e) = self.refresh().await {
+                            log::warn!("⚠️ 刷新分片路由表失败: {:?}", e);
+                        }
+                    }
+                    redirect = Some(owner);
+                }
+                Retry::Later => tokio::time::sleep(RETRY_BACKOFF * attempt).await,
+                Retry::Reroute => {
+                    if let Err(e) = self.refresh().await {
+                        log::warn!("⚠️ 刷新分片路由表失败: {:?}", e);
+                    }
+                    tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+                }
+            }
+        }
+    }
+
+    /// 查询用户所在的群组：群组按 group_id 分布在各分片节点，向全部节点并发查询后合并去重
+    ///
+    /// 单个节点失败不影响其余节点的结果，失败的节点随结果返回，由调用方决定降级方式。
+    pub async fn get_user_groups(&self, uid: &str) -> Result<UserGroups, Status> {
+        let routing = self.routing().await?;
+        let requests = routing.ring.nodes().map(|node_addr| {
+            let client = self.client(node_addr);
+            let request = IdReq {
+                ref_id: uid.to_string(),
+            };
+            async move {
+                let response = match client {
+                    Ok(mut client) => client.get_user_groups(request).await,
+                    Err(status) => Err(status),
+                };
+                (node_addr, response)
+            }
+        });
+        let mut result = UserGroups::default();
+        for (node_addr, response) in join_all(requests).await {
+            match response {
+                Ok(response) => result.group_ids.extend(response.into_inner().group_ids),
+                Err(status) => {
+                    log::warn!("⚠️ 分片节点 {} 查询用户 {} 的群组失败: {}", node_addr, uid, status.message());
+                    result.failed_nodes.push(node_addr.clone());
+                }
+            }
+        }
+        // 迁移期间同一群组可能同时出现在新旧归属节点
+        result.group_ids.sort();
+        result.group_ids.dedup();
+        Ok(result)
+    }
+
+    /// 成员能否在群内发言：只由归属分片节点从内存判断，不查询数据库
+    ///
+    /// 归属变化、纪元领先等拒绝按分片路由规则重试；仍然失败时拒绝发言，由客户端稍后重发。
+    pub async fn check_can_speak(&self, group_id: &str, uid: &str) -> CheckCanSpeakResp {
+        let request = CheckCanSpeakReq {
+            group_id: group_id.to_string(),
+            user_id: uid.to_string(),
+        };
+        match self
+            .call_idempotent(group_id, |mut client| {
+                let request = request.clone();
+                async move { client.check_can_speak(request).await }
+            })
+            .await
+        {
+            Ok(resp) => resp,
+            Err(status) => {
+                log::warn!("⚠️ 分片判断发言权限失败 group_id={} uid={}: {}", group_id, uid, status.message());
+                CheckCanSpeakResp {
+                    can_speak: false,
+                    reason: "group.shard.unavailable".to_string(),
+                    mute_until: 0,
+                }
+            }
+        }
+    }
+
+    /// 分片节点的拒绝是否可重试
+    fn classify(status: &Status, idempotent: bool) -> Option<Retry> {
+        match (status.code(), status.message()) {
+            (Code::FailedPrecondition, SHARD_MOVED) => {
+                let metadata = status.metadata();
+                let owner = metadata.get(SHARD_OWNER_HEADER)?.to_str().ok()?.to_string();
+                let epoch = metadata.get(SHARD_EPOCH_HEADER).and_then(|value| value.to_str().ok()).and_then(|value| value.parse().ok()).unwrap_or(0);
+                Some(Retry::Redirect(owner, epoch))
+            }
+            (Code::Unavailable, SHARD_EPOCH_AHEAD | SHARD_GROUP_MIGRATING) => Some(Retry::Later),
+            // 其他不可用无法确定请求是否已执行，非幂等调用重试可能重复写入
+            (Code::Unavailable, _) if idempotent => Some(Retry::Reroute),
+            _ => None,
+        }
+    }
+
+    pub fn init() {
+        if INSTANCE.get().is_some() {
+            return;
+        }
+        INSTANCE.set(Arc::new(Self::new())).expect("INSTANCE already initialized");
+    }
+
+    /// 获取单例
+    pub fn get() -> Arc<Self> {
+        INSTANCE.get().expect("INSTANCE is not initialized").clone()
+    }
+}
+
+static INSTANCE: OnceCell<Arc<ShardClientService>> = OnceCell::new();
+
+#[cfg(test)]
+mod tests {
+    use super::{Retry, ShardClientService};
+    use crate::consts::shard_const::{SHARD_EPOCH_AHEAD, SHARD_EPOCH_HEADER, SHARD_MOVED, SHARD_OWNER_HEADER};
+    use tonic::metadata::{MetadataMap, MetadataValue};
+    use tonic::{Code, Status};
+
+    #[test]
+    fn test_classify_shard_rejections() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(SHARD_OWNER_HEADER, MetadataValue::from_static("10.0.0.2:9000"));
+        metadata.insert(SHARD_EPOCH_HEADER, MetadataValue::from(7u64));
+        let moved = Status::with_metadata(Code::FailedPrecondition, SHARD_MOVED, metadata);
+        assert!(matches!(ShardClientService::classify(&moved, false), Some(Retry::Redirect(owner, 7)) if owner == "10.0.0.2:9000"));
+
+        // 缺少归属节点的重定向无法处理
+        let moved_without_owner = Status::failed_precondition(SHARD_MOVED);
+        assert!(ShardClientService::classify(&moved_without_owner, false).is_none());
+
+        assert!(matches!(ShardClientService::classify(&Status::unavailable(SHARD_EPOCH_AHEAD), false), Some(Retry::Later)));
+        // 节点不可达只对幂等调用改投
+        let refused = Status::unavailable("connection refused");
+        assert!(matches!(ShardClientService::classify(&refused, true), Some(Retry::Reroute)));
+        assert!(ShardClientService::classify(&refused, false).is_none());
+        assert!(ShardClientService::classify(&Status::internal("boom"), true).is_none());
+    }
+}
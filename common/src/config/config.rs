@@ -18,11 +18,20 @@ pub struct AppConfig {
     pub kafka: Option<KafkaConfig>,
     pub shard: Option<ShardConfig>,
     pub socket: Option<SocketConfig>,
+    pub login_policy: Option<LoginPolicyConfig>,
+    pub arb: Option<ArbConfig>,
 }
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct ShardConfig {
     pub client_addr: Option<String>,
     pub server_addr: Option<String>,
+    /// 一致性哈希环每个节点的虚拟节点数（默认 160）；只由仲裁读取，
+    /// 经 `ListAllNodesResponse` / `UpdateVersionReq` 下发，其余服务按下发值建环
+    pub virtual_nodes: Option<usize>,
+    /// 群组成员本地快照及变更日志目录，未配置时不落盘（重启全量从数据库加载）
+    pub data_dir: Option<String>,
+    /// 本地快照间隔（秒，默认 300），重启后只需对账快照之后变化的群组
+    pub snapshot_interval_secs: Option<u64>,
 }
 impl AppConfig {
     pub fn new(file: &String) -> Self {
@@ -76,6 +85,12 @@ impl AppConfig {
     pub fn get_shard(&self) -> ShardConfig {
         self.shard.clone().unwrap_or_default()
     }
+    pub fn get_login_policy(&self) -> LoginPolicyConfig {
+        self.login_policy.clone().unwrap_or_default()
+    }
+    pub fn get_arb(&self) -> ArbConfig {
+        self.arb.clone().unwrap_or_default()
+    }
     /// 获取单例
     pub fn get() -> Arc<Self> {
         INSTANCE.get().expect("INSTANCE is not initialized").clone()
@@ -129,4 +144,49 @@ pub struct KafkaConfig {
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct SocketConfig {
     pub node_addr: String,
+    /// WebSocket 监听地址（Web 端接入），未配置则不启动
+    pub ws_addr: Option<String>,
+    /// TLS 配置，未配置则使用明文（TCP 与 WebSocket 监听共用）
+    pub tls: Option<SocketTlsConfig>,
+}
+/// 同一设备类型多会话登录策略
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceLoginPolicy {
+    /// 允许同时在线多个会话
+    AllowMany,
+    /// 新登录挤掉旧会话
+    NewestWins,
+    /// 已有会话在线时拒绝新登录
+    OldestWins,
+}
+/// 按设备类型配置登录策略，未配置的设备类型使用默认值
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct LoginPolicyConfig {
+    pub mobile: Option<DeviceLoginPolicy>,
+    pub desktop: Option<DeviceLoginPolicy>,
+    pub web: Option<DeviceLoginPolicy>,
+}
+/// 仲裁服务主备配置：多个实例通过 Redis 租约选主，仅主实例对外提供服务
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ArbConfig {
+    /// 实例标识，默认 `{server_addr}-{pid}`
+    pub instance_id: Option<String>,
+    /// 主实例租约时长（毫秒），默认 10000
+    pub lease_ttl_ms: Option<u64>,
+}
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct SocketTlsConfig {
+    /// 服务端证书链（PEM）
+    pub cert_path: String,
+    /// 服务端私钥（PEM）
+    pub key_path: String,
+    /// 客户端证书 CA（PEM），配置后启用客户端证书校验（内部机器人）；
+    /// 携带证书的连接只能登录证书 CN 或 SAN DNS 名称与之相同的 uid
+    pub client_ca_path: Option<String>,
+    /// 是否强制要求客户端证书，默认 false（未携带证书的普通客户端仍可连接）
+    #[serde(default)]
+    pub client_cert_required: bool,
+    /// 证书文件变更检查间隔（秒），默认 30
+    pub reload_interval_secs: Option<u64>,
 }
@@ -0,0 +1,249 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::Hasher;
+use twox_hash::XxHash64;
+
+/// 默认每个物理节点的虚拟节点数（仲裁未配置或未下发时使用）
+pub const DEFAULT_VIRTUAL_NODES: usize = 160;
+
+/// 哈希环上的一段闭区间 `[start, end]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl HashRange {
+    pub fn contains(&self, hash: u64) -> bool {
+        self.start <= hash && hash <= self.end
+    }
+}
+
+/// 成员变更后归属发生变化的区间
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeMove {
+    pub range: HashRange,
+    /// 原归属节点（原环为空时为 None）
+    pub from: Option<String>,
+    /// 新归属节点（新环为空时为 None）
+    pub to: Option<String>,
+}
+
+/// 带虚拟节点的一致性哈希环
+///
+/// 每个物理节点在环上放置 `virtual_nodes` 个点（`{node}#{i}` 的哈希），
+/// key 归属于顺时针方向第一个点所属的节点；增删节点只影响相邻区间。
+/// 虚拟节点数以仲裁配置为准：仲裁随节点列表下发，各方用 `from_arb` 按下发值建环。
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    virtual_nodes: usize,
+    /// 环上的点 → 物理节点
+    ring: BTreeMap<u64, String>,
+    nodes: BTreeSet<String>,
+}
+
+impl HashRing {
+    pub fn new(virtual_nodes: usize) -> Self {
+        Self {
+            virtual_nodes: virtual_nodes.max(1),
+            ring: BTreeMap::new(),
+            nodes: BTreeSet::new(),
+        }
+    }
+
+    pub fn with_nodes<I, S>(virtual_nodes: usize, nodes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut ring = Self::new(virtual_nodes);
+        for node in nodes {
+            ring.add_node(node);
+        }
+        ring
+    }
+
+    /// 按仲裁下发的虚拟节点数构建，为 0（旧版本仲裁未下发）时取 `DEFAULT_VIRTUAL_NODES`
+    pub fn from_arb<I, S>(virtual_nodes: u32, nodes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let virtual_nodes = if virtual_nodes == 0 { DEFAULT_VIRTUAL_NODES } else { virtual_nodes as usize };
+        Self::with_nodes(virtual_nodes, nodes)
+    }
+
+    /// key 在环上的位置：对 UTF-8 字节做 XxHash64（种子 0），不依赖 `Hash` 实现附加的分隔符，跨版本、跨语言一致
+    pub fn hash(key: &str) -> u64 {
+        let mut hasher = XxHash64::with_seed(0);
+        hasher.write(key.as_bytes());
+        hasher.finish()
+    }
+
+    pub fn add_node(&mut self, node: impl Into<String>) {
+        let node = node.into();
+        if !self.nodes.insert(node.clone()) {
+            return;
+        }
+        for i in 0..self.virtual_nodes {
+            // 极少数点冲突时保留字典序较小的节点，保证各方构建结果一致
+            let point = Self::hash(&format!("{}#{}", node, i));
+            match self.ring.get(&point) {
+                Some(owner) if owner <= &node => {}
+                _ => {
+                    self.ring.insert(point, node.clone());
+                }
+            }
+        }
+    }
+
+    pub fn remove_node(&mut self, node: &str) {
+        if !self.nodes.remove(node) {
+            return;
+        }
+        let nodes: Vec<String> = self.nodes.iter().cloned().collect();
+        *self = Self::with_nodes(self.virtual_nodes, nodes);
+    }
+
+    pub fn virtual_nodes(&self) -> usize {
+        self.virtual_nodes
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &String> {
+        self.nodes.iter()
+    }
+
+    pub fn contains_node(&self, node: &str) -> bool {
+        self.nodes.contains(node)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// key 归属的节点，环为空时返回 None
+    pub fn locate(&self, key: &str) -> Option<&str> {
+        self.locate_hash(Self::hash(key))
+    }
+
+    pub fn locate_hash(&self, hash: u64) -> Option<&str> {
+        self.ring.range(hash..).next().or_else(|| self.ring.iter().next()).map(|(_, node)| node.as_str())
+    }
+
+    /// 节点负责的全部区间（已合并相邻区间）
+    pub fn ranges_of(&self, node: &str) -> Vec<HashRange> {
+        Self::segments(self.ring.keys().copied())
+            .into_iter()
+            .filter(|range| self.locate_hash(range.end) == Some(node))
+            .fold(Vec::new(), merge_adjacent)
+    }
+
+    /// 从当前环切换到 `next` 时归属发生变化的区间（相邻且迁移方向相同的区间已合并）
+    pub fn diff(&self, next: &HashRing) -> Vec<RangeMove> {
+        let points: BTreeSet<u64> = self.ring.keys().chain(next.ring.keys()).copied().collect();
+        let mut moves: Vec<RangeMove> = Vec::new();
+        for range in Self::segments(points.into_iter()) {
+            let from = self.locate_hash(range.end);
+            let to = next.locate_hash(range.end);
+            if from == to {
+                continue;
+            }
+            match moves.last_mut() {
+                Some(last) if last.range.end.wrapping_add(1) == range.start && last.from.as_deref() == from && last.to.as_deref() == to => {
+                    last.range.end = range.end;
+                }
+                _ => moves.push(RangeMove {
+                    range,
+                    from: from.map(str::to_string),
+                    to: to.map(str::to_string),
+                }),
+            }
+        }
+        moves
+    }
+
+    /// 以有序点切分整个哈希空间：`[0, p0]`、`(p0, p1]`……`(pn, u64::MAX]`，
+    /// 每段的归属即其右端点的归属
+    fn segments(points: impl Iterator<Item = u64>) -> Vec<HashRange> {
+        let mut segments = Vec::new();
+        let mut start = 0u64;
+        for point in points {
+            segments.push(HashRange { start, end: point });
+            if point == u64::MAX {
+                return segments;
+            }
+            start = point + 1;
+        }
+        segments.push(HashRange { start, end: u64::MAX });
+        segments
+    }
+}
+
+fn merge_adjacent(mut ranges: Vec<HashRange>, range: HashRange) -> Vec<HashRange> {
+    match ranges.last_mut() {
+        Some(last) if last.end.wrapping_add(1) == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashRing;
+
+    fn nodes(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("127.0.0.1:{}", 50000 + i)).collect()
+    }
+
+    #[test]
+    fn test_hash_is_xxhash64_of_raw_bytes() {
+        assert_eq!(HashRing::hash(""), 0xef46_db37_51d8_e999);
+        assert_eq!(HashRing::hash("abc"), 0x44bc_2cf5_ad77_0999);
+    }
+
+    #[test]
+    fn test_from_arb_defaults_virtual_nodes() {
+        assert_eq!(HashRing::from_arb(0, nodes(2)).virtual_nodes(), super::DEFAULT_VIRTUAL_NODES);
+        assert_eq!(HashRing::from_arb(64, nodes(2)).virtual_nodes(), 64);
+    }
+
+    #[test]
+    fn test_every_node_owns_keys() {
+        let ring = HashRing::with_nodes(160, nodes(4));
+        let mut counts = std::collections::HashMap::new();
+        for i in 0..10_000 {
+            *counts.entry(ring.locate(&format!("group-{}", i)).unwrap().to_string()).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 4);
+        assert!(counts.values().all(|c| *c > 1_000));
+    }
+
+    #[test]
+    fn test_diff_matches_moved_keys() {
+        let before = HashRing::with_nodes(64, nodes(3));
+        let after = HashRing::with_nodes(64, nodes(4));
+        let moves = before.diff(&after);
+        let new_node = nodes(4).pop().unwrap();
+        assert!(moves.iter().all(|m| m.to.as_deref() == Some(new_node.as_str())));
+
+        for i in 0..5_000 {
+            let key = format!("group-{}", i);
+            let hash = HashRing::hash(&key);
+            let moved = before.locate(&key) != after.locate(&key);
+            assert_eq!(moved, moves.iter().any(|m| m.range.contains(hash)));
+        }
+    }
+
+    #[test]
+    fn test_remove_node_restores_ring() {
+        let mut ring = HashRing::with_nodes(32, nodes(3));
+        ring.add_node("127.0.0.1:60000");
+        ring.remove_node("127.0.0.1:60000");
+        let expected = HashRing::with_nodes(32, nodes(3));
+        assert!(ring.diff(&expected).is_empty());
+        assert_eq!(ring.ranges_of("127.0.0.1:50000"), expected.ranges_of("127.0.0.1:50000"));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod common_utils;
 pub mod date_util;
+pub mod hash_ring;
 pub mod validate;
@@ -0,0 +1,8 @@
+// group_member 集合的索引与集合选项，部署或升级时由运维执行一次：
+//   mongosh "mongodb://<host>/<db>" scripts/mongo/group_member.js
+
+// 分片节点重启对账按 update_time 查询快照之后有变化的群组
+db.group_member.createIndex({ update_time: 1 }, { name: "idx_update_time" });
+
+// 分片节点跟随变更流时依赖删除前镜像定位被删除成员所属的群组（需 MongoDB 6.0+ 副本集）
+db.runCommand({ collMod: "group_member", changeStreamPreAndPostImages: { enabled: true } });